@@ -1,9 +1,81 @@
-use rusqlite::{Connection, params};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use r2d2::{Pool, PooledConnection};
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::Value;
+use rusqlite::{Connection, ErrorCode, OpenFlags, OptionalExtension, params, params_from_iter};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
-use chrono::Utc;
+use crate::history::{Entry, History};
+use crate::rank;
+use crate::util::sibling_path;
+use crate::recurrence::{next_due_date, RecurrenceRule};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, TimeZone, Utc};
+
+/// Task priority, stored as an integer so it sorts naturally in SQL
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+impl Priority {
+    pub fn as_i32(self) -> i32 {
+        match self {
+            Priority::None => 0,
+            Priority::Low => 1,
+            Priority::Medium => 2,
+            Priority::High => 3,
+        }
+    }
+    
+    /// Lowercase name, empty for `None`
+    pub fn label(self) -> &'static str {
+        match self {
+            Priority::None => "",
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+        }
+    }
+    
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(Priority::None),
+            1 => Some(Priority::Low),
+            2 => Some(Priority::Medium),
+            3 => Some(Priority::High),
+            _ => None,
+        }
+    }
+}
+
+impl ToSql for Priority {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_i32()))
+    }
+}
+
+impl FromSql for Priority {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let raw = i64::column_result(value)?;
+        i32::try_from(raw)
+            .ok()
+            .and_then(Priority::from_i32)
+            .ok_or(FromSqlError::OutOfRange(raw))
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -15,27 +87,4795 @@ pub struct Task {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     pub completed: bool,
+    /// When the task was last completed; cleared when it's reopened
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub due_date: Option<String>,
     pub updated_at: i64,
-    pub order: i32,
+    /// Set once when the task is created; edits never change it
+    #[serde(default)]
+    pub created_at: i64,
+    /// Position in the list; see `rank::between`
+    #[serde(default)]
+    pub rank: String,
     #[serde(default)]
     pub deleted: bool,
+    /// Hidden from the main list but kept, unlike `deleted`
+    #[serde(default)]
+    pub archived: bool,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    /// iCal RRULE describing how the task repeats, e.g. `FREQ=WEEKLY;BYDAY=SA`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<String>,
+    /// The task this one is a subtask of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<String>,
+    /// The project (list) this task belongs to; `None` is the inbox
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    /// Long-form notes, separate from the one-line `description`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Chip color as `#rrggbb`; see `validate_color`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// How long the task is expected to take, for planning the day
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub estimate_minutes: Option<i32>,
+    /// When each field was last edited, keyed by its JSON name, so edits to
+    /// different fields on two devices merge instead of the newer task winning
+    /// outright. Fields untouched since the task was created have no entry.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub field_updated_at: BTreeMap<String, i64>,
 }
 
-pub struct Database {
-    conn: Mutex<Connection>,
+/// A file or link referenced by a task. This is metadata only: `path` points at
+/// something on the device that attached it, and the file contents are never
+/// stored in the database or synced, so other devices may not be able to open it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub name: String,
+    /// Local file path or URL
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+}
+
+/// Fields supplied when creating a task; everything else is generated
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub due_date: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub priority: Priority,
+    pub recurrence: Option<String>,
+    pub parent_id: Option<String>,
+    pub project_id: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    pub color: Option<String>,
+    pub estimate_minutes: Option<i32>,
+}
+
+/// Change to one nullable field of a `TaskPatch`. In JSON a missing field keeps
+/// the current value, `null` clears it and anything else sets it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum FieldUpdate<T> {
+    #[default]
+    Keep,
+    Set(T),
+    Clear,
+}
+
+impl<T> FieldUpdate<T> {
+    fn apply(self, current: &mut Option<T>) {
+        match self {
+            FieldUpdate::Keep => {}
+            FieldUpdate::Set(value) => *current = Some(value),
+            FieldUpdate::Clear => *current = None,
+        }
+    }
+}
+
+// Only reached for fields that are present, so `Keep` comes from `#[serde(default)]`
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for FieldUpdate<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<T>::deserialize(deserializer)? {
+            Some(value) => FieldUpdate::Set(value),
+            None => FieldUpdate::Clear,
+        })
+    }
+}
+
+/// Partial edit for `update_task_fields`; fields left out aren't touched
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskPatch {
+    pub title: Option<String>,
+    #[serde(default)]
+    pub description: FieldUpdate<String>,
+    #[serde(default)]
+    pub due_date: FieldUpdate<String>,
+    pub tags: Option<Vec<String>>,
+    pub priority: Option<Priority>,
+    #[serde(default)]
+    pub recurrence: FieldUpdate<String>,
+    #[serde(default)]
+    pub parent_id: FieldUpdate<String>,
+    #[serde(default)]
+    pub project_id: FieldUpdate<String>,
+    #[serde(default)]
+    pub notes: FieldUpdate<String>,
+    #[serde(default)]
+    pub color: FieldUpdate<String>,
+    #[serde(default)]
+    pub estimate_minutes: FieldUpdate<i32>,
+}
+
+impl TaskPatch {
+    fn apply(self, task: &mut Task) {
+        if let Some(title) = self.title {
+            task.title = title;
+        }
+        self.description.apply(&mut task.description);
+        self.due_date.apply(&mut task.due_date);
+        if let Some(tags) = self.tags {
+            task.tags = tags;
+        }
+        if let Some(priority) = self.priority {
+            task.priority = priority;
+        }
+        self.recurrence.apply(&mut task.recurrence);
+        self.parent_id.apply(&mut task.parent_id);
+        self.project_id.apply(&mut task.project_id);
+        self.notes.apply(&mut task.notes);
+        self.color.apply(&mut task.color);
+        self.estimate_minutes.apply(&mut task.estimate_minutes);
+    }
+}
+
+/// Task shape written by the old `load_tasks`/`save_tasks` commands to `tasks.json`
+#[derive(Deserialize, Debug)]
+pub struct LegacyTask {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub completed: bool,
+}
+
+/// Criteria for `query_tasks`; every field that is set must match.
+/// An empty filter returns what `get_all_tasks` does.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskFilter {
+    pub completed: Option<bool>,
+    /// Due strictly before this time (epoch millis)
+    pub due_before: Option<i64>,
+    /// Due at or after this time (epoch millis)
+    pub due_after: Option<i64>,
+    pub tag: Option<String>,
+    /// Text in the title or description
+    pub search: Option<String>,
+}
+
+/// A remote edit that diverged from an unpushed local edit of the same task
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskConflict {
+    pub task_id: String,
+    pub local: Task,
+    pub remote: Task,
+    pub detected_at: i64,
+}
+
+/// Why `Database::update_task` didn't write
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum UpdateError {
+    /// The task changed since the caller read it; `current` is the stored version
+    /// to reapply the edit to before retrying
+    Conflict { current: Box<Task> },
+    Failed { message: String },
+}
+
+impl std::fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateError::Conflict { current } => write!(f, "Task {} was changed elsewhere", current.id),
+            UpdateError::Failed { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<UpdateError> for String {
+    fn from(e: UpdateError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<String> for UpdateError {
+    fn from(message: String) -> Self {
+        UpdateError::Failed { message }
+    }
+}
+
+/// What `Database::add_task_unique` does when an open task already has the title
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum DuplicatePolicy {
+    /// Hand back the existing task instead of adding another
+    ReturnExisting,
+    /// Fail with an error naming the existing task
+    Reject,
+}
+
+/// What `Database::recover` salvaged from a damaged file
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryReport {
+    pub recovered_rows: usize,
+    /// Tables where reading hit damage partway, so later rows may be missing
+    pub incomplete_tables: Vec<String>,
+    /// Where the damaged file was moved, kept in case more can be saved by hand
+    pub damaged_path: String,
+}
+
+/// Task counts for the stats panel, over non-deleted tasks
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskStats {
+    pub total: usize,
+    pub completed: usize,
+    pub incomplete: usize,
+    /// Open, unarchived and past their due date
+    pub overdue: usize,
+    /// Open, unarchived and due within the next 24 hours
+    pub due_soon: usize,
+    /// Created since the start of this week (Monday, local time)
+    pub created_this_week: usize,
+}
+
+/// What `upsert_batch_from_remote` did with a batch of remote tasks
+#[derive(Debug, Default)]
+pub struct RemoteBatch {
+    /// Live tasks that were inserted or updated
+    pub upserted: Vec<String>,
+    /// Tasks that became tombstones
+    pub deleted: Vec<String>,
+    /// Remote edits recorded as conflicts instead of applied
+    pub conflicts: usize,
+}
+
+/// How a pulled task is applied over a local edit that hasn't been pushed yet.
+/// Tasks without unpushed edits always take the newer version.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum ConflictStrategy {
+    /// Merge field by field, the newest edit of each winning. Versions without
+    /// edit times are compared whole, and a newer diverging remote one is
+    /// recorded as a conflict.
+    #[default]
+    NewestWins,
+    /// Keep the local edit and push it over the remote version
+    LocalWins,
+    /// Drop the local edit in favour of the remote version
+    RemoteWins,
+    /// Record every divergence as a conflict for the user to resolve
+    Manual,
+}
+
+/// Which side of a conflict to keep
+#[derive(Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictWinner {
+    Local,
+    Remote,
+}
+
+/// A named list grouping tasks, replicated as its own document
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    pub name: String,
+    pub color: String,
+    pub order: f64,
+    pub updated_at: i64,
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+/// One interval of work on a task, kept on this device only. `ended_at` is
+/// `None` while the timer is running.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeEntry {
+    pub id: i64,
+    pub task_id: String,
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+}
+
+/// What a `TaskEvent` did to the task
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TaskEventKind {
+    Created,
+    Updated,
+    Completed,
+    Reopened,
+    Deleted,
+    Restored,
+}
+
+impl TaskEventKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            TaskEventKind::Created => "created",
+            TaskEventKind::Updated => "updated",
+            TaskEventKind::Completed => "completed",
+            TaskEventKind::Reopened => "reopened",
+            TaskEventKind::Deleted => "deleted",
+            TaskEventKind::Restored => "restored",
+        }
+    }
+    
+    fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "created" => Some(TaskEventKind::Created),
+            "updated" => Some(TaskEventKind::Updated),
+            "completed" => Some(TaskEventKind::Completed),
+            "reopened" => Some(TaskEventKind::Reopened),
+            "deleted" => Some(TaskEventKind::Deleted),
+            "restored" => Some(TaskEventKind::Restored),
+            _ => None,
+        }
+    }
+}
+
+/// One entry in a task's edit history, kept on this device only
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskEvent {
+    pub id: i64,
+    pub task_id: String,
+    pub kind: TaskEventKind,
+    /// Epoch millis
+    pub at: i64,
+    /// The new task for `Created`; otherwise each changed field's JSON name
+    /// mapped to its `[before, after]` values
+    pub details: serde_json::Value,
+}
+
+/// One task a template creates. Its due date is `due_offset_minutes` after
+/// the template is instantiated, or unset.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateTask {
+    pub title: String,
+    pub description: Option<String>,
+    pub due_offset_minutes: Option<i64>,
+}
+
+/// A named set of tasks saved with `Database::save_template`, kept on this device only
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Template {
+    pub name: String,
+    pub tasks: Vec<TemplateTask>,
+    pub updated_at: i64,
+}
+
+/// A tag and how many tasks carry it, from `Database::get_all_tags`
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: usize,
+}
+
+/// On-disk size of the database, WAL included, before and after `Database::compact`
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactStats {
+    pub before_bytes: u64,
+    pub after_bytes: u64,
+}
+
+/// Current layout of the backup envelope; bump when it changes and migrate older ones in `import_all`
+const BACKUP_VERSION: u32 = 2;
+
+/// Everything needed to restore the database, tombstones included
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Backup {
+    version: u32,
+    exported_at: i64,
+    tasks: Vec<Task>,
+    #[serde(default)]
+    projects: Vec<Project>,
+    #[serde(default)]
+    sync_state: Option<BackupSyncState>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BackupSyncState {
+    last_seq: Option<String>,
+    last_synced_at: Option<i64>,
+}
+
+/// A Todoist export: either the sync API's `{"items": [...]}` or a bare item list
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum TodoistExport {
+    Items { items: Vec<TodoistItem> },
+    List(Vec<TodoistItem>),
+}
+
+#[derive(Deserialize, Debug)]
+struct TodoistItem {
+    /// A string in current exports, a number in older ones
+    id: serde_json::Value,
+    content: String,
+    #[serde(default)]
+    description: String,
+    due: Option<TodoistDue>,
+    /// 1 (normal) to 4 (urgent)
+    #[serde(default)]
+    priority: i32,
+    #[serde(default)]
+    checked: bool,
+    #[serde(default)]
+    is_deleted: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct TodoistDue {
+    date: String,
+}
+
+impl TodoistItem {
+    fn source_id(&self) -> String {
+        match &self.id {
+            serde_json::Value::String(id) => id.clone(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Map Todoist's 1 (normal) to 4 (urgent) onto our scale
+fn todoist_priority(priority: i32) -> Priority {
+    match priority {
+        4 => Priority::High,
+        3 => Priority::Medium,
+        2 => Priority::Low,
+        _ => Priority::None,
+    }
+}
+
+const PROJECT_COLUMNS: &str = "id, rev, name, color, project_order, updated_at, deleted";
+
+fn project_from_row(row: &rusqlite::Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        rev: row.get(1)?,
+        name: row.get(2)?,
+        color: row.get(3)?,
+        order: row.get(4)?,
+        updated_at: row.get(5)?,
+        deleted: row.get::<_, i32>(6)? != 0,
+    })
+}
+
+fn fetch_project(conn: &Connection, id: &str) -> Result<Project, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM projects WHERE id = ?1 AND deleted = 0", PROJECT_COLUMNS),
+        params![id],
+        project_from_row,
+    ).map_err(|e| format!("Project not found: {}", e))
+}
+
+/// Columns selected for every `Task` read, in the order `task_from_row` expects.
+const TASK_COLUMNS: &str =
+    "id, rev, title, description, completed, due_date, updated_at, task_rank, deleted, tags, priority, recurrence, parent_id, project_id, created_at, archived, notes, attachments, completed_at, field_updated_at, color, estimate_minutes";
+
+fn task_from_row(row: &rusqlite::Row) -> rusqlite::Result<Task> {
+    let tags_json: String = row.get(9)?;
+    let tags = serde_json::from_str(&tags_json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(9, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let attachments_json: String = row.get(17)?;
+    let attachments = serde_json::from_str(&attachments_json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(17, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let field_updated_at_json: String = row.get(19)?;
+    let field_updated_at = serde_json::from_str(&field_updated_at_json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(19, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    
+    Ok(Task {
+        id: row.get(0)?,
+        rev: row.get(1)?,
+        title: row.get(2)?,
+        description: row.get(3)?,
+        completed: row.get::<_, i32>(4)? != 0,
+        due_date: row.get(5)?,
+        updated_at: row.get(6)?,
+        rank: row.get(7)?,
+        deleted: row.get::<_, i32>(8)? != 0,
+        tags,
+        priority: row.get(10)?,
+        recurrence: row.get(11)?,
+        parent_id: row.get(12)?,
+        project_id: row.get(13)?,
+        created_at: row.get(14)?,
+        archived: row.get::<_, i32>(15)? != 0,
+        notes: row.get(16)?,
+        attachments,
+        completed_at: row.get(18)?,
+        field_updated_at,
+        color: row.get(20)?,
+        estimate_minutes: row.get(21)?,
+    })
+}
+
+/// A CouchDB-style `N-hash` revision with generation `n` and a random hash
+fn new_rev(n: i64) -> String {
+    let bytes: [u8; 16] = rand::random();
+    let hash: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}", n, hash)
+}
+
+/// Split an `N-hash` revision into its generation and hash; `None` when it
+/// doesn't have that shape
+fn parse_rev(rev: &str) -> Option<(i64, &str)> {
+    let (n, hash) = rev.split_once('-')?;
+    let n = n.parse().ok().filter(|&n| n > 0)?;
+    (!hash.is_empty()).then_some((n, hash))
+}
+
+/// Generation of a revision, 0 when there's none or it's malformed
+fn rev_number(rev: Option<&str>) -> i64 {
+    rev.and_then(parse_rev).map_or(0, |(n, _)| n)
+}
+
+/// The revision after `rev`; a missing or malformed one starts over at 1
+fn next_rev(rev: Option<&str>) -> String {
+    new_rev(rev_number(rev) + 1)
+}
+
+/// How far apart two devices' clocks may be. Versions written closer together
+/// than this are ordered by revision rather than by timestamps that can't be trusted.
+const CLOCK_SKEW_TOLERANCE_MS: i64 = 5_000;
+
+/// Whether `a` is a later version of the same task than `b`: the later
+/// `updated_at`, unless the two are within `CLOCK_SKEW_TOLERANCE_MS`, then the
+/// higher revision number, then the greater revision id. Every device picks
+/// the same winner. Must match the `WHERE` of `UPSERT_TASK_SQL`.
+fn is_newer(a: &Task, b: &Task) -> bool {
+    if a.updated_at.abs_diff(b.updated_at) > CLOCK_SKEW_TOLERANCE_MS as u64 {
+        return a.updated_at > b.updated_at;
+    }
+    
+    let rev_a = a.rev.as_deref();
+    let rev_b = b.rev.as_deref();
+    (rev_number(rev_a), rev_a.unwrap_or("")) > (rev_number(rev_b), rev_b.unwrap_or(""))
+}
+
+fn fetch_task(conn: &Connection, id: &str) -> Result<Task, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM tasks WHERE id = ?1", TASK_COLUMNS),
+        params![id],
+        task_from_row,
+    ).map_err(|e| format!("Task not found: {}", e))
+}
+
+fn validate_recurrence(recurrence: Option<&str>) -> Result<(), String> {
+    if let Some(rule) = recurrence {
+        RecurrenceRule::parse(rule)?;
+    }
+    Ok(())
+}
+
+/// Colors are stored as `#rrggbb` so every device can render them
+fn validate_color(color: Option<&str>) -> Result<(), String> {
+    match color {
+        Some(color) if color.len() != 7
+            || !color.starts_with('#')
+            || !color[1..].chars().all(|c| c.is_ascii_hexdigit()) =>
+        {
+            Err(format!("Invalid color '{}': expected a hex color like #1a2b3c", color))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Estimates are whole minutes, and a task can't take no time at all
+fn validate_estimate(estimate_minutes: Option<i32>) -> Result<(), String> {
+    match estimate_minutes {
+        Some(minutes) if minutes <= 0 => Err(format!("Invalid estimate {}: must be at least one minute", minutes)),
+        _ => Ok(()),
+    }
+}
+
+/// Reject parents that don't exist or that would make a task its own ancestor
+fn validate_parent(conn: &Connection, task_id: &str, parent_id: Option<&str>) -> Result<(), String> {
+    let Some(parent_id) = parent_id else {
+        return Ok(());
+    };
+    
+    let parent_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM tasks WHERE id = ?1 AND deleted = 0)",
+        params![parent_id],
+        |row| row.get(0)
+    ).map_err(|e| format!("Query error: {}", e))?;
+    
+    if !parent_exists {
+        return Err(format!("Parent task {} not found", parent_id));
+    }
+    
+    // Walk up from the new parent; reaching the task itself means a cycle
+    let creates_cycle: bool = conn.query_row(
+        "WITH RECURSIVE ancestors(id) AS (
+            SELECT ?1
+            UNION
+            SELECT tasks.parent_id FROM tasks JOIN ancestors ON tasks.id = ancestors.id
+            WHERE tasks.parent_id IS NOT NULL
+         )
+         SELECT EXISTS(SELECT 1 FROM ancestors WHERE id = ?2)",
+        params![parent_id, task_id],
+        |row| row.get(0)
+    ).map_err(|e| format!("Query error: {}", e))?;
+    
+    if creates_cycle {
+        return Err("A task cannot be a subtask of itself or of one of its own subtasks".to_string());
+    }
+    
+    Ok(())
+}
+
+/// Soft-delete every descendant of a task with the same `updated_at`, so the
+/// whole subtree's deletion replicates together
+fn cascade_delete(conn: &Connection, parent_id: &str, updated_at: i64) -> Result<(), String> {
+    conn.execute(
+        "UPDATE tasks SET deleted = 1, updated_at = ?1, dirty = 1,
+            field_updated_at = json_set(field_updated_at, '$.deleted', ?1)
+         WHERE deleted = 0 AND id IN (
+            WITH RECURSIVE descendants(id) AS (
+                SELECT id FROM tasks WHERE parent_id = ?2
+                UNION
+                SELECT tasks.id FROM tasks JOIN descendants ON tasks.parent_id = descendants.id
+            )
+            SELECT id FROM descendants
+         )",
+        params![updated_at, parent_id],
+    ).map_err(|e| format!("Failed to delete subtasks: {}", e))?;
+    
+    Ok(())
+}
+
+/// Non-deleted subtasks of a task at any depth, parents before their children
+fn fetch_live_descendants(conn: &Connection, parent_id: &str) -> Result<Vec<Task>, String> {
+    let mut stmt = conn.prepare(&format!(
+        "WITH RECURSIVE descendants(id, depth) AS (
+            SELECT id, 1 FROM tasks WHERE parent_id = ?1
+            UNION
+            SELECT tasks.id, descendants.depth + 1 FROM tasks JOIN descendants ON tasks.parent_id = descendants.id
+         )
+         SELECT {} FROM tasks
+         WHERE deleted = 0 AND id IN (SELECT id FROM descendants)
+         ORDER BY (SELECT MIN(depth) FROM descendants WHERE descendants.id = tasks.id) ASC",
+        TASK_COLUMNS
+    )).map_err(|e| format!("Failed to prepare: {}", e))?;
+    
+    let tasks = stmt.query_map(params![parent_id], task_from_row)
+        .map_err(|e| format!("Query error: {}", e))?;
+    
+    tasks.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Collect error: {}", e))
+}
+
+/// Rank that places a task after every live task
+fn end_of_list_rank(conn: &Connection) -> String {
+    let max_rank: Option<String> = conn
+        .query_row(
+            "SELECT MAX(task_rank) FROM tasks WHERE deleted = 0",
+            [],
+            |row| row.get(0)
+        )
+        .unwrap_or(None);
+    
+    rank::between(max_rank.as_deref(), None)
+}
+
+/// Log a new task's `Created` event and return its history change, whose
+/// "before" is a tombstone
+fn creation(conn: &Connection, task: &Task) -> Result<(Task, Task), String> {
+    let details = serde_json::to_value(task).unwrap_or_default();
+    insert_task_events(conn, [(task.id.as_str(), TaskEventKind::Created, details)])?;
+    Ok((Task { deleted: true, ..task.clone() }, task.clone()))
+}
+
+/// Most task events kept; the oldest are dropped past this
+const TASK_EVENT_LIMIT: i64 = 10_000;
+
+/// Append an event for each edit; new tasks are logged by `creation`.
+/// Changes to nothing but the rank, e.g. a restore, log no `Updated` event.
+fn log_task_events(conn: &Connection, changes: &[(Task, Task)]) -> Result<(), String> {
+    let events = changes.iter().filter_map(|(before, after)| {
+        let kind = match (before.deleted, after.deleted, before.completed, after.completed) {
+            (false, true, _, _) => TaskEventKind::Deleted,
+            (true, false, _, _) => TaskEventKind::Restored,
+            (_, _, false, true) => TaskEventKind::Completed,
+            (_, _, true, false) => TaskEventKind::Reopened,
+            _ => TaskEventKind::Updated,
+        };
+        let details = field_diff(before, after);
+        if kind == TaskEventKind::Updated && details.is_empty() {
+            return None;
+        }
+        Some((after.id.as_str(), kind, serde_json::Value::Object(details)))
+    });
+    insert_task_events(conn, events)
+}
+
+/// Write events and drop the oldest past `TASK_EVENT_LIMIT`
+fn insert_task_events<'a>(
+    conn: &Connection,
+    events: impl IntoIterator<Item = (&'a str, TaskEventKind, serde_json::Value)>,
+) -> Result<(), String> {
+    let at = Utc::now().timestamp_millis();
+    let mut stmt = conn.prepare(
+        "INSERT INTO task_events (task_id, kind, at, details) VALUES (?1, ?2, ?3, ?4)"
+    ).map_err(|e| format!("Failed to prepare: {}", e))?;
+    
+    for (task_id, kind, details) in events {
+        stmt.execute(params![task_id, kind.as_str(), at, details.to_string()])
+            .map_err(|e| format!("Failed to log task event: {}", e))?;
+    }
+    
+    conn.execute(
+        "DELETE FROM task_events 
+         WHERE id <= (SELECT id FROM task_events ORDER BY id DESC LIMIT 1 OFFSET ?1)",
+        params![TASK_EVENT_LIMIT],
+    ).map_err(|e| format!("Failed to trim task events: {}", e))?;
+    
+    Ok(())
+}
+
+/// `write_task` and its event in one transaction
+fn write_logged(conn: &Connection, before: &Task, task: &Task) -> Result<Task, String> {
+    let tx = conn.unchecked_transaction()
+        .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+    let written = write_task(&tx, task)?;
+    log_task_events(&tx, &[(before.clone(), written.clone())])?;
+    tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+    Ok(written)
+}
+
+/// `[before, after]` of each field that changed, keyed by its JSON name
+fn field_diff(before: &Task, after: &Task) -> serde_json::Map<String, serde_json::Value> {
+    let (old, new) = (serde_json::to_value(before).unwrap_or_default(), serde_json::to_value(after).unwrap_or_default());
+    changed_fields(before, after).into_iter()
+        .filter(|field| *field != "rank")
+        .map(|field| {
+            let value = |task: &serde_json::Value| task.get(field).cloned().unwrap_or_default();
+            (field.to_string(), serde_json::json!([value(&old), value(&new)]))
+        })
+        .collect()
+}
+
+/// The tasks stored for the template called `name`
+fn parse_template_tasks(name: &str, json: &str) -> Result<Vec<TemplateTask>, String> {
+    serde_json::from_str(json).map_err(|e| format!("Template '{}' is unreadable: {}", name, e))
+}
+
+/// Insert a new task at the end of the list
+fn insert_task(conn: &Connection, mut new_task: NewTask) -> Result<Task, String> {
+    new_task.tags = normalize_tags(&new_task.tags);
+    validate_recurrence(new_task.recurrence.as_deref())?;
+    validate_color(new_task.color.as_deref())?;
+    validate_estimate(new_task.estimate_minutes)?;
+    
+    let id = Uuid::now_v7().to_string();
+    validate_parent(conn, &id, new_task.parent_id.as_deref())?;
+    
+    let rev = new_rev(1);
+    let updated_at = Utc::now().timestamp_millis();
+    let rank = end_of_list_rank(conn);
+    
+    conn.execute(
+        "INSERT INTO tasks (id, rev, title, description, completed, due_date, due_at, updated_at, created_at, task_rank, deleted, tags, priority, recurrence, parent_id, project_id, notes, attachments, color, estimate_minutes, dirty)
+         VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, ?7, ?8, 0, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, 1)",
+        params![
+            id,
+            rev,
+            new_task.title,
+            new_task.description,
+            new_task.due_date,
+            new_task.due_date.as_deref().and_then(due_date_millis),
+            updated_at,
+            rank,
+            tags_to_json(&new_task.tags)?,
+            new_task.priority,
+            new_task.recurrence,
+            new_task.parent_id,
+            new_task.project_id,
+            new_task.notes,
+            attachments_to_json(&new_task.attachments)?,
+            new_task.color,
+            new_task.estimate_minutes,
+        ],
+    ).map_err(|e| format!("Failed to insert task: {}", e))?;
+    
+    Ok(Task {
+        id,
+        rev: Some(rev),
+        title: new_task.title,
+        description: new_task.description,
+        completed: false,
+        completed_at: None,
+        due_date: new_task.due_date,
+        updated_at,
+        created_at: updated_at,
+        rank,
+        deleted: false,
+        archived: false,
+        tags: new_task.tags,
+        priority: new_task.priority,
+        recurrence: new_task.recurrence,
+        parent_id: new_task.parent_id,
+        project_id: new_task.project_id,
+        notes: new_task.notes,
+        attachments: new_task.attachments,
+        color: new_task.color,
+        estimate_minutes: new_task.estimate_minutes,
+        field_updated_at: BTreeMap::new(),
+    })
+}
+
+/// Mark a task completed and, if it repeats, insert its next occurrence
+fn complete_task(conn: &Connection, mut task: Task) -> Result<(Task, Option<Task>), String> {
+    let rule = task.recurrence.as_deref().map(RecurrenceRule::parse).transpose()?;
+    
+    task.completed = true;
+    let completed = write_task(conn, &task)?;
+    
+    let next_due = match &rule {
+        Some(rule) => next_due_date(rule, task.due_date.as_deref())?,
+        None => None,
+    };
+    
+    // No next occurrence for one-off tasks or once the rule's UNTIL has passed
+    let next = match next_due {
+        Some(due_date) => Some(insert_task(conn, NewTask {
+            title: task.title,
+            description: task.description,
+            due_date: Some(due_date),
+            tags: task.tags,
+            priority: task.priority,
+            recurrence: task.recurrence,
+            parent_id: task.parent_id,
+            project_id: task.project_id,
+            notes: task.notes,
+            attachments: task.attachments,
+            color: task.color,
+            estimate_minutes: task.estimate_minutes,
+        })?),
+        None => None,
+    };
+    
+    Ok((completed, next))
+}
+
+/// Checks `write_task` makes before writing `task`
+fn validate_task(conn: &Connection, task: &Task) -> Result<(), String> {
+    validate_recurrence(task.recurrence.as_deref())?;
+    validate_color(task.color.as_deref())?;
+    validate_estimate(task.estimate_minutes)?;
+    // A tombstone doesn't need a live parent, e.g. when redoing a cascaded delete
+    if !task.deleted {
+        validate_parent(conn, &task.id, task.parent_id.as_deref())?;
+    }
+    Ok(())
+}
+
+/// Overwrite a task's fields, bumping its revision and `updated_at` and
+/// stamping the fields that changed in `field_updated_at`
+fn write_task(conn: &Connection, task: &Task) -> Result<Task, String> {
+    let task = &Task { tags: normalize_tags(&task.tags), ..task.clone() };
+    validate_task(conn, task)?;
+    
+    let new_rev = next_rev(task.rev.as_deref());
+    let updated_at = Utc::now().timestamp_millis();
+    // Completing stamps the time once; reopening clears it so a later completion restamps
+    let completed_at = match task.completed {
+        true => task.completed_at.or(Some(updated_at)),
+        false => None,
+    };
+    // Compare against the stored row, since `task` may be an old snapshot (e.g. undo)
+    let mut field_updated_at = task.field_updated_at.clone();
+    if let Ok(stored) = fetch_task(conn, &task.id) {
+        field_updated_at = stored.field_updated_at.clone();
+        for field in changed_fields(&stored, task) {
+            field_updated_at.insert(field.to_string(), updated_at);
+        }
+    }
+    
+    let written = Task {
+        rev: Some(new_rev),
+        updated_at,
+        completed_at,
+        field_updated_at,
+        ..task.clone()
+    };
+    store_task(conn, &written)?;
+    
+    if task.deleted {
+        cascade_delete(conn, &task.id, updated_at)?;
+    }
+    
+    Ok(written)
+}
+
+/// Write every column of a task as given and mark it for pushing
+fn store_task(conn: &Connection, task: &Task) -> Result<(), String> {
+    conn.execute(
+        "UPDATE tasks SET 
+            rev = ?1, 
+            title = ?2, 
+            description = ?3, 
+            completed = ?4, 
+            due_date = ?5, 
+            updated_at = ?6, 
+            task_rank = ?7,
+            deleted = ?8,
+            tags = ?9,
+            priority = ?10,
+            recurrence = ?11,
+            parent_id = ?12,
+            project_id = ?13,
+            archived = ?14,
+            due_at = ?15,
+            notes = ?16,
+            attachments = ?17,
+            completed_at = ?18,
+            field_updated_at = ?19,
+            color = ?20,
+            estimate_minutes = ?21,
+            dirty = 1
+         WHERE id = ?22",
+        params![
+            task.rev,
+            task.title,
+            task.description,
+            task.completed as i32,
+            task.due_date,
+            task.updated_at,
+            task.rank,
+            task.deleted as i32,
+            tags_to_json(&task.tags)?,
+            task.priority,
+            task.recurrence,
+            task.parent_id,
+            task.project_id,
+            task.archived as i32,
+            task.due_date.as_deref().and_then(due_date_millis),
+            task.notes,
+            attachments_to_json(&task.attachments)?,
+            task.completed_at,
+            field_times_to_json(&task.field_updated_at)?,
+            task.color,
+            task.estimate_minutes,
+            task.id
+        ],
+    ).map_err(|e| format!("Failed to update task: {}", e))?;
+    
+    Ok(())
+}
+
+/// Tags are stored trimmed, lowercased and with inner whitespace collapsed,
+/// so "Work", "work" and " work " are one tag
+fn normalize_tag(tag: &str) -> String {
+    tag.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// `normalize_tag` applied to each tag, dropping empty ones and repeats
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    tags.iter()
+        .map(|tag| normalize_tag(tag))
+        .filter(|tag| !tag.is_empty() && seen.insert(tag.clone()))
+        .collect()
+}
+
+fn tags_to_json(tags: &[String]) -> Result<String, String> {
+    serde_json::to_string(&normalize_tags(tags)).map_err(|e| format!("Failed to serialize tags: {}", e))
+}
+
+fn attachments_to_json(attachments: &[Attachment]) -> Result<String, String> {
+    serde_json::to_string(attachments).map_err(|e| format!("Failed to serialize attachments: {}", e))
+}
+
+fn field_times_to_json(field_updated_at: &BTreeMap<String, i64>) -> Result<String, String> {
+    serde_json::to_string(field_updated_at).map_err(|e| format!("Failed to serialize field times: {}", e))
+}
+
+/// Parse a stored due date into epoch millis. Date-only and zone-less values are
+/// read as local time, matching how the frontend interprets them.
+pub fn due_date_millis(due_date: &str) -> Option<i64> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(due_date) {
+        return Some(dt.timestamp_millis());
+    }
+    
+    let naive = NaiveDateTime::parse_from_str(due_date, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(due_date, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(due_date, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })?;
+    
+    Local.from_local_datetime(&naive).earliest().map(|dt| dt.timestamp_millis())
+}
+
+/// Validate a due date from the frontend and rewrite it in canonical form:
+/// `YYYY-MM-DD` for dates, `YYYY-MM-DDTHH:MM:SS` for local times, and UTC
+/// RFC 3339 for anything carrying an offset.
+fn normalize_due_date(due_date: &str) -> Result<String, String> {
+    let due_date = due_date.trim();
+    
+    if let Ok(dt) = DateTime::parse_from_rfc3339(due_date) {
+        return Ok(dt.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(due_date, "%Y-%m-%d") {
+        return Ok(date.format("%Y-%m-%d").to_string());
+    }
+    
+    NaiveDateTime::parse_from_str(due_date, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(due_date, "%Y-%m-%dT%H:%M"))
+        .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S").to_string())
+        .map_err(|_| format!("Invalid due date '{}': expected an ISO 8601 date or date-time", due_date))
+}
+
+/// Push a due date `add_millis` later, keeping its canonical form. A date
+/// without a time moves by whole days when the offset allows it; otherwise
+/// the result is a local time. An unset due date is snoozed from now.
+fn snoozed_due_date(due_date: Option<&str>, add_millis: i64) -> Result<String, String> {
+    const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+    
+    let date_only = due_date.and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+    if let Some(date) = date_only.filter(|_| add_millis % DAY_MILLIS == 0) {
+        return date.checked_add_signed(chrono::Duration::days(add_millis / DAY_MILLIS))
+            .map(|date| date.format("%Y-%m-%d").to_string())
+            .ok_or_else(|| "Snoozed due date is out of range".to_string());
+    }
+    
+    let base = match due_date {
+        Some(d) => due_date_millis(d).ok_or_else(|| format!("Invalid due date '{}'", d))?,
+        None => Utc::now().timestamp_millis(),
+    };
+    let due = base.checked_add(add_millis)
+        .and_then(|millis| Local.timestamp_millis_opt(millis).single())
+        .ok_or_else(|| "Snoozed due date is out of range".to_string())?;
+    
+    if due_date.is_some_and(|d| DateTime::parse_from_rfc3339(d).is_ok()) {
+        Ok(due.with_timezone(&Utc).to_rfc3339_opts(SecondsFormat::Secs, true))
+    } else {
+        Ok(due.format("%Y-%m-%dT%H:%M:%S").to_string())
+    }
+}
+
+/// Insert a remote task, or overwrite the local copy only if the remote one is
+/// newer as `is_newer` decides, with ?24 the clock skew tolerance.
+/// Rows written here match the server, so they are never marked dirty.
+const UPSERT_TASK_SQL: &str =
+    "INSERT INTO tasks (id, rev, title, description, completed, due_date, updated_at, task_rank, deleted, tags, priority, recurrence, parent_id, project_id, created_at, archived, due_at, notes, attachments, completed_at, field_updated_at, color, estimate_minutes, dirty)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, 0)
+     ON CONFLICT(id) DO UPDATE SET
+        rev = excluded.rev,
+        title = excluded.title,
+        description = excluded.description,
+        completed = excluded.completed,
+        due_date = excluded.due_date,
+        updated_at = excluded.updated_at,
+        task_rank = excluded.task_rank,
+        deleted = excluded.deleted,
+        tags = excluded.tags,
+        priority = excluded.priority,
+        recurrence = excluded.recurrence,
+        parent_id = excluded.parent_id,
+        project_id = excluded.project_id,
+        created_at = excluded.created_at,
+        archived = excluded.archived,
+        due_at = excluded.due_at,
+        notes = excluded.notes,
+        attachments = excluded.attachments,
+        completed_at = excluded.completed_at,
+        field_updated_at = excluded.field_updated_at,
+        color = excluded.color,
+        estimate_minutes = excluded.estimate_minutes,
+        dirty = 0
+     WHERE excluded.updated_at > tasks.updated_at + ?24
+        OR (excluded.updated_at >= tasks.updated_at - ?24
+            AND (CAST(COALESCE(excluded.rev, '') AS INTEGER), COALESCE(excluded.rev, ''))
+                > (CAST(COALESCE(tasks.rev, '') AS INTEGER), COALESCE(tasks.rev, '')))";
+
+const UPSERT_PROJECT_SQL: &str =
+    "INSERT INTO projects (id, rev, name, color, project_order, updated_at, deleted, dirty)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 0)
+     ON CONFLICT(id) DO UPDATE SET
+        rev = excluded.rev,
+        name = excluded.name,
+        color = excluded.color,
+        project_order = excluded.project_order,
+        updated_at = excluded.updated_at,
+        deleted = excluded.deleted,
+        dirty = 0
+     WHERE excluded.updated_at > projects.updated_at";
+
+/// Non-deleted task ids with their ranks, in list order
+fn ordered_task_ids(conn: &Connection) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, task_rank FROM tasks WHERE deleted = 0 ORDER BY task_rank ASC, id ASC"
+    ).map_err(|e| format!("Failed to prepare: {}", e))?;
+    
+    let tasks = stmt.query_map([], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    }).map_err(|e| format!("Query error: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Collect error: {}", e));
+    tasks
+}
+
+/// Ids and ranks of the tasks `Database::get_tasks_for_project` lists, in order
+fn project_task_ids(conn: &Connection, project_id: &str) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT id, task_rank FROM tasks
+         WHERE deleted = 0 AND archived = 0 AND project_id = ?1
+         ORDER BY task_rank ASC, id ASC"
+    ).map_err(|e| format!("Failed to prepare: {}", e))?;
+    
+    let tasks = stmt.query_map(params![project_id], |row| {
+        Ok((row.get(0)?, row.get(1)?))
+    }).map_err(|e| format!("Query error: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Collect error: {}", e));
+    tasks
+}
+
+/// Swap a task with its neighbour in `tasks`, an ordered list of ids and ranks.
+/// Returns the moved task and that neighbour, whose rank stays as it was;
+/// nothing at the ends of the list.
+fn reorder_within(conn: &Connection, tasks: &[(String, String)], task_id: &str, direction: &str) -> Result<Vec<Task>, String> {
+    let current_index = tasks.iter().position(|(id, _)| id == task_id)
+        .ok_or_else(|| "Task not found".to_string())?;
+    
+    // Place the task on the far side of its neighbour
+    let (neighbour, before, after) = if direction == "up" {
+        if current_index == 0 { return Ok(Vec::new()); }
+        let neighbour = tasks[current_index - 1].0.as_str();
+        let before = current_index.checked_sub(2).map(|i| tasks[i].0.as_str());
+        (neighbour, before, Some(neighbour))
+    } else {
+        if current_index >= tasks.len() - 1 { return Ok(Vec::new()); }
+        let neighbour = tasks[current_index + 1].0.as_str();
+        let after = tasks.get(current_index + 2).map(|(id, _)| id.as_str());
+        (neighbour, Some(neighbour), after)
+    };
+    
+    let moved = move_between(conn, task_id, before, after)?;
+    Ok(vec![moved, fetch_task(conn, neighbour)?])
+}
+
+/// Move a task so it ends up at `new_index` in `tasks`, an ordered list of ids
+/// and ranks. Only the moved row gets a new rank; returns it.
+fn move_to_index_within(conn: &Connection, mut tasks: Vec<(String, String)>, task_id: &str, new_index: usize) -> Result<Task, String> {
+    let current_index = tasks.iter().position(|(id, _)| id == task_id)
+        .ok_or_else(|| "Task not found".to_string())?;
+    
+    if new_index >= tasks.len() {
+        return Err(format!("Index {} is out of range for {} tasks", new_index, tasks.len()));
+    }
+    if new_index == current_index {
+        return fetch_task(conn, task_id);
+    }
+    
+    // Neighbours at the new index once the task is out of the list
+    tasks.remove(current_index);
+    let before = new_index.checked_sub(1).map(|i| tasks[i].0.as_str());
+    let after = tasks.get(new_index).map(|(id, _)| id.as_str());
+    
+    move_between(conn, task_id, before, after)
+}
+
+fn task_rank_of(conn: &Connection, id: &str) -> Result<String, String> {
+    conn.query_row(
+        "SELECT task_rank FROM tasks WHERE id = ?1 AND deleted = 0",
+        params![id],
+        |row| row.get(0)
+    ).map_err(|e| format!("Task {} not found: {}", id, e))
+}
+
+/// Move a task between two neighbours by giving it a rank between theirs.
+/// Only the moved row changes, so there's nothing to renumber or sync besides it.
+/// Returns the moved task.
+fn move_between(conn: &Connection, task_id: &str, before_id: Option<&str>, after_id: Option<&str>) -> Result<Task, String> {
+    if before_id == Some(task_id) || after_id == Some(task_id) {
+        return Err("A task cannot be moved relative to itself".to_string());
+    }
+    
+    let before = before_id.map(|id| task_rank_of(conn, id)).transpose()?;
+    let after = after_id.map(|id| task_rank_of(conn, id)).transpose()?;
+    let new_rank = rank::between(before.as_deref(), after.as_deref());
+    
+    let updated_at = Utc::now().timestamp_millis();
+    let changed = conn.execute(
+        "UPDATE tasks SET task_rank = ?1, updated_at = ?2, dirty = 1,
+            field_updated_at = json_set(field_updated_at, '$.rank', ?2)
+         WHERE id = ?3 AND deleted = 0",
+        params![new_rank, updated_at, task_id],
+    ).map_err(|e| format!("Failed to move task: {}", e))?;
+    
+    if changed == 0 {
+        return Err("Task not found".to_string());
+    }
+    
+    fetch_task(conn, task_id)
+}
+
+/// Add a column to an existing table unless it is already present.
+/// `CREATE TABLE IF NOT EXISTS` won't touch databases created by older versions.
+/// Returns whether the column was added.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<bool, String> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))
+        .map_err(|e| format!("Failed to read table info: {}", e))?;
+    
+    let columns: Vec<String> = stmt.query_map([], |row| row.get::<_, String>(1))
+        .map_err(|e| format!("Failed to read table info: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read table info: {}", e))?;
+    
+    if columns.iter().any(|c| c == column) {
+        return Ok(false);
+    }
+    
+    conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+        .map_err(|e| format!("Failed to add column {}: {}", column, e))?;
+    
+    Ok(true)
+}
+
+type Migration = fn(&Connection) -> Result<(), String>;
+
+/// Schema changes, applied in order. A database at `user_version` N has had the
+/// first N applied. Append new migrations; never edit or reorder existing ones.
+const MIGRATIONS: &[Migration] = &[
+    initial_schema,
+    add_notes_and_attachments,
+    add_completed_at,
+    add_import_sources,
+    rank_tasks,
+    add_field_updated_at,
+    add_task_color,
+    add_time_tracking,
+    add_templates,
+    normalize_stored_tags,
+    add_task_events,
+];
+
+/// Bring the schema up to date, one transaction per migration so a failure
+/// leaves the database at the last version that fully applied
+fn run_migrations(conn: &mut Connection) -> Result<(), String> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))?;
+    
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to begin migration {}: {}", index, e))?;
+        migration(&tx).map_err(|e| format!("Migration {} failed: {}", index, e))?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", index + 1))
+            .map_err(|e| format!("Failed to bump schema version: {}", e))?;
+        tx.commit().map_err(|e| format!("Failed to commit migration {}: {}", index, e))?;
+        log::info!("Applied migration {}", index);
+    }
+    
+    Ok(())
+}
+
+/// Migration 0: the schema as it stood before versioning. Databases from that era
+/// also report version 0 and may be missing later columns, so every step here
+/// tolerates existing tables and columns.
+fn initial_schema(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS tasks (
+            id TEXT PRIMARY KEY,
+            rev TEXT,
+            title TEXT NOT NULL,
+            description TEXT,
+            completed INTEGER NOT NULL DEFAULT 0,
+            due_date TEXT,
+            updated_at INTEGER NOT NULL,
+            task_order REAL NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            tags TEXT NOT NULL DEFAULT '[]',
+            priority INTEGER NOT NULL DEFAULT 0,
+            recurrence TEXT,
+            parent_id TEXT,
+            project_id TEXT,
+            created_at INTEGER NOT NULL,
+            archived INTEGER NOT NULL DEFAULT 0,
+            due_at INTEGER,
+            dirty INTEGER NOT NULL DEFAULT 1
+        );
+        
+        CREATE TABLE IF NOT EXISTS projects (
+            id TEXT PRIMARY KEY,
+            rev TEXT,
+            name TEXT NOT NULL,
+            color TEXT NOT NULL,
+            project_order REAL NOT NULL,
+            updated_at INTEGER NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            dirty INTEGER NOT NULL DEFAULT 1
+        );
+        
+        CREATE TABLE IF NOT EXISTS sync_state (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            last_seq TEXT,
+            last_synced_at INTEGER,
+            last_push_at INTEGER
+        );
+        
+        CREATE TABLE IF NOT EXISTS conflicts (
+            task_id TEXT PRIMARY KEY,
+            local TEXT NOT NULL,
+            remote TEXT NOT NULL,
+            detected_at INTEGER NOT NULL
+        );
+        
+        CREATE INDEX IF NOT EXISTS idx_tasks_updated_at ON tasks(updated_at);
+        CREATE INDEX IF NOT EXISTS idx_tasks_deleted ON tasks(deleted);
+        "
+    ).map_err(|e| format!("Failed to create tables: {}", e))?;
+    
+    // Migrate databases created before these columns existed.
+    // Older databases declare task_order as INTEGER; its numeric affinity still
+    // stores fractional orders as REAL, so that column needs no rebuild.
+    add_column_if_missing(conn, "tasks", "tags", "TEXT NOT NULL DEFAULT '[]'")?;
+    add_column_if_missing(conn, "tasks", "priority", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "tasks", "recurrence", "TEXT")?;
+    add_column_if_missing(conn, "tasks", "dirty", "INTEGER NOT NULL DEFAULT 1")?;
+    // Deliberately not a FOREIGN KEY: a subtask may be pulled before its parent
+    add_column_if_missing(conn, "tasks", "parent_id", "TEXT")?;
+    add_column_if_missing(conn, "tasks", "project_id", "TEXT")?;
+    if add_column_if_missing(conn, "tasks", "created_at", "INTEGER NOT NULL DEFAULT 0")? {
+        // Best guess for rows that predate the column
+        conn.execute("UPDATE tasks SET created_at = updated_at", [])
+            .map_err(|e| format!("Failed to backfill created_at: {}", e))?;
+    }
+    add_column_if_missing(conn, "tasks", "archived", "INTEGER NOT NULL DEFAULT 0")?;
+    if add_column_if_missing(conn, "tasks", "due_at", "INTEGER")? {
+        backfill_due_at(conn)?;
+    }
+    add_column_if_missing(conn, "sync_state", "last_push_at", "INTEGER")?;
+    
+    conn.execute_batch(
+        "
+        CREATE INDEX IF NOT EXISTS idx_tasks_dirty ON tasks(dirty);
+        CREATE INDEX IF NOT EXISTS idx_tasks_parent_id ON tasks(parent_id);
+        CREATE INDEX IF NOT EXISTS idx_tasks_project_id ON tasks(project_id);
+        CREATE INDEX IF NOT EXISTS idx_tasks_due_at ON tasks(due_at);
+        "
+    ).map_err(|e| format!("Failed to create indexes: {}", e))?;
+    
+    Ok(())
+}
+
+/// Migration 1: long-form notes and attachment metadata on tasks
+fn add_notes_and_attachments(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        ALTER TABLE tasks ADD COLUMN notes TEXT;
+        ALTER TABLE tasks ADD COLUMN attachments TEXT NOT NULL DEFAULT '[]';
+        "
+    ).map_err(|e| format!("Failed to add notes columns: {}", e))
+}
+
+/// Migration 2: when each task was completed
+fn add_completed_at(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        ALTER TABLE tasks ADD COLUMN completed_at INTEGER;
+        UPDATE tasks SET completed_at = updated_at WHERE completed = 1;
+        CREATE INDEX IF NOT EXISTS idx_tasks_completed_at ON tasks(completed_at);
+        "
+    ).map_err(|e| format!("Failed to add completed_at column: {}", e))
+}
+
+/// Migration 3: which imported items each task came from, so re-imports skip them
+fn add_import_sources(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE import_sources (
+            source TEXT NOT NULL,
+            source_id TEXT NOT NULL,
+            task_id TEXT NOT NULL,
+            PRIMARY KEY (source, source_id)
+        );
+        "
+    ).map_err(|e| format!("Failed to create import_sources table: {}", e))
+}
+
+/// Migration 4: replace float `task_order` with string `task_rank`, converting
+/// each order with `rank::from_order` so every device arrives at the same ranks
+fn rank_tasks(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("ALTER TABLE tasks ADD COLUMN task_rank TEXT NOT NULL DEFAULT ''")
+        .map_err(|e| format!("Failed to add task_rank column: {}", e))?;
+    
+    let rows = {
+        let mut stmt = conn.prepare("SELECT id, task_order FROM tasks")
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)))
+            .map_err(|e| format!("Failed to query task orders: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect task orders: {}", e))?
+    };
+    
+    for (id, order) in rows {
+        conn.execute("UPDATE tasks SET task_rank = ?1 WHERE id = ?2", params![rank::from_order(order), id])
+            .map_err(|e| format!("Failed to backfill task_rank: {}", e))?;
+    }
+    
+    conn.execute_batch(
+        "
+        ALTER TABLE tasks DROP COLUMN task_order;
+        CREATE INDEX IF NOT EXISTS idx_tasks_rank ON tasks(task_rank);
+        "
+    ).map_err(|e| format!("Failed to drop task_order column: {}", e))
+}
+
+/// Migration 5: per-field edit times for merging concurrent edits
+fn add_field_updated_at(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("ALTER TABLE tasks ADD COLUMN field_updated_at TEXT NOT NULL DEFAULT '{}'")
+        .map_err(|e| format!("Failed to add field_updated_at column: {}", e))
+}
+
+/// Migration 6: optional color chip on tasks
+fn add_task_color(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("ALTER TABLE tasks ADD COLUMN color TEXT")
+        .map_err(|e| format!("Failed to add color column: {}", e))
+}
+
+/// Migration 7: task estimates, and the time log. Time entries stay on this
+/// device; at most one per task may be running, i.e. have no `ended_at`.
+fn add_time_tracking(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        ALTER TABLE tasks ADD COLUMN estimate_minutes INTEGER;
+        
+        CREATE TABLE time_entries (
+            id INTEGER PRIMARY KEY,
+            task_id TEXT NOT NULL,
+            started_at INTEGER NOT NULL,
+            ended_at INTEGER
+        );
+        
+        CREATE INDEX idx_time_entries_task ON time_entries(task_id);
+        CREATE UNIQUE INDEX idx_time_entries_running ON time_entries(task_id) WHERE ended_at IS NULL;
+        "
+    ).map_err(|e| format!("Failed to add time tracking: {}", e))
+}
+
+/// Migration 8: saved task templates, keyed by name
+fn add_templates(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE templates (
+            name TEXT PRIMARY KEY,
+            tasks TEXT NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        "
+    ).map_err(|e| format!("Failed to add templates: {}", e))
+}
+
+/// Migration 9: rewrite tags saved before they were normalized. Not marked for pushing;
+/// the next edit of each task sends its normalized tags.
+fn normalize_stored_tags(conn: &Connection) -> Result<(), String> {
+    let rows = {
+        let mut stmt = conn.prepare("SELECT id, tags FROM tasks")
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to query tags: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tags: {}", e))?
+    };
+    
+    for (id, json) in rows {
+        let Ok(tags) = serde_json::from_str::<Vec<String>>(&json) else {
+            continue;
+        };
+        if normalize_tags(&tags) != tags {
+            conn.execute("UPDATE tasks SET tags = ?1 WHERE id = ?2", params![tags_to_json(&tags)?, id])
+                .map_err(|e| format!("Failed to normalize tags: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Migration 10: the per-task history behind `get_task_history`
+fn add_task_events(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "
+        CREATE TABLE task_events (
+            id INTEGER PRIMARY KEY,
+            task_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            at INTEGER NOT NULL,
+            details TEXT NOT NULL
+        );
+        
+        CREATE INDEX idx_task_events_task ON task_events(task_id);
+        "
+    ).map_err(|e| format!("Failed to add task events: {}", e))
+}
+
+/// Fill `due_at` for rows written before the column existed. Unparseable due dates
+/// are left as NULL rather than failing the migration.
+fn backfill_due_at(conn: &Connection) -> Result<(), String> {
+    let rows = {
+        let mut stmt = conn.prepare("SELECT id, due_date FROM tasks WHERE due_date IS NOT NULL")
+            .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .map_err(|e| format!("Failed to query due dates: {}", e))?;
+        rows.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect due dates: {}", e))?
+    };
+    
+    for (id, due_date) in rows {
+        if let Some(due_at) = due_date_millis(&due_date) {
+            conn.execute("UPDATE tasks SET due_at = ?1 WHERE id = ?2", params![due_at, id])
+                .map_err(|e| format!("Failed to backfill due_at: {}", e))?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Bring the schema up to date and set up full-text search, returning whether
+/// FTS5 is available
+fn prepare_schema(conn: &mut Connection) -> Result<bool, String> {
+    run_migrations(conn)?;
+    
+    match setup_full_text_search(conn) {
+        Ok(()) => {
+            log::info!("Full-text search: using FTS5 index");
+            Ok(true)
+        }
+        Err(e) => {
+            log::warn!("Full-text search: FTS5 unavailable ({}), falling back to LIKE", e);
+            Ok(false)
+        }
+    }
+}
+
+/// Create the FTS5 index over title/description and the triggers that keep it current.
+/// Returns an error if this SQLite build lacks FTS5.
+fn setup_full_text_search(conn: &Connection) -> Result<(), String> {
+    let existed: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'tasks_fts')",
+        [],
+        |row| row.get(0)
+    ).map_err(|e| format!("Failed to inspect schema: {}", e))?;
+    
+    conn.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
+            title,
+            description,
+            content = 'tasks',
+            content_rowid = 'rowid'
+        );
+        
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_insert AFTER INSERT ON tasks BEGIN
+            INSERT INTO tasks_fts(rowid, title, description)
+            VALUES (new.rowid, new.title, new.description);
+        END;
+        
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_delete AFTER DELETE ON tasks BEGIN
+            INSERT INTO tasks_fts(tasks_fts, rowid, title, description)
+            VALUES ('delete', old.rowid, old.title, old.description);
+        END;
+        
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_update AFTER UPDATE OF title, description ON tasks BEGIN
+            INSERT INTO tasks_fts(tasks_fts, rowid, title, description)
+            VALUES ('delete', old.rowid, old.title, old.description);
+            INSERT INTO tasks_fts(rowid, title, description)
+            VALUES (new.rowid, new.title, new.description);
+        END;
+        "
+    ).map_err(|e| format!("Failed to create search index: {}", e))?;
+    
+    // Index rows that were written before the index existed
+    if !existed {
+        conn.execute("INSERT INTO tasks_fts(tasks_fts) VALUES ('rebuild')", [])
+            .map_err(|e| format!("Failed to build search index: {}", e))?;
+    }
+    
+    Ok(())
+}
+
+/// Turn free-form user input into an FTS5 query of quoted prefix terms,
+/// so punctuation and operators in the input can't cause syntax errors.
+fn fts_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Largest page `get_tasks_page` returns
+const MAX_PAGE_SIZE: i64 = 500;
+
+/// Lowercase and strip diacritics, so "Café" and "cafe" compare equal
+fn fold_text(text: &str) -> String {
+    text.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Title compared by `add_task_unique`: folded, with runs of whitespace collapsed
+fn title_key(title: &str) -> String {
+    fold_text(&title.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+/// An open task in the same project whose title matches `title` once normalized
+fn find_open_duplicate(conn: &Connection, title: &str, project_id: Option<&str>) -> Result<Option<Task>, String> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM tasks
+         WHERE deleted = 0 AND completed = 0 AND project_id IS ?1
+         ORDER BY task_rank ASC, id ASC",
+        TASK_COLUMNS
+    )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    
+    let tasks = stmt.query_map(params![project_id], task_from_row)
+        .map_err(|e| format!("Failed to query tasks: {}", e))?;
+    
+    let key = title_key(title);
+    for task in tasks {
+        let task = task.map_err(|e| format!("Failed to read task: {}", e))?;
+        if title_key(&task.title) == key {
+            return Ok(Some(task));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the title or description contains `needle`, itself already folded
+fn matches_folded(task: &Task, needle: &str) -> bool {
+    fold_text(&task.title).contains(needle)
+        || task.description.as_deref().is_some_and(|d| fold_text(d).contains(needle))
+}
+
+/// Whether two versions of a task differ in anything the user can see
+fn content_differs(a: &Task, b: &Task) -> bool {
+    a.title != b.title
+        || a.description != b.description
+        || a.completed != b.completed
+        || a.due_date != b.due_date
+        || a.deleted != b.deleted
+        || a.archived != b.archived
+        || a.tags != b.tags
+        || a.priority != b.priority
+        || a.recurrence != b.recurrence
+        || a.parent_id != b.parent_id
+        || a.project_id != b.project_id
+        || a.notes != b.notes
+        || a.attachments != b.attachments
+        || a.color != b.color
+        || a.estimate_minutes != b.estimate_minutes
+}
+
+/// JSON names of the fields that differ between two versions of a task, as
+/// keyed in `field_updated_at`
+fn changed_fields(a: &Task, b: &Task) -> Vec<&'static str> {
+    [
+        ("title", a.title != b.title),
+        ("description", a.description != b.description),
+        ("completed", a.completed != b.completed),
+        ("dueDate", a.due_date != b.due_date),
+        ("rank", a.rank != b.rank),
+        ("deleted", a.deleted != b.deleted),
+        ("archived", a.archived != b.archived),
+        ("tags", a.tags != b.tags),
+        ("priority", a.priority != b.priority),
+        ("recurrence", a.recurrence != b.recurrence),
+        ("parentId", a.parent_id != b.parent_id),
+        ("projectId", a.project_id != b.project_id),
+        ("notes", a.notes != b.notes),
+        ("attachments", a.attachments != b.attachments),
+        ("color", a.color != b.color),
+        ("estimateMinutes", a.estimate_minutes != b.estimate_minutes),
+    ]
+    .into_iter()
+    .filter(|(_, changed)| *changed)
+    .map(|(field, _)| field)
+    .collect()
+}
+
+/// Combine an unpushed local version of a task with a remote one field by field.
+/// Each field comes from whichever side edited it last, the remote one on a tie,
+/// so only an edit to the same field on both sides loses anything.
+fn merge_fields(local: &Task, remote: &Task) -> Task {
+    let edited_at = |task: &Task, field: &str| task.field_updated_at.get(field).copied().unwrap_or(0);
+    let local_wins = |field: &str| edited_at(local, field) > edited_at(remote, field);
+    
+    let mut merged = remote.clone();
+    if local_wins("title") {
+        merged.title = local.title.clone();
+    }
+    if local_wins("description") {
+        merged.description = local.description.clone();
+    }
+    if local_wins("completed") {
+        merged.completed = local.completed;
+        merged.completed_at = local.completed_at;
+    }
+    if local_wins("dueDate") {
+        merged.due_date = local.due_date.clone();
+    }
+    if local_wins("rank") {
+        merged.rank = local.rank.clone();
+    }
+    if local_wins("deleted") {
+        merged.deleted = local.deleted;
+    }
+    if local_wins("archived") {
+        merged.archived = local.archived;
+    }
+    if local_wins("tags") {
+        merged.tags = local.tags.clone();
+    }
+    if local_wins("priority") {
+        merged.priority = local.priority;
+    }
+    if local_wins("recurrence") {
+        merged.recurrence = local.recurrence.clone();
+    }
+    if local_wins("parentId") {
+        merged.parent_id = local.parent_id.clone();
+    }
+    if local_wins("projectId") {
+        merged.project_id = local.project_id.clone();
+    }
+    if local_wins("notes") {
+        merged.notes = local.notes.clone();
+    }
+    if local_wins("attachments") {
+        merged.attachments = local.attachments.clone();
+    }
+    if local_wins("color") {
+        merged.color = local.color.clone();
+    }
+    if local_wins("estimateMinutes") {
+        merged.estimate_minutes = local.estimate_minutes;
+    }
+    
+    for (field, &at) in &local.field_updated_at {
+        let newest = merged.field_updated_at.entry(field.clone()).or_insert(at);
+        *newest = (*newest).max(at);
+    }
+    // A version neither side has yet, so it has to replace both when pushed
+    merged.updated_at = local.updated_at.max(remote.updated_at) + 1;
+    merged
+}
+
+/// `local` written so it wins over `remote` on every device: newer than both, with
+/// each field that differs stamped as edited just now
+fn keep_local(local: &Task, remote: &Task) -> Task {
+    let updated_at = local.updated_at.max(remote.updated_at) + 1;
+    let mut kept = Task { updated_at, ..local.clone() };
+    for field in changed_fields(local, remote) {
+        kept.field_updated_at.insert(field.to_string(), updated_at);
+    }
+    kept
+}
+
+/// Park a diverging remote version next to the local one until the user picks a winner.
+/// Replaces any earlier conflict for the task with the latest remote version.
+fn record_conflict(conn: &Connection, local: &Task, remote: &Task) -> Result<(), String> {
+    let local_json = serde_json::to_string(local)
+        .map_err(|e| format!("Failed to serialize task: {}", e))?;
+    let remote_json = serde_json::to_string(remote)
+        .map_err(|e| format!("Failed to serialize task: {}", e))?;
+    
+    conn.execute(
+        "INSERT OR REPLACE INTO conflicts (task_id, local, remote, detected_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![local.id, local_json, remote_json, Utc::now().timestamp_millis()],
+    ).map_err(|e| format!("Failed to record conflict: {}", e))?;
+    
+    Ok(())
+}
+
+/// Open a SQLCipher connection keyed with `key_hex`, a raw 256-bit key.
+fn open_keyed(db_path: &Path, key_hex: &str) -> Result<Connection, String> {
+    let conn = Connection::open(db_path)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    
+    // Raw key syntax skips SQLCipher's own PBKDF2, the key is already random
+    conn.execute_batch(&format!("PRAGMA key = \"x'{}'\";", key_hex))
+        .map_err(|e| format!("Failed to set database key: {}", e))?;
+    
+    Ok(conn)
+}
+
+/// Settings that don't persist in the file and must be applied to every connection
+const CONNECTION_PRAGMAS: &str =
+    "PRAGMA synchronous = NORMAL;
+     PRAGMA busy_timeout = 5000;
+     PRAGMA foreign_keys = ON;";
+
+/// Quote a CSV field when it contains a delimiter, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Escape characters Markdown would otherwise treat as formatting
+fn markdown_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\`*_[]<>#|~".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape a TEXT value for iCalendar (RFC 5545 section 3.3.11)
+fn ics_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Append a content line, folded so no physical line exceeds 75 octets
+fn push_ics_line(ics: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            width = 1;
+        }
+        ics.push(c);
+        width += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+/// A `DUE` property for a stored due date: a DATE for date-only values, a floating
+/// local time for zone-less ones, and UTC for everything else
+fn ics_due(due_date: &str) -> Option<String> {
+    if let Ok(date) = NaiveDate::parse_from_str(due_date, "%Y-%m-%d") {
+        return Some(format!("DUE;VALUE=DATE:{}", date.format("%Y%m%d")));
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(due_date, "%Y-%m-%dT%H:%M:%S") {
+        return Some(format!("DUE:{}", dt.format("%Y%m%dT%H%M%S")));
+    }
+    
+    let due = DateTime::<Utc>::from_timestamp_millis(due_date_millis(due_date)?)?;
+    Some(format!("DUE:{}", due.format("%Y%m%dT%H%M%SZ")))
+}
+
+/// Non-deleted tasks in list order, for the export formats
+fn fetch_exported_tasks(conn: &Connection) -> Result<Vec<Task>, String> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} 
+         FROM tasks 
+         WHERE deleted = 0 
+         ORDER BY task_rank ASC, id ASC",
+        TASK_COLUMNS
+    )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+    
+    let tasks = stmt.query_map([], task_from_row)
+        .map_err(|e| format!("Failed to query tasks: {}", e))?;
+    
+    tasks.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect tasks: {}", e))
+}
+
+/// Per-connection tuning: WAL lets readers run alongside a writer, and the busy
+/// timeout waits out short write locks instead of failing with "database is locked".
+fn configure_connection(conn: &Connection) -> Result<(), String> {
+    let journal_mode: String = conn
+        .query_row("PRAGMA journal_mode = WAL", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to set journal mode: {}", e))?;
+    if !journal_mode.eq_ignore_ascii_case("wal") {
+        return Err(format!("Failed to enable WAL, journal mode is {}", journal_mode));
+    }
+    
+    conn.execute_batch(CONNECTION_PRAGMAS).map_err(|e| format!("Failed to configure connection: {}", e))?;
+    
+    let busy_timeout: i64 = conn
+        .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read busy timeout: {}", e))?;
+    let foreign_keys: i64 = conn
+        .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read foreign keys: {}", e))?;
+    if busy_timeout != 5000 || foreign_keys != 1 {
+        return Err("Connection pragmas did not take effect".to_string());
+    }
+    
+    Ok(())
+}
+
+/// Whether the keyed connection can actually read the file
+fn is_readable(conn: &Connection) -> bool {
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .is_ok()
+}
+
+/// Re-encrypt a database created before encryption at rest, replacing the plaintext file.
+fn encrypt_plaintext_database(db_path: &Path, key_hex: &str) -> Result<(), String> {
+    let encrypted_path = db_path.with_extension("db.encrypting");
+    let _ = std::fs::remove_file(&encrypted_path);
+    
+    {
+        let plain = Connection::open(db_path)
+            .map_err(|e| format!("Failed to open plaintext database: {}", e))?;
+        
+        if !is_readable(&plain) {
+            return Err("Database is neither plaintext nor readable with the current key".to_string());
+        }
+        
+        plain.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            params![encrypted_path.to_string_lossy(), format!("x'{}'", key_hex)],
+        ).map_err(|e| format!("Failed to attach encrypted database: {}", e))?;
+        
+        plain.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))
+            .map_err(|e| format!("Failed to export to encrypted database: {}", e))?;
+        
+        plain.execute_batch("DETACH DATABASE encrypted;")
+            .map_err(|e| format!("Failed to detach encrypted database: {}", e))?;
+    }
+    
+    std::fs::rename(&encrypted_path, db_path)
+        .map_err(|e| format!("Failed to replace plaintext database: {}", e))
+}
+
+/// Errors that mean the file itself is damaged rather than the query being wrong.
+/// A wrong key or a plaintext file shows up as `NotADatabase` instead.
+fn is_corruption(e: &rusqlite::Error) -> bool {
+    e.sqlite_error_code() == Some(ErrorCode::DatabaseCorrupt)
+}
+
+/// Run SQLite's `integrity_check`. `Ok(false)` means the file is damaged,
+/// including when the damage keeps the check itself from finishing.
+fn run_integrity_check(conn: &Connection) -> Result<bool, String> {
+    let result = conn.prepare("PRAGMA integrity_check")
+        .and_then(|mut stmt| stmt.query_map([], |row| row.get::<_, String>(0))?.collect::<Result<Vec<_>, _>>());
+    match result {
+        Ok(problems) if problems == ["ok"] => Ok(true),
+        Ok(problems) => {
+            for problem in problems.iter().flat_map(|problem| problem.lines()).take(10) {
+                log::warn!("Integrity check: {}", problem);
+            }
+            Ok(false)
+        }
+        Err(e) if is_corruption(&e) => {
+            log::warn!("Integrity check: {}", e);
+            Ok(false)
+        }
+        Err(e) => Err(format!("Failed to check integrity: {}", e)),
+    }
+}
+
+/// Tables `Database::recover` copies. The sync checkpoint is left behind so the
+/// next sync pulls everything and fills in rows that couldn't be read, and the
+/// search index is rebuilt from the copied tasks rather than copied.
+fn recoverable_tables(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master
+         WHERE type = 'table' AND name NOT LIKE 'sqlite_%' AND name != 'sync_state'
+           AND name NOT LIKE 'tasks_fts%'",
+    ).map_err(|e| format!("Failed to prepare: {}", e))?;
+    
+    let tables = stmt.query_map([], |row| row.get(0))
+        .map_err(|e| format!("Failed to list tables: {}", e))?;
+    tables.collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to list tables: {}", e))
+}
+
+/// Columns of `table`, or the error when the schema can't be read
+fn table_columns(conn: &Connection, table: &str) -> rusqlite::Result<Vec<String>> {
+    conn.prepare("SELECT name FROM pragma_table_info(?1)")?
+        .query_map(params![table], |row| row.get(0))?
+        .collect()
+}
+
+/// Copy rows of `table` from `damaged` into `fresh` until the first unreadable
+/// one. Returns how many were copied and whether the whole table was read.
+fn copy_readable_rows(damaged: &Connection, fresh: &Connection, table: &str) -> Result<(usize, bool), String> {
+    // Columns both sides have; none at all when the damaged schema is unreadable
+    let Ok(damaged_columns) = table_columns(damaged, table) else {
+        return Ok((0, false));
+    };
+    let columns: Vec<String> = table_columns(fresh, table)
+        .map_err(|e| format!("Failed to read columns of {}: {}", table, e))?
+        .into_iter()
+        .filter(|column| damaged_columns.contains(column))
+        .collect();
+    if columns.is_empty() {
+        return Ok((0, false));
+    }
+    
+    let column_list = columns.join(", ");
+    let mut insert = fresh.prepare(&format!(
+        "INSERT OR IGNORE INTO {} ({}) VALUES ({})",
+        table,
+        column_list,
+        vec!["?"; columns.len()].join(", "),
+    )).map_err(|e| format!("Failed to prepare insert into {}: {}", table, e))?;
+    
+    let Ok(mut select) = damaged.prepare(&format!("SELECT {} FROM {}", column_list, table)) else {
+        return Ok((0, false));
+    };
+    let Ok(mut rows) = select.query([]) else {
+        return Ok((0, false));
+    };
+    
+    let mut copied = 0;
+    loop {
+        let row = match rows.next() {
+            Ok(Some(row)) => row,
+            Ok(None) => return Ok((copied, true)),
+            Err(_) => return Ok((copied, false)),
+        };
+        let values = (0..columns.len())
+            .map(|i| row.get::<_, Value>(i))
+            .collect::<Result<Vec<_>, _>>();
+        let Ok(values) = values else {
+            return Ok((copied, false));
+        };
+        copied += insert.execute(params_from_iter(values))
+            .map_err(|e| format!("Failed to copy a row of {}: {}", table, e))?;
+    }
+}
+
+/// Fill the new database at `fresh_path` with what `Database::recover` can read
+/// from `db_path`. Returns the rows copied and the tables that stopped early.
+fn copy_recoverable(db_path: &Path, fresh_path: &Path, key_hex: &str) -> Result<(usize, Vec<String>), String> {
+    let damaged = open_keyed(db_path, key_hex)?;
+    let mut fresh = open_keyed(fresh_path, key_hex)?;
+    run_migrations(&mut fresh)?;
+    
+    let mut recovered_rows = 0;
+    let mut incomplete_tables = Vec::new();
+    
+    let tx = fresh.transaction()
+        .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+    for table in recoverable_tables(&tx)? {
+        let (copied, complete) = copy_readable_rows(&damaged, &tx, &table)?;
+        log::info!(table:% = table, rows = copied, complete = complete; "Recovered rows");
+        recovered_rows += copied;
+        if !complete {
+            incomplete_tables.push(table);
+        }
+    }
+    tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+    
+    // Index the copied tasks; without FTS5 the database falls back to LIKE on open
+    if let Err(e) = setup_full_text_search(&fresh) {
+        log::warn!("Recovery: search index not rebuilt ({})", e);
+    }
+    
+    Ok((recovered_rows, incomplete_tables))
+}
+
+/// Connections kept open when no pool size is given
+pub const DEFAULT_POOL_SIZE: u32 = 4;
+
+/// Bounds for how long a queued edit waits for the next one
+const MIN_UPDATE_DEBOUNCE: Duration = Duration::from_millis(100);
+const MAX_UPDATE_DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// An edit from `queue_update` waiting to be written
+struct QueuedUpdate {
+    task: Task,
+    queued_at: Instant,
+}
+
+pub struct Database {
+    /// `None` for a database from `new_in_memory`
+    path: Option<PathBuf>,
+    /// Taken by `close`; every later call fails
+    pool: RwLock<Option<Pool<SqliteConnectionManager>>>,
+    /// Keeps an in-memory database alive while pooled connections come and go
+    memory_anchor: Mutex<Option<Connection>>,
+    fts_enabled: bool,
+    history: Mutex<History>,
+    /// Edits from `queue_update` by task id, written by `flush_queued`
+    queued: Mutex<HashMap<String, QueuedUpdate>>,
+    /// Milliseconds a queued edit must sit unchanged before it's written
+    update_debounce_ms: AtomicU64,
+}
+
+impl Database {
+    /// Open the database encrypted at rest with SQLCipher. `key_hex` is the hex-encoded
+    /// 256-bit key; an existing plaintext database is migrated in place on first open.
+    pub fn new(db_path: PathBuf, key_hex: &str) -> Result<Self, String> {
+        Self::with_pool_size(db_path, key_hex, DEFAULT_POOL_SIZE)
+    }
+    
+    /// Like `new`, with `pool_size` connections so sync writes don't block UI reads
+    pub fn with_pool_size(db_path: PathBuf, key_hex: &str, pool_size: u32) -> Result<Self, String> {
+        let mut conn = open_keyed(&db_path, key_hex)?;
+        if !is_readable(&conn) {
+            drop(conn);
+            log::info!("Encrypting existing plaintext database");
+            encrypt_plaintext_database(&db_path, key_hex)?;
+            conn = open_keyed(&db_path, key_hex)?;
+            if !is_readable(&conn) {
+                return Err("Failed to open database with the encryption key".to_string());
+            }
+        }
+        configure_connection(&conn)?;
+        let fts_enabled = prepare_schema(&mut conn)?;
+        drop(conn);
+        
+        let key_pragma = format!("PRAGMA key = \"x'{}'\";", key_hex);
+        // WAL is stored in the file and was verified above; the rest is per connection
+        let manager = SqliteConnectionManager::file(&db_path).with_init(move |conn| {
+            conn.execute_batch(&key_pragma)?;
+            conn.execute_batch(CONNECTION_PRAGMAS)
+        });
+        let pool = Pool::builder()
+            .max_size(pool_size)
+            .build(manager)
+            .map_err(|e| format!("Failed to create connection pool: {}", e))?;
+        
+        Ok(Self::from_pool(Some(db_path), pool, fts_enabled, None))
+    }
+    
+    /// A database that lives only in memory and is gone once dropped, for demo
+    /// mode and tests. Nothing is encrypted since nothing reaches the disk.
+    /// It's kept in SQLite's `memdb` VFS under a unique name rather than opened
+    /// with `open_in_memory`, so that every pooled connection sees the same data.
+    pub fn new_in_memory() -> Result<Self, String> {
+        let uri = format!("file:/taskist-{}?vfs=memdb", Uuid::new_v4());
+        let flags = OpenFlags::SQLITE_OPEN_READ_WRITE
+            | OpenFlags::SQLITE_OPEN_CREATE
+            | OpenFlags::SQLITE_OPEN_URI
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+        
+        let mut conn = Connection::open_with_flags(&uri, flags)
+            .map_err(|e| format!("Failed to open in-memory database: {}", e))?;
+        conn.execute_batch(CONNECTION_PRAGMAS)
+            .map_err(|e| format!("Failed to configure connection: {}", e))?;
+        let fts_enabled = prepare_schema(&mut conn)?;
+        
+        let manager = SqliteConnectionManager::file(&uri)
+            .with_flags(flags)
+            .with_init(|conn| conn.execute_batch(CONNECTION_PRAGMAS));
+        let pool = Pool::builder()
+            .max_size(DEFAULT_POOL_SIZE)
+            .build(manager)
+            .map_err(|e| format!("Failed to create connection pool: {}", e))?;
+        
+        Ok(Self::from_pool(None, pool, fts_enabled, Some(conn)))
+    }
+    
+    fn from_pool(
+        path: Option<PathBuf>,
+        pool: Pool<SqliteConnectionManager>,
+        fts_enabled: bool,
+        memory_anchor: Option<Connection>,
+    ) -> Self {
+        Self {
+            path,
+            pool: RwLock::new(Some(pool)),
+            memory_anchor: Mutex::new(memory_anchor),
+            fts_enabled,
+            history: Mutex::new(History::new()),
+            queued: Mutex::new(HashMap::new()),
+            update_debounce_ms: AtomicU64::new(1000),
+        }
+    }
+    
+    /// Check out a connection from the pool
+    fn conn(&self) -> Result<PooledConnection<SqliteConnectionManager>, String> {
+        let pool = self.pool.read().map_err(|e| format!("Pool error: {}", e))?;
+        pool.as_ref()
+            .ok_or_else(|| "The database is closed".to_string())?
+            .get()
+            .map_err(|e| format!("Pool error: {}", e))
+    }
+    
+    /// Close the database for good. Connections still checked out close when
+    /// they're returned, and unsaved queued edits are dropped.
+    pub fn close(&self) {
+        if let Ok(mut pool) = self.pool.write() {
+            pool.take();
+        }
+        if let Ok(mut anchor) = self.memory_anchor.lock() {
+            anchor.take();
+        }
+        if let Ok(mut queued) = self.queued.lock() {
+            queued.clear();
+        }
+    }
+    
+    /// The database file with its WAL and shared memory, plus a copy left by
+    /// `recover`. Empty for an in-memory database.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let Some(path) = &self.path else {
+            return Vec::new();
+        };
+        let damaged_path = path.with_extension("db.damaged");
+        vec![
+            path.clone(),
+            sibling_path(path, "-wal"),
+            sibling_path(path, "-shm"),
+            sibling_path(&damaged_path, "-wal"),
+            sibling_path(&damaged_path, "-shm"),
+            damaged_path,
+        ]
+    }
+    
+    /// Whether the file on disk is encrypted, i.e. doesn't start with SQLite's
+    /// plaintext header. Always false for an in-memory database.
+    pub fn is_encrypted(&self) -> Result<bool, String> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+        let mut header = [0u8; 16];
+        std::fs::File::open(path)
+            .and_then(|mut file| file.read_exact(&mut header))
+            .map_err(|e| format!("Failed to read database header: {}", e))?;
+        Ok(&header != b"SQLite format 3\0")
+    }
+    
+    /// Whether this database came from `new_in_memory`
+    pub fn is_in_memory(&self) -> bool {
+        self.path.is_none()
+    }
+    
+    /// Remember a user action so it can be undone
+    fn record(&self, changes: Vec<(Task, Task)>) {
+        if let Ok(mut history) = self.history.lock() {
+            history.record(Entry { changes });
+        }
+    }
+    
+    /// Write back one side of a history entry, keeping each task's current rev
+    fn restore_states<'a>(&self, states: impl Iterator<Item = &'a Task>) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let mut restored = Vec::new();
+        let mut changes = Vec::new();
+        for state in states {
+            let current = fetch_task(&tx, &state.id)?;
+            let written = write_task(&tx, &Task { rev: current.rev.clone(), ..state.clone() })?;
+            changes.push((current, written.clone()));
+            restored.push(written);
+        }
+        
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        Ok(restored)
+    }
+    
+    /// Revert the most recent recorded action. Returns the restored tasks, or
+    /// nothing if there is no history.
+    pub fn undo_last(&self) -> Result<Vec<Task>, String> {
+        let entry = self.history.lock().map_err(|e| format!("Lock error: {}", e))?.pop_undo();
+        let Some(entry) = entry else {
+            return Ok(Vec::new());
+        };
+        
+        let result = self.restore_states(entry.changes.iter().map(|(before, _)| before));
+        
+        let mut history = self.history.lock().map_err(|e| format!("Lock error: {}", e))?;
+        match result {
+            Ok(_) => history.push_redo(entry),
+            Err(_) => history.push_undo(entry),
+        }
+        result
+    }
+    
+    /// Re-apply the most recently undone action
+    pub fn redo_last(&self) -> Result<Vec<Task>, String> {
+        let entry = self.history.lock().map_err(|e| format!("Lock error: {}", e))?.pop_redo();
+        let Some(entry) = entry else {
+            return Ok(Vec::new());
+        };
+        
+        let result = self.restore_states(entry.changes.iter().map(|(_, after)| after));
+        
+        let mut history = self.history.lock().map_err(|e| format!("Lock error: {}", e))?;
+        match result {
+            Ok(_) => history.push_undo(entry),
+            Err(_) => history.push_redo(entry),
+        }
+        result
+    }
+    
+    pub fn add_task(&self, mut new_task: NewTask) -> Result<Task, String> {
+        new_task.due_date = new_task.due_date.as_deref().map(normalize_due_date).transpose()?;
+        
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        let task = insert_task(&tx, new_task)?;
+        let change = creation(&tx, &task)?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(vec![change]);
+        Ok(task)
+    }
+    
+    /// Like `add_task`, unless an open task in the same project already has the
+    /// title, ignoring case, accents and spacing. Returns the task and whether it
+    /// was newly added.
+    pub fn add_task_unique(&self, mut new_task: NewTask, policy: DuplicatePolicy) -> Result<(Task, bool), String> {
+        new_task.due_date = new_task.due_date.as_deref().map(normalize_due_date).transpose()?;
+        
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        if let Some(existing) = find_open_duplicate(&tx, &new_task.title, new_task.project_id.as_deref())? {
+            return match policy {
+                DuplicatePolicy::ReturnExisting => Ok((existing, false)),
+                DuplicatePolicy::Reject => Err(format!("Task \"{}\" already exists", existing.title)),
+            };
+        }
+        
+        let task = insert_task(&tx, new_task)?;
+        let change = creation(&tx, &task)?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(vec![change]);
+        Ok((task, true))
+    }
+    
+    /// Copy a task into a brand-new one titled "<title> (copy)", placed right
+    /// after the original. The copy starts out incomplete.
+    pub fn duplicate_task(&self, id: &str) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let original = fetch_task(&conn, id)?;
+        if original.deleted {
+            return Err(format!("Task {} not found", id));
+        }
+        
+        // One transaction, so a failed move doesn't leave the copy at the end of the list
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let copy = insert_task(&tx, NewTask {
+            title: format!("{} (copy)", original.title),
+            description: original.description,
+            due_date: original.due_date,
+            tags: original.tags,
+            priority: original.priority,
+            recurrence: original.recurrence,
+            parent_id: original.parent_id,
+            project_id: original.project_id,
+            notes: original.notes,
+            attachments: original.attachments,
+            color: original.color,
+            estimate_minutes: original.estimate_minutes,
+        })?;
+        
+        let tasks = ordered_task_ids(&tx)?;
+        let next = tasks.iter()
+            .position(|(task_id, _)| task_id == id)
+            .and_then(|index| tasks.get(index + 1))
+            .map(|(task_id, _)| task_id.as_str())
+            .filter(|&task_id| task_id != copy.id);
+        let copy = move_between(&tx, &copy.id, Some(id), next)?;
+        let change = creation(&tx, &copy)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(vec![change]);
+        Ok(copy)
+    }
+    
+    /// Move a task's due date `add_millis` later, or to `add_millis` from now
+    /// when it has none. Completed tasks can't be snoozed.
+    pub fn snooze_task(&self, id: &str, add_millis: i64) -> Result<Task, String> {
+        if add_millis <= 0 {
+            return Err("Snooze duration must be positive".to_string());
+        }
+        
+        let conn = self.conn()?;
+        
+        let before = fetch_task(&conn, id)?;
+        if before.deleted {
+            return Err(format!("Task {} not found", id));
+        }
+        if before.completed {
+            return Err("Completed tasks can't be snoozed".to_string());
+        }
+        
+        let task = write_logged(&conn, &before, &Task {
+            due_date: Some(snoozed_due_date(before.due_date.as_deref(), add_millis)?),
+            ..before.clone()
+        })?;
+        self.record(vec![(before, task.clone())]);
+        Ok(task)
+    }
+    
+    pub fn get_all_tasks(&self) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// One page of the task list, in the same order as `get_all_tasks`. `limit`
+    /// is clamped to `1..=MAX_PAGE_SIZE` and a negative `offset` starts at 0.
+    pub fn get_tasks_page(&self, offset: i64, limit: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        // Ties on task_rank are broken by id so pages never overlap
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 
+             ORDER BY task_rank ASC, id ASC 
+             LIMIT ?1 OFFSET ?2",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![limit.clamp(1, MAX_PAGE_SIZE), offset.max(0)], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Number of tasks `get_tasks_page` pages through
+    pub fn count_tasks(&self) -> Result<i64, String> {
+        let conn = self.conn()?;
+        
+        conn.query_row(
+            "SELECT COUNT(*) FROM tasks WHERE deleted = 0 AND archived = 0",
+            [],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to count tasks: {}", e))
+    }
+    
+    /// Import tasks from the legacy `tasks.json`, keeping their ids and skipping any
+    /// already present. Returns how many were inserted.
+    pub fn import_legacy_json(&self, tasks: Vec<LegacyTask>) -> Result<usize, String> {
+        let conn = self.conn()?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let mut rank = end_of_list_rank(&tx);
+        let updated_at = Utc::now().timestamp_millis();
+        let mut imported = 0;
+        
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO tasks (id, rev, title, completed, completed_at, updated_at, created_at, task_rank, deleted, dirty)
+                 VALUES (?1, ?2, ?3, ?4, CASE WHEN ?4 THEN ?5 END, ?5, ?5, ?6, 0, 1)
+                 ON CONFLICT(id) DO NOTHING"
+            ).map_err(|e| format!("Failed to prepare import: {}", e))?;
+            
+            for task in tasks {
+                let inserted = stmt.execute(params![
+                    task.id,
+                    next_rev(None),
+                    task.title,
+                    task.completed as i32,
+                    updated_at,
+                    rank,
+                ]).map_err(|e| format!("Failed to import task {}: {}", task.id, e))?;
+                if inserted > 0 {
+                    creation(&tx, &fetch_task(&tx, &task.id)?)?;
+                    imported += 1;
+                }
+                rank = rank::between(Some(&rank), None);
+            }
+        }
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        Ok(imported)
+    }
+    
+    /// Get top-level tasks, i.e. those that aren't a subtask of anything
+    pub fn get_root_tasks(&self) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND parent_id IS NULL 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Get the direct subtasks of a task
+    pub fn get_subtasks(&self, parent_id: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND parent_id = ?1 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![parent_id], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Get all non-deleted tasks, most urgent first
+    pub fn get_all_tasks_sorted_by_priority(&self) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 
+             ORDER BY priority DESC, task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// All non-deleted tasks as CSV, header first, for spreadsheets and reports
+    pub fn export_csv(&self) -> Result<String, String> {
+        let conn = self.conn()?;
+        let tasks = fetch_exported_tasks(&conn)?;
+        
+        let mut csv = String::from("id,title,description,completed,due_date,priority,updated_at\r\n");
+        for task in tasks {
+            let updated_at = DateTime::<Utc>::from_timestamp_millis(task.updated_at)
+                .map(|dt| dt.to_rfc3339())
+                .unwrap_or_default();
+            let fields = [
+                csv_field(&task.id),
+                csv_field(&task.title),
+                csv_field(task.description.as_deref().unwrap_or("")),
+                task.completed.to_string(),
+                csv_field(task.due_date.as_deref().unwrap_or("")),
+                task.priority.label().to_string(),
+                updated_at,
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push_str("\r\n");
+        }
+        
+        Ok(csv)
+    }
+    
+    /// A Markdown checklist with open tasks under `## To-Do` and completed ones under `## Done`
+    pub fn export_markdown(&self) -> Result<String, String> {
+        let conn = self.conn()?;
+        let tasks = fetch_exported_tasks(&conn)?;
+        
+        let mut todo = String::from("## To-Do\n\n");
+        let mut done = String::from("## Done\n\n");
+        for task in tasks {
+            let (section, checkbox) = if task.completed {
+                (&mut done, "[x]")
+            } else {
+                (&mut todo, "[ ]")
+            };
+            
+            section.push_str(&format!("- {} {}", checkbox, markdown_escape(&task.title)));
+            if let Some(due_date) = &task.due_date {
+                section.push_str(&format!(" ({})", due_date));
+            }
+            section.push('\n');
+        }
+        
+        Ok(format!("{}\n{}", todo, done))
+    }
+    
+    /// An iCalendar feed with a VTODO for every open, non-archived task that has a due date
+    pub fn export_ics(&self) -> Result<String, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND completed = 0 AND due_date IS NOT NULL 
+             ORDER BY due_at ASC, task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))?;
+        
+        let mut ics = String::new();
+        push_ics_line(&mut ics, "BEGIN:VCALENDAR");
+        push_ics_line(&mut ics, "VERSION:2.0");
+        push_ics_line(&mut ics, "PRODID:-//Taskist//Taskist Desktop//EN");
+        for task in tasks {
+            // Skip due dates that can't be read rather than emit an invalid DUE
+            let Some(due) = task.due_date.as_deref().and_then(ics_due) else {
+                continue;
+            };
+            let stamp = DateTime::<Utc>::from_timestamp_millis(task.updated_at).unwrap_or_default();
+            
+            push_ics_line(&mut ics, "BEGIN:VTODO");
+            push_ics_line(&mut ics, &format!("UID:{}", task.id));
+            push_ics_line(&mut ics, &format!("DTSTAMP:{}", stamp.format("%Y%m%dT%H%M%SZ")));
+            push_ics_line(&mut ics, &format!("SUMMARY:{}", ics_escape(&task.title)));
+            if let Some(description) = task.description.as_deref().filter(|d| !d.is_empty()) {
+                push_ics_line(&mut ics, &format!("DESCRIPTION:{}", ics_escape(description)));
+            }
+            push_ics_line(&mut ics, &due);
+            push_ics_line(&mut ics, if task.completed { "STATUS:COMPLETED" } else { "STATUS:NEEDS-ACTION" });
+            // iCalendar ranks 1 (highest) to 9 (lowest), 0 meaning undefined
+            let priority = match task.priority {
+                Priority::High => 1,
+                Priority::Medium => 5,
+                Priority::Low => 9,
+                Priority::None => 0,
+            };
+            if priority > 0 {
+                push_ics_line(&mut ics, &format!("PRIORITY:{}", priority));
+            }
+            push_ics_line(&mut ics, "END:VTODO");
+        }
+        push_ics_line(&mut ics, "END:VCALENDAR");
+        
+        Ok(ics)
+    }
+    
+    /// Get all tasks ordered by creation time
+    pub fn get_all_tasks_by_created(&self, ascending: bool) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 
+             ORDER BY created_at {}, task_rank ASC, id ASC",
+            TASK_COLUMNS,
+            if ascending { "ASC" } else { "DESC" }
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Search titles and descriptions, best matches first. Uses the FTS5 index when
+    /// available (its tokenizer already ignores case and accents) and a folded
+    /// substring scan otherwise.
+    pub fn search_tasks(&self, query: &str) -> Result<Vec<Task>, String> {
+        if query.trim().is_empty() {
+            return Ok(Vec::new());
+        }
+        if !self.fts_enabled {
+            return self.search_tasks_folded(query);
+        }
+        
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             JOIN (SELECT rowid AS match_rowid, rank AS match_rank FROM tasks_fts WHERE tasks_fts MATCH ?1) 
+               ON tasks.rowid = match_rowid 
+             WHERE deleted = 0 
+             ORDER BY match_rank",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![fts_query(query)], task_from_row)
+            .map_err(|e| format!("Failed to search tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Find tasks whose title or description contains `query`, ignoring case and
+    /// accents ("cafe" finds "Café meeting"). Results keep their original text.
+    pub fn search_tasks_folded(&self, query: &str) -> Result<Vec<Task>, String> {
+        let needle = fold_text(query.trim());
+        if needle.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to search tasks: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))?;
+        
+        Ok(tasks.into_iter()
+            .filter(|task| matches_folded(task, &needle))
+            .collect())
+    }
+    
+    /// Get open, non-archived tasks whose due date falls within `[from, to]` (epoch millis)
+    pub fn get_tasks_due_within(&self, from: i64, to: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND completed = 0 AND due_at BETWEEN ?1 AND ?2 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![from, to], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Get non-archived tasks due in `[start, end)` (epoch millis), earliest first
+    pub fn get_tasks_due_between(&self, start: i64, end: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND due_at >= ?1 AND due_at < ?2 
+             ORDER BY due_at ASC, task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![start, end], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Get tasks completed in `[start, end)` (epoch millis), in the order they were finished
+    pub fn get_completed_between(&self, start: i64, end: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND completed = 1 AND completed_at >= ?1 AND completed_at < ?2 
+             ORDER BY completed_at ASC, task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![start, end], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Get open tasks whose due date is before `now` (epoch millis), most overdue first.
+    /// Tasks without a due date are never overdue.
+    pub fn get_overdue_tasks(&self, now: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND completed = 0 AND due_at < ?1 
+             ORDER BY due_at ASC, task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![now], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Open tasks for the daily view: overdue, due in `[day_start, day_end)` (epoch
+    /// millis, the user's local day) or high priority. Each task appears once,
+    /// ordered by due date then priority, with undated high priority tasks last.
+    pub fn get_today_view(&self, day_start: i64, day_end: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        // One query with OR'd conditions, so a task matching several is still one row
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND completed = 0 
+               AND (due_at < ?1 OR (due_at >= ?1 AND due_at < ?2) OR priority = ?3) 
+             ORDER BY due_at IS NULL, due_at ASC, priority DESC, task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![day_start, day_end, Priority::High], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Count tasks for the stats panel
+    pub fn get_stats(&self) -> Result<TaskStats, String> {
+        let conn = self.conn()?;
+        let now = Local::now();
+        let today = now.date_naive();
+        let week_start = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+        let week_start = Local.from_local_datetime(&week_start.and_time(NaiveTime::MIN))
+            .earliest()
+            .map_or(0, |dt| dt.timestamp_millis());
+        let now = now.timestamp_millis();
+        let day_from_now = now + 24 * 60 * 60 * 1000;
+        
+        let (total, completed, created_this_week): (i64, i64, i64) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(completed), 0), COALESCE(SUM(created_at >= ?1), 0)
+             FROM tasks WHERE deleted = 0",
+            params![week_start],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).map_err(|e| format!("Failed to count tasks: {}", e))?;
+        
+        // The unary plus keeps SQLite off idx_tasks_deleted, so idx_tasks_due_at
+        // narrows this to tasks due before tomorrow
+        let (overdue, due_soon): (i64, i64) = conn.query_row(
+            "SELECT COALESCE(SUM(due_at < ?1), 0), COALESCE(SUM(due_at >= ?1), 0)
+             FROM tasks
+             WHERE due_at < ?2 AND +deleted = 0 AND archived = 0 AND completed = 0",
+            params![now, day_from_now],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).map_err(|e| format!("Failed to count due tasks: {}", e))?;
+        
+        Ok(TaskStats {
+            total: total as usize,
+            completed: completed as usize,
+            incomplete: (total - completed) as usize,
+            overdue: overdue as usize,
+            due_soon: due_soon as usize,
+            created_this_week: created_this_week as usize,
+        })
+    }
+    
+    /// Completed tasks per local day for the streak heatmap, as `(YYYY-MM-DD, count)`
+    /// in date order. Covers `completed_at` in `[start, end)` (epoch millis); days
+    /// are shifted by `tz_offset_minutes` east of UTC so they break at local
+    /// midnight. Days without completions are left out.
+    pub fn completions_by_day(&self, start: i64, end: i64, tz_offset_minutes: i32) -> Result<Vec<(String, i64)>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT date((completed_at + ?3 * 60000) / 1000, 'unixepoch') AS day, COUNT(*)
+             FROM tasks
+             WHERE deleted = 0 AND completed = 1 AND completed_at >= ?1 AND completed_at < ?2
+             GROUP BY day
+             ORDER BY day"
+        ).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let days = stmt.query_map(params![start, end, tz_offset_minutes], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Failed to count completions: {}", e))?;
+        
+        days.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect completions: {}", e))
+    }
+    
+    /// Get non-archived tasks matching all of `filter`, in list order. Tasks
+    /// without a due date never match a due date bound.
+    pub fn query_tasks(&self, filter: &TaskFilter) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut conditions = vec!["deleted = 0", "archived = 0"];
+        let mut values: Vec<rusqlite::types::Value> = Vec::new();
+        
+        if let Some(completed) = filter.completed {
+            conditions.push("completed = ?");
+            values.push((completed as i64).into());
+        }
+        if let Some(due_before) = filter.due_before {
+            conditions.push("due_at < ?");
+            values.push(due_before.into());
+        }
+        if let Some(due_after) = filter.due_after {
+            conditions.push("due_at >= ?");
+            values.push(due_after.into());
+        }
+        if let Some(tag) = &filter.tag {
+            conditions.push("EXISTS (SELECT 1 FROM json_each(tasks.tags) WHERE json_each.value = ?)");
+            values.push(normalize_tag(tag).into());
+        }
+        
+        // Without FTS5 the search is applied to the results below instead
+        let search = filter.search.as_deref().map(str::trim).filter(|q| !q.is_empty());
+        if let Some(query) = search.filter(|_| self.fts_enabled) {
+            conditions.push("rowid IN (SELECT rowid FROM tasks_fts WHERE tasks_fts MATCH ?)");
+            values.push(fts_query(query).into());
+        }
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE {} 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS,
+            conditions.join(" AND ")
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(rusqlite::params_from_iter(values), task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))?;
+        
+        match search.filter(|_| !self.fts_enabled).map(fold_text) {
+            Some(needle) => Ok(tasks.into_iter()
+                .filter(|task| matches_folded(task, &needle))
+                .collect()),
+            None => Ok(tasks),
+        }
+    }
+    
+    /// Get all non-deleted tasks carrying the given tag
+    pub fn get_tasks_by_tag(&self, tag: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 
+               AND EXISTS (SELECT 1 FROM json_each(tasks.tags) WHERE json_each.value = ?1)
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![normalize_tag(tag)], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Every tag on a task that isn't deleted, by name, with how many tasks carry it
+    pub fn get_all_tags(&self) -> Result<Vec<TagCount>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT json_each.value, COUNT(DISTINCT tasks.id)
+             FROM tasks, json_each(tasks.tags)
+             WHERE tasks.deleted = 0
+             GROUP BY json_each.value
+             ORDER BY json_each.value"
+        ).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tags = stmt.query_map([], |row| Ok(TagCount { tag: row.get(0)?, count: row.get(1)? }))
+            .map_err(|e| format!("Failed to query tags: {}", e))?;
+        
+        tags.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tags: {}", e))
+    }
+    
+    /// Replace `old` with `new` on every task carrying it, in one transaction and
+    /// undone as one step. Returns the tasks that changed.
+    pub fn rename_tag(&self, old: &str, new: &str) -> Result<Vec<Task>, String> {
+        let (old, new) = (normalize_tag(old), normalize_tag(new));
+        if new.is_empty() {
+            return Err("Tag cannot be empty".to_string());
+        }
+        if old == new {
+            return Ok(Vec::new());
+        }
+        
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let tagged = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {} 
+                 FROM tasks 
+                 WHERE deleted = 0 
+                   AND EXISTS (SELECT 1 FROM json_each(tasks.tags) WHERE json_each.value = ?1)",
+                TASK_COLUMNS
+            )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+            
+            let tasks = stmt.query_map(params![old], task_from_row)
+                .map_err(|e| format!("Failed to query tasks: {}", e))?;
+            
+            tasks.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to collect tasks: {}", e))?
+        };
+        
+        let mut renamed = Vec::with_capacity(tagged.len());
+        let mut changes = Vec::with_capacity(tagged.len());
+        for task in tagged {
+            let tags = task.tags.iter()
+                .map(|tag| if *tag == old { new.clone() } else { tag.clone() })
+                .collect();
+            let updated = write_task(&tx, &Task { tags, ..task.clone() })?;
+            changes.push((task, updated.clone()));
+            renamed.push(updated);
+        }
+        
+        log_task_events(&tx, &changes)?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(renamed)
+    }
+    
+    /// Write `task` only if its `rev` is still the stored one, so an edit made
+    /// from a stale copy can't silently undo a newer one
+    pub fn update_task(&self, task: &Task) -> Result<Task, UpdateError> {
+        self.write_update(task, true)
+    }
+    
+    /// Like `update_task` without the rev check, for callers that just read the task
+    pub fn overwrite_task(&self, task: &Task) -> Result<Task, String> {
+        self.write_update(task, false).map_err(String::from)
+    }
+    
+    fn write_update(&self, task: &Task, check_rev: bool) -> Result<Task, UpdateError> {
+        let task = Task {
+            due_date: task.due_date.as_deref().map(normalize_due_date).transpose()?,
+            ..task.clone()
+        };
+        
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let before = fetch_task(&tx, &task.id)?;
+        if check_rev && before.rev != task.rev {
+            return Err(UpdateError::Conflict { current: Box::new(before) });
+        }
+        let updated = write_task(&tx, &task)?;
+        let changes = vec![(before, updated.clone())];
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(updated)
+    }
+    
+    /// Change only the fields set in `patch`, leaving the rest as stored
+    pub fn update_task_fields(&self, id: &str, patch: TaskPatch) -> Result<Task, String> {
+        let mut task = fetch_task(&*self.conn()?, id)?;
+        
+        // An edit still queued for the task gets the patch too, or flushing it would undo it
+        if let Ok(mut queued) = self.queued.lock() {
+            if let Some(update) = queued.get_mut(id) {
+                patch.clone().apply(&mut update.task);
+            }
+        }
+        
+        patch.apply(&mut task);
+        self.overwrite_task(&task)
+    }
+    
+    /// Hold an edit back instead of writing it, replacing any edit already queued
+    /// for the same task. `flush_queued` writes it once edits to the task stop, so
+    /// typing costs one revision and one sync push rather than one per keystroke.
+    pub fn queue_update(&self, task: Task) -> Result<Task, String> {
+        let task = Task {
+            due_date: task.due_date.as_deref().map(normalize_due_date).transpose()?,
+            ..task
+        };
+        
+        // Reject now what the flush would, since a failed flush can only be logged
+        let conn = self.conn()?;
+        if fetch_task(&conn, &task.id)?.deleted {
+            return Err(format!("Task {} is deleted", task.id));
+        }
+        validate_task(&conn, &Task { deleted: false, ..task.clone() })?;
+        
+        let mut queued = self.queued.lock().map_err(|_| "Update queue is poisoned".to_string())?;
+        queued.insert(task.id.clone(), QueuedUpdate { task: task.clone(), queued_at: Instant::now() });
+        Ok(task)
+    }
+    
+    /// Write queued edits that have been unchanged for the update debounce, or all of
+    /// them when `all` is set. Only the edited content is applied, on top of the task
+    /// as it is now, so a toggle, move or delete made meanwhile isn't undone.
+    /// Returns the tasks that changed.
+    pub fn flush_queued(&self, all: bool) -> Result<Vec<Task>, String> {
+        let debounce = self.update_debounce();
+        let due: Vec<Task> = {
+            let mut queued = self.queued.lock().map_err(|_| "Update queue is poisoned".to_string())?;
+            let ids: Vec<String> = queued.iter()
+                .filter(|(_, update)| all || update.queued_at.elapsed() >= debounce)
+                .map(|(id, _)| id.clone())
+                .collect();
+            ids.iter().filter_map(|id| queued.remove(id)).map(|update| update.task).collect()
+        };
+        
+        if due.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let conn = self.conn()?;
+        let mut written = Vec::new();
+        for edit in due {
+            let before = match fetch_task(&conn, &edit.id) {
+                Ok(task) if !task.deleted => task,
+                _ => continue,
+            };
+            let task = Task {
+                title: edit.title,
+                description: edit.description,
+                due_date: edit.due_date,
+                tags: edit.tags,
+                priority: edit.priority,
+                recurrence: edit.recurrence,
+                parent_id: edit.parent_id,
+                project_id: edit.project_id,
+                notes: edit.notes,
+                attachments: edit.attachments,
+                color: edit.color,
+                estimate_minutes: edit.estimate_minutes,
+                ..before.clone()
+            };
+            if !content_differs(&before, &task) {
+                continue;
+            }
+            
+            match write_logged(&conn, &before, &task) {
+                Ok(updated) => {
+                    self.record(vec![(before, updated.clone())]);
+                    written.push(updated);
+                }
+                Err(e) => log::error!("Queued update for {} failed: {}", task.id, e),
+            }
+        }
+        
+        Ok(written)
+    }
+    
+    /// Drop the edit queued for a task, e.g. once a newer full update was written
+    pub fn discard_queued(&self, id: &str) {
+        if let Ok(mut queued) = self.queued.lock() {
+            queued.remove(id);
+        }
+    }
+    
+    /// How long a queued edit waits for the next one, clamped to 100ms..=10s
+    pub fn set_update_debounce(&self, debounce: Duration) {
+        let debounce = debounce.clamp(MIN_UPDATE_DEBOUNCE, MAX_UPDATE_DEBOUNCE);
+        self.update_debounce_ms.store(debounce.as_millis() as u64, Ordering::Relaxed);
+    }
+    
+    pub fn update_debounce(&self) -> Duration {
+        Duration::from_millis(self.update_debounce_ms.load(Ordering::Relaxed))
+    }
+    
+    /// Replace just a task's notes without rewriting its other fields
+    pub fn set_task_notes(&self, id: &str, notes: Option<String>) -> Result<Task, String> {
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let before = fetch_task(&tx, id)?;
+        let rev = next_rev(before.rev.as_deref());
+        let updated_at = Utc::now().timestamp_millis();
+        
+        tx.execute(
+            "UPDATE tasks SET notes = ?1, rev = ?2, updated_at = ?3, dirty = 1,
+                field_updated_at = json_set(field_updated_at, '$.notes', ?3)
+             WHERE id = ?4",
+            params![notes, rev, updated_at, id],
+        ).map_err(|e| format!("Failed to update notes: {}", e))?;
+        
+        let task = Task { notes, rev: Some(rev), updated_at, ..before.clone() };
+        let changes = vec![(before, task.clone())];
+        log_task_events(&tx, &changes)?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(task)
+    }
+    
+    /// Replace just a task's color, or clear it with `None`
+    pub fn set_task_color(&self, id: &str, color: Option<String>) -> Result<Task, String> {
+        validate_color(color.as_deref())?;
+        let conn = self.conn()?;
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let before = fetch_task(&tx, id)?;
+        let rev = next_rev(before.rev.as_deref());
+        let updated_at = Utc::now().timestamp_millis();
+        
+        tx.execute(
+            "UPDATE tasks SET color = ?1, rev = ?2, updated_at = ?3, dirty = 1,
+                field_updated_at = json_set(field_updated_at, '$.color', ?3)
+             WHERE id = ?4",
+            params![color, rev, updated_at, id],
+        ).map_err(|e| format!("Failed to update color: {}", e))?;
+        
+        let task = Task { color, rev: Some(rev), updated_at, ..before.clone() };
+        let changes = vec![(before, task.clone())];
+        log_task_events(&tx, &changes)?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(task)
+    }
+    
+    /// Start timing work on a task. Fails if its timer is already running.
+    pub fn start_timer(&self, id: &str) -> Result<TimeEntry, String> {
+        let conn = self.conn()?;
+        
+        if fetch_task(&conn, id)?.deleted {
+            return Err(format!("Task {} not found", id));
+        }
+        
+        let started_at = Utc::now().timestamp_millis();
+        // The partial unique index allows one running entry per task
+        match conn.execute(
+            "INSERT INTO time_entries (task_id, started_at) VALUES (?1, ?2)",
+            params![id, started_at],
+        ) {
+            Ok(_) => Ok(TimeEntry { id: conn.last_insert_rowid(), task_id: id.to_string(), started_at, ended_at: None }),
+            Err(e) if e.sqlite_error_code() == Some(ErrorCode::ConstraintViolation) => {
+                Err(format!("Timer for task {} is already running", id))
+            }
+            Err(e) => Err(format!("Failed to start timer: {}", e)),
+        }
+    }
+    
+    /// Stop a task's running timer, returning the finished entry
+    pub fn stop_timer(&self, id: &str) -> Result<TimeEntry, String> {
+        let conn = self.conn()?;
+        
+        let ended_at = Utc::now().timestamp_millis();
+        conn.query_row(
+            "UPDATE time_entries SET ended_at = MAX(?1, started_at)
+             WHERE task_id = ?2 AND ended_at IS NULL
+             RETURNING id, task_id, started_at, ended_at",
+            params![ended_at, id],
+            |row| Ok(TimeEntry { id: row.get(0)?, task_id: row.get(1)?, started_at: row.get(2)?, ended_at: row.get(3)? }),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to stop timer: {}", e))?
+        .ok_or_else(|| format!("No timer is running for task {}", id))
+    }
+    
+    /// Total time logged on a task in milliseconds, counting a running timer up to now
+    pub fn get_time_spent(&self, id: &str) -> Result<i64, String> {
+        let conn = self.conn()?;
+        
+        conn.query_row(
+            "SELECT COALESCE(SUM(MAX(COALESCE(ended_at, ?1), started_at) - started_at), 0)
+             FROM time_entries WHERE task_id = ?2",
+            params![Utc::now().timestamp_millis(), id],
+            |row| row.get(0),
+        ).map_err(|e| format!("Failed to sum time entries: {}", e))
+    }
+    
+    /// Edit history of a task on this device, oldest first
+    pub fn get_task_history(&self, id: &str) -> Result<Vec<TaskEvent>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT id, task_id, kind, at, details FROM task_events WHERE task_id = ?1 ORDER BY id ASC"
+        ).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let events = stmt.query_map(params![id], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?, row.get::<_, i64>(3)?, row.get::<_, String>(4)?))
+        }).map_err(|e| format!("Failed to query task events: {}", e))?;
+        
+        events.map(|event| {
+            let (id, task_id, kind, at, details) = event.map_err(|e| format!("Failed to read task event: {}", e))?;
+            Ok(TaskEvent {
+                id,
+                task_id,
+                kind: TaskEventKind::parse(&kind).ok_or_else(|| format!("Unknown task event kind '{}'", kind))?,
+                at,
+                details: serde_json::from_str(&details).map_err(|e| format!("Task event {} is unreadable: {}", id, e))?,
+            })
+        }).collect()
+    }
+    
+    /// Save `tasks` as the template called `name`, replacing one with the same name
+    pub fn save_template(&self, name: &str, tasks: Vec<TemplateTask>) -> Result<Template, String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Template name cannot be empty".to_string());
+        }
+        if tasks.is_empty() {
+            return Err("A template needs at least one task".to_string());
+        }
+        if tasks.iter().any(|task| task.title.trim().is_empty()) {
+            return Err("Template tasks need a title".to_string());
+        }
+        
+        let json = serde_json::to_string(&tasks)
+            .map_err(|e| format!("Failed to serialize template: {}", e))?;
+        let updated_at = Utc::now().timestamp_millis();
+        
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO templates (name, tasks, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET tasks = excluded.tasks, updated_at = excluded.updated_at",
+            params![name, json, updated_at],
+        ).map_err(|e| format!("Failed to save template: {}", e))?;
+        
+        Ok(Template { name: name.to_string(), tasks, updated_at })
+    }
+    
+    /// Every saved template, by name
+    pub fn list_templates(&self) -> Result<Vec<Template>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare("SELECT name, tasks, updated_at FROM templates ORDER BY name COLLATE NOCASE")
+            .map_err(|e| format!("Failed to prepare: {}", e))?;
+        let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get(2)?)))
+            .map_err(|e| format!("Query error: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Collect error: {}", e))?;
+        
+        rows.into_iter()
+            .map(|(name, json, updated_at)| Ok(Template { tasks: parse_template_tasks(&name, &json)?, name, updated_at }))
+            .collect()
+    }
+    
+    pub fn delete_template(&self, name: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        
+        let deleted = conn.execute("DELETE FROM templates WHERE name = ?1", params![name.trim()])
+            .map_err(|e| format!("Failed to delete template: {}", e))?;
+        if deleted == 0 {
+            return Err(format!("No template named '{}'", name));
+        }
+        Ok(())
+    }
+    
+    /// Add the tasks of the template called `name`, with due dates offset from
+    /// now. Undone as one step.
+    pub fn instantiate_template(&self, name: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let json: String = conn.query_row("SELECT tasks FROM templates WHERE name = ?1", params![name.trim()], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to load template: {}", e))?
+            .ok_or_else(|| format!("No template named '{}'", name))?;
+        let template_tasks = parse_template_tasks(name, &json)?;
+        
+        let now = Utc::now();
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        let mut tasks = Vec::with_capacity(template_tasks.len());
+        for template_task in template_tasks {
+            let due_date = template_task.due_offset_minutes
+                .map(|minutes| {
+                    chrono::Duration::try_minutes(minutes)
+                        .and_then(|offset| now.checked_add_signed(offset))
+                        .map(|due| due.to_rfc3339_opts(SecondsFormat::Secs, true))
+                        .ok_or_else(|| format!("Due offset of {} minutes is out of range", minutes))
+                })
+                .transpose()?;
+            tasks.push(insert_task(&tx, NewTask {
+                title: template_task.title,
+                description: template_task.description,
+                due_date,
+                ..NewTask::default()
+            })?);
+        }
+        let changes = tasks.iter()
+            .map(|task| creation(&tx, task))
+            .collect::<Result<Vec<_>, _>>()?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(tasks)
+    }
+    
+    /// Soft-delete a task and its subtasks, returning them as deleted, the task first
+    pub fn delete_task(&self, id: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let updated_at = Utc::now().timestamp_millis();
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let mut removed = vec![fetch_task(&tx, id)?];
+        removed.extend(fetch_live_descendants(&tx, id)?);
+        
+        // Soft delete for sync purposes
+        tx.execute(
+            "UPDATE tasks SET deleted = 1, updated_at = ?1, dirty = 1,
+                field_updated_at = json_set(field_updated_at, '$.deleted', ?1)
+             WHERE id = ?2",
+            params![updated_at, id],
+        ).map_err(|e| format!("Failed to delete task: {}", e))?;
+        
+        cascade_delete(&tx, id, updated_at)?;
+        
+        // Subtasks were stamped with the same time by `cascade_delete`
+        let deleted: Vec<Task> = removed.iter()
+            .map(|task| Task { deleted: true, updated_at, ..task.clone() })
+            .collect();
+        let changes: Vec<_> = removed.into_iter().zip(deleted.iter().cloned()).collect();
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(deleted)
+    }
+    
+    pub fn toggle_task_completion(&self, id: &str) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        // Get current task
+        let mut task = fetch_task(&conn, id)?;
+        
+        drop(conn); // Return the connection before calling update_task
+        
+        if !task.completed && task.recurrence.is_some() {
+            return self.complete_and_spawn_next(id).map(|(completed, _)| completed);
+        }
+        
+        task.completed = !task.completed;
+        self.overwrite_task(&task)
+    }
+    
+    /// Complete a task and, if it repeats, create its next occurrence as a fresh task.
+    /// The completed instance stays in place; returns it along with the new task.
+    pub fn complete_and_spawn_next(&self, id: &str) -> Result<(Task, Option<Task>), String> {
+        let conn = self.conn()?;
+        
+        let task = fetch_task(&conn, id)?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let (completed, next) = complete_task(&tx, task.clone())?;
+        let mut changes = vec![(task, completed.clone())];
+        log_task_events(&tx, &changes)?;
+        if let Some(next) = &next {
+            changes.push(creation(&tx, next)?);
+        }
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        
+        Ok((completed, next))
+    }
+    
+    /// Complete or reopen several tasks in one transaction. Completing a recurring
+    /// task spawns its next occurrence as usual. If any id fails nothing changes.
+    pub fn bulk_set_completed(&self, ids: &[String], completed: bool) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let mut updated = Vec::with_capacity(ids.len());
+        let mut changes = Vec::with_capacity(ids.len());
+        let mut created = Vec::new();
+        for id in ids {
+            let before = fetch_task(&tx, id)?;
+            let task = if completed && !before.completed {
+                let (task, next) = complete_task(&tx, before.clone())?;
+                if let Some(next) = &next {
+                    created.push(creation(&tx, next)?);
+                }
+                task
+            } else {
+                write_task(&tx, &Task { completed, ..before.clone() })?
+            };
+            changes.push((before, task.clone()));
+            updated.push(task);
+        }
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        changes.extend(created);
+        self.record(changes);
+        Ok(updated)
+    }
+    
+    /// Soft-delete several tasks (and their subtasks) in one transaction.
+    /// If any id fails nothing changes.
+    pub fn bulk_delete(&self, ids: &[String]) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let mut deleted = Vec::with_capacity(ids.len());
+        let mut changes = Vec::with_capacity(ids.len());
+        for id in ids {
+            let task = fetch_task(&tx, id)?;
+            let subtasks = fetch_live_descendants(&tx, id)?;
+            let removed = write_task(&tx, &Task { deleted: true, ..task.clone() })?;
+            changes.push((task, removed.clone()));
+            // Subtasks go with their parent, so undo has to bring them back too
+            for subtask in subtasks {
+                changes.push((subtask.clone(), Task { deleted: true, ..subtask }));
+            }
+            deleted.push(removed);
+        }
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(deleted)
+    }
+    
+    pub fn archive_task(&self, id: &str) -> Result<Task, String> {
+        self.set_archived(id, true)
+    }
+    
+    pub fn unarchive_task(&self, id: &str) -> Result<Task, String> {
+        self.set_archived(id, false)
+    }
+    
+    fn set_archived(&self, id: &str, archived: bool) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let before = fetch_task(&conn, id)?;
+        let task = write_logged(&conn, &before, &Task { archived, ..before.clone() })?;
+        self.record(vec![(before, task.clone())]);
+        Ok(task)
+    }
+    
+    pub fn get_archived_tasks(&self) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 1 
+             ORDER BY updated_at DESC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Get soft-deleted tasks, most recently deleted first
+    pub fn get_deleted_tasks(&self) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 1 
+             ORDER BY updated_at DESC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    /// Bring a task back from the trash at the end of the list. The write is a
+    /// normal dirty edit, so the un-deletion syncs like any other change.
+    pub fn restore_task(&self, id: &str) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let before = fetch_task(&conn, id)?;
+        if !before.deleted {
+            return Err(format!("Task {} is not in the trash", id));
+        }
+        
+        // A subtask whose parent is still deleted comes back as a top-level task
+        let parent_alive = match before.parent_id.as_deref() {
+            Some(parent_id) => fetch_task(&conn, parent_id).is_ok_and(|parent| !parent.deleted),
+            None => false,
+        };
+        
+        let task = write_logged(&conn, &before, &Task {
+            deleted: false,
+            rank: end_of_list_rank(&conn),
+            parent_id: if parent_alive { before.parent_id.clone() } else { None },
+            ..before.clone()
+        })?;
+        self.record(vec![(before, task.clone())]);
+        Ok(task)
+    }
+    
+    /// Archive every completed task that hasn't been touched for `millis`.
+    /// Returns the archived tasks.
+    pub fn archive_completed_older_than(&self, millis: i64) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let cutoff = Utc::now().timestamp_millis() - millis;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let stale = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {} 
+                 FROM tasks 
+                 WHERE deleted = 0 AND archived = 0 AND completed = 1 AND updated_at < ?1",
+                TASK_COLUMNS
+            )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+            
+            let tasks = stmt.query_map(params![cutoff], task_from_row)
+                .map_err(|e| format!("Failed to query tasks: {}", e))?;
+            
+            tasks.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to collect tasks: {}", e))?
+        };
+        
+        let mut archived = Vec::with_capacity(stale.len());
+        let mut changes = Vec::with_capacity(stale.len());
+        for task in stale {
+            let updated = write_task(&tx, &Task { archived: true, ..task.clone() })?;
+            changes.push((task, updated.clone()));
+            archived.push(updated);
+        }
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        self.record(changes);
+        Ok(archived)
+    }
+    
+    /// Swap a task with its neighbour above or below. Returns the moved task and
+    /// that neighbour, whose rank stays as it was; nothing at the ends of the list.
+    pub fn reorder_task(&self, task_id: &str, direction: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let tasks = ordered_task_ids(&conn)?;
+        reorder_within(&conn, &tasks, task_id, direction)
+    }
+    
+    /// `reorder_task` within a project's view, swapping only with tasks of the
+    /// same project so other projects keep their order. Ranks are global, so in
+    /// the full list the task also jumps past other projects' tasks in between.
+    pub fn reorder_task_in_project(&self, task_id: &str, direction: &str, project_id: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let tasks = project_task_ids(&conn, project_id)?;
+        reorder_within(&conn, &tasks, task_id, direction)
+    }
+    
+    /// Move a task to a specific target position (by target task ID), returning it
+    pub fn move_task_to_position(&self, task_id: &str, target_task_id: &str) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let tasks = ordered_task_ids(&conn)?;
+        
+        let current_index = tasks.iter().position(|(id, _)| id == task_id)
+            .ok_or_else(|| "Task not found".to_string())?;
+        
+        let target_index = tasks.iter().position(|(id, _)| id == target_task_id)
+            .ok_or_else(|| "Target task not found".to_string())?;
+        
+        // If same position, nothing to do
+        if current_index == target_index {
+            return fetch_task(&conn, task_id);
+        }
+        
+        // Dragging down lands after the target, dragging up lands before it
+        let (before, after) = if current_index < target_index {
+            let after = tasks.get(target_index + 1).map(|(id, _)| id.as_str());
+            (Some(target_task_id), after)
+        } else {
+            let before = target_index.checked_sub(1).map(|i| tasks[i].0.as_str());
+            (before, Some(target_task_id))
+        };
+        
+        move_between(&conn, task_id, before, after)
+    }
+    
+    /// Move a task so it ends up at `new_index` among non-deleted tasks, e.g. where
+    /// it was dropped. Only the moved row gets a new rank; returns it.
+    pub fn move_task_to_index(&self, task_id: &str, new_index: usize) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let tasks = ordered_task_ids(&conn)?;
+        move_to_index_within(&conn, tasks, task_id, new_index)
+    }
+    
+    /// `move_task_to_index` with `new_index` counted among the project's tasks as
+    /// `get_tasks_for_project` lists them. The new rank sits between its project
+    /// neighbours in the global order, past any other projects' tasks between them.
+    pub fn move_task_to_index_in_project(&self, task_id: &str, new_index: usize, project_id: &str) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let tasks = project_task_ids(&conn, project_id)?;
+        move_to_index_within(&conn, tasks, task_id, new_index)
+    }
+    
+    /// Move several tasks, e.g. a multi-select drag, so they sit together in their
+    /// current relative order with the first at `new_index`. Only the moved rows
+    /// get new ranks, all in one transaction and with one `updated_at`. Returns the
+    /// moved tasks in their new order.
+    pub fn move_tasks_to_index(&self, task_ids: &[String], new_index: usize) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let tasks = ordered_task_ids(&conn)?;
+        
+        let selected: HashSet<&str> = task_ids.iter().map(String::as_str).collect();
+        if let Some(missing) = selected.iter().find(|id| !tasks.iter().any(|(task_id, _)| task_id == *id)) {
+            return Err(format!("Task {} not found", missing));
+        }
+        
+        let (moved, rest): (Vec<_>, Vec<_>) = tasks.iter()
+            .partition(|(id, _)| selected.contains(id.as_str()));
+        if moved.is_empty() {
+            return Ok(Vec::new());
+        }
+        if new_index > rest.len() {
+            return Err(format!("Index {} is out of range for {} tasks", new_index, rest.len()));
+        }
+        
+        // Already together at the target, e.g. dropped back where they were
+        let in_place = tasks.iter()
+            .skip(new_index)
+            .take(moved.len())
+            .all(|(id, _)| selected.contains(id.as_str()));
+        if in_place {
+            return moved.iter().map(|(id, _)| fetch_task(&conn, id)).collect();
+        }
+        
+        // Neighbours of the group once the moved tasks are out of the list
+        let after = rest.get(new_index).map(|(_, rank)| rank.as_str());
+        let mut prev = new_index.checked_sub(1).map(|i| rest[i].1.clone());
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let updated_at = Utc::now().timestamp_millis();
+        for (id, _) in &moved {
+            let new_rank = rank::between(prev.as_deref(), after);
+            tx.execute(
+                "UPDATE tasks SET task_rank = ?1, updated_at = ?2, dirty = 1,
+                    field_updated_at = json_set(field_updated_at, '$.rank', ?2)
+                 WHERE id = ?3",
+                params![new_rank, updated_at, id],
+            ).map_err(|e| format!("Failed to move task: {}", e))?;
+            prev = Some(new_rank);
+        }
+        
+        let moved = moved.iter()
+            .map(|(id, _)| fetch_task(&tx, id))
+            .collect::<Result<Vec<_>, _>>()?;
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        Ok(moved)
+    }
+    
+    /// Move a task between two neighbours, writing only the moved row.
+    /// `before_id` is the task that should end up directly above it, `after_id` directly below.
+    pub fn move_task_between(&self, task_id: &str, before_id: Option<&str>, after_id: Option<&str>) -> Result<Task, String> {
+        let conn = self.conn()?;
+        move_between(&conn, task_id, before_id, after_id)
+    }
+    
+    // Project methods
+    
+    pub fn add_project(&self, name: String, color: String) -> Result<Project, String> {
+        let conn = self.conn()?;
+        
+        let max_order: f64 = conn
+            .query_row(
+                "SELECT COALESCE(MAX(project_order), 0) FROM projects WHERE deleted = 0",
+                [],
+                |row| row.get(0)
+            )
+            .unwrap_or(0.0);
+        
+        let id = Uuid::now_v7().to_string();
+        let rev = next_rev(None);
+        let updated_at = Utc::now().timestamp_millis();
+        let order = max_order + 1.0;
+        
+        conn.execute(
+            "INSERT INTO projects (id, rev, name, color, project_order, updated_at, deleted, dirty)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, 1)",
+            params![id, rev, name, color, order, updated_at],
+        ).map_err(|e| format!("Failed to insert project: {}", e))?;
+        
+        Ok(Project {
+            id,
+            rev: Some(rev),
+            name,
+            color,
+            order,
+            updated_at,
+            deleted: false,
+        })
+    }
+    
+    pub fn get_all_projects(&self) -> Result<Vec<Project>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM projects WHERE deleted = 0 ORDER BY project_order ASC",
+            PROJECT_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let projects = stmt.query_map([], project_from_row)
+            .map_err(|e| format!("Failed to query projects: {}", e))?;
+        
+        projects.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect projects: {}", e))
+    }
+    
+    pub fn rename_project(&self, id: &str, name: String) -> Result<Project, String> {
+        let conn = self.conn()?;
+        
+        let project = fetch_project(&conn, id)?;
+        let rev = next_rev(project.rev.as_deref());
+        let updated_at = Utc::now().timestamp_millis();
+        
+        conn.execute(
+            "UPDATE projects SET name = ?1, rev = ?2, updated_at = ?3, dirty = 1 WHERE id = ?4",
+            params![name, rev, updated_at, id],
+        ).map_err(|e| format!("Failed to rename project: {}", e))?;
+        
+        Ok(Project {
+            rev: Some(rev),
+            name,
+            updated_at,
+            ..project
+        })
+    }
+    
+    /// Soft-delete a project. Its tasks move back to the inbox rather than vanishing.
+    pub fn delete_project(&self, id: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        
+        let updated_at = Utc::now().timestamp_millis();
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        tx.execute(
+            "UPDATE projects SET deleted = 1, updated_at = ?1, dirty = 1 WHERE id = ?2",
+            params![updated_at, id],
+        ).map_err(|e| format!("Failed to delete project: {}", e))?;
+        
+        let unassigned = {
+            let mut stmt = tx.prepare(&format!("SELECT {} FROM tasks WHERE project_id = ?1", TASK_COLUMNS))
+                .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+            let tasks = stmt.query_map(params![id], task_from_row)
+                .map_err(|e| format!("Failed to query project tasks: {}", e))?;
+            tasks.collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("Failed to collect project tasks: {}", e))?
+        };
+        
+        tx.execute(
+            "UPDATE tasks SET project_id = NULL, updated_at = ?1, dirty = 1,
+                field_updated_at = json_set(field_updated_at, '$.projectId', ?1)
+             WHERE project_id = ?2",
+            params![updated_at, id],
+        ).map_err(|e| format!("Failed to unassign project tasks: {}", e))?;
+        
+        let changes = unassigned.into_iter()
+            .map(|before| fetch_task(&tx, &before.id).map(|after| (before, after)))
+            .collect::<Result<Vec<_>, _>>()?;
+        log_task_events(&tx, &changes)?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))
+    }
+    
+    pub fn get_tasks_for_project(&self, project_id: &str) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE deleted = 0 AND archived = 0 AND project_id = ?1 
+             ORDER BY task_rank ASC, id ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        
+        let tasks = stmt.query_map(params![project_id], task_from_row)
+            .map_err(|e| format!("Failed to query tasks: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Failed to collect tasks: {}", e))
+    }
+    
+    // Sync-related methods
+    
+    /// Tasks changed locally that the server hasn't seen yet, including tombstones.
+    /// `dirty` alone decides, since `updated_at` is wall-clock time and can go back.
+    pub fn get_dirty_tasks(&self) -> Result<Vec<Task>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} 
+             FROM tasks 
+             WHERE dirty = 1 AND id NOT IN (SELECT task_id FROM conflicts) 
+             ORDER BY updated_at ASC",
+            TASK_COLUMNS
+        )).map_err(|e| format!("Failed to prepare: {}", e))?;
+        
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Query error: {}", e))?;
+        
+        tasks.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Collect error: {}", e))
+    }
+    
+    /// Clear the dirty flag once tasks have been pushed successfully. Each id comes
+    /// with the `updated_at` that was pushed, so a task edited while the push was in
+    /// flight stays dirty and goes out next cycle.
+    pub fn mark_clean(&self, pushed: &[(String, i64)]) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        {
+            let mut stmt = tx.prepare("UPDATE tasks SET dirty = 0 WHERE id = ?1 AND updated_at = ?2")
+                .map_err(|e| format!("Failed to prepare: {}", e))?;
+            
+            for (id, updated_at) in pushed {
+                stmt.execute(params![id, updated_at])
+                    .map_err(|e| format!("Failed to mark task clean: {}", e))?;
+            }
+        }
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))
+    }
+    
+    /// Record the revision the server assigned to a pushed task. Doesn't touch
+    /// `dirty` or `updated_at`, since the content itself hasn't changed.
+    pub fn set_task_rev(&self, id: &str, rev: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        
+        conn.execute("UPDATE tasks SET rev = ?1 WHERE id = ?2", params![rev, id])
+            .map_err(|e| format!("Failed to update rev: {}", e))?;
+        
+        Ok(())
+    }
+    
+    /// Projects with local changes the server hasn't seen yet, including tombstones
+    pub fn get_dirty_projects(&self) -> Result<Vec<Project>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM projects WHERE dirty = 1 ORDER BY updated_at ASC",
+            PROJECT_COLUMNS
+        )).map_err(|e| format!("Failed to prepare: {}", e))?;
+        
+        let projects = stmt.query_map([], project_from_row)
+            .map_err(|e| format!("Query error: {}", e))?;
+        
+        projects.collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Collect error: {}", e))
+    }
+    
+    /// Whether any task or project still has changes waiting to be pushed
+    pub fn has_unpushed_changes(&self) -> Result<bool, String> {
+        let conn = self.conn()?;
+        
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM tasks WHERE dirty = 1 AND id NOT IN (SELECT task_id FROM conflicts))
+                 OR EXISTS(SELECT 1 FROM projects WHERE dirty = 1)",
+            [],
+            |row| row.get(0),
+        ).map_err(|e| format!("Query error: {}", e))
+    }
+    
+    /// `mark_clean` for projects
+    pub fn mark_projects_clean(&self, pushed: &[(String, i64)]) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        {
+            let mut stmt = tx.prepare("UPDATE projects SET dirty = 0 WHERE id = ?1 AND updated_at = ?2")
+                .map_err(|e| format!("Failed to prepare: {}", e))?;
+            
+            for (id, updated_at) in pushed {
+                stmt.execute(params![id, updated_at])
+                    .map_err(|e| format!("Failed to mark project clean: {}", e))?;
+            }
+        }
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))
+    }
+    
+    pub fn upsert_projects_from_remote(&self, projects: &[Project]) -> Result<(), String> {
+        let mut conn = self.conn()?;
+        
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        {
+            let mut stmt = tx.prepare(UPSERT_PROJECT_SQL)
+                .map_err(|e| format!("Failed to prepare upsert: {}", e))?;
+            
+            for project in projects {
+                stmt.execute(params![
+                    project.id,
+                    project.rev,
+                    project.name,
+                    project.color,
+                    project.order,
+                    project.updated_at,
+                    project.deleted as i32,
+                ]).map_err(|e| format!("Failed to upsert project {}: {}", project.id, e))?;
+            }
+        }
+        
+        tx.commit().map_err(|e| format!("Failed to commit upsert batch: {}", e))
+    }
+    
+    #[allow(dead_code)]
+    pub fn upsert_from_remote(&self, task: &Task) -> Result<(), String> {
+        self.upsert_batch_from_remote(std::slice::from_ref(task), ConflictStrategy::default()).map(|_| ())
+    }
+    
+    /// Apply a batch of remote tasks in one transaction. Any failing row rolls
+    /// back the whole batch. Remote versions that diverge from an unpushed local
+    /// edit are handled as `strategy` says, possibly recorded as a conflict
+    /// instead of overwriting it. Returns the tasks that changed and how many
+    /// conflicts were recorded.
+    pub fn upsert_batch_from_remote(&self, tasks: &[Task], strategy: ConflictStrategy) -> Result<RemoteBatch, String> {
+        let mut conn = self.conn()?;
+        
+        let tx = conn.transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        let mut applied = RemoteBatch::default();
+        
+        {
+            let mut stmt = tx.prepare(UPSERT_TASK_SQL)
+                .map_err(|e| format!("Failed to prepare upsert: {}", e))?;
+            let mut dirty_stmt = tx.prepare(&format!(
+                "SELECT {} FROM tasks WHERE id = ?1 AND dirty = 1",
+                TASK_COLUMNS
+            )).map_err(|e| format!("Failed to prepare: {}", e))?;
+            
+            for task in tasks {
+                let local = dirty_stmt.query_row(params![task.id], task_from_row)
+                    .optional()
+                    .map_err(|e| format!("Failed to read local task {}: {}", task.id, e))?;
+                let diverged = local.as_ref().is_some_and(|local| content_differs(local, task));
+                match local {
+                    Some(local) if diverged && strategy == ConflictStrategy::LocalWins => {
+                        store_task(&tx, &keep_local(&local, task))?;
+                        continue;
+                    }
+                    Some(_) if diverged && strategy == ConflictStrategy::RemoteWins => {
+                        // Written as it is, even when older, and no longer pending a push
+                        store_task(&tx, task)?;
+                        tx.execute("UPDATE tasks SET dirty = 0 WHERE id = ?1", params![task.id])
+                            .map_err(|e| format!("Failed to apply remote task {}: {}", task.id, e))?;
+                        let ids = if task.deleted { &mut applied.deleted } else { &mut applied.upserted };
+                        ids.push(task.id.clone());
+                        continue;
+                    }
+                    Some(local) if diverged && strategy == ConflictStrategy::Manual => {
+                        record_conflict(&tx, &local, task)?;
+                        applied.conflicts += 1;
+                        continue;
+                    }
+                    Some(local) if !task.field_updated_at.is_empty() && !local.field_updated_at.is_empty() => {
+                        let merged = merge_fields(&local, task);
+                        // Local edits the server hasn't seen survive, and the row stays
+                        // dirty so the merge gets pushed. Otherwise the remote version
+                        // already is the merge and is applied as usual.
+                        if !changed_fields(&merged, task).is_empty() {
+                            if !changed_fields(&merged, &local).is_empty() {
+                                let ids = if merged.deleted { &mut applied.deleted } else { &mut applied.upserted };
+                                ids.push(task.id.clone());
+                            }
+                            store_task(&tx, &merged)?;
+                            continue;
+                        }
+                    }
+                    // Without edit times on both sides only whole versions can be compared
+                    Some(local) if diverged && is_newer(task, &local) => {
+                        record_conflict(&tx, &local, task)?;
+                        applied.conflicts += 1;
+                        continue;
+                    }
+                    _ => {}
+                }
+                
+                let changed = stmt.execute(params![
+                    task.id,
+                    task.rev,
+                    task.title,
+                    task.description,
+                    task.completed as i32,
+                    task.due_date,
+                    task.updated_at,
+                    task.rank,
+                    task.deleted as i32,
+                    tags_to_json(&task.tags)?,
+                    task.priority,
+                    task.recurrence,
+                    task.parent_id,
+                    task.project_id,
+                    task.created_at,
+                    task.archived as i32,
+                    task.due_date.as_deref().and_then(due_date_millis),
+                    task.notes,
+                    attachments_to_json(&task.attachments)?,
+                    task.completed_at,
+                    field_times_to_json(&task.field_updated_at)?,
+                    task.color,
+                    task.estimate_minutes,
+                    CLOCK_SKEW_TOLERANCE_MS,
+                ]).map_err(|e| format!("Failed to upsert task {}: {}", task.id, e))?;
+                
+                // Nothing changes when the local copy is already as new
+                if changed > 0 {
+                    let ids = if task.deleted { &mut applied.deleted } else { &mut applied.upserted };
+                    ids.push(task.id.clone());
+                }
+            }
+        }
+        
+        // Dropping an uncommitted transaction rolls it back, so early returns above undo the batch
+        tx.commit().map_err(|e| format!("Failed to commit upsert batch: {}", e))?;
+        
+        Ok(applied)
+    }
+    
+    pub fn get_conflicts(&self) -> Result<Vec<TaskConflict>, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(
+            "SELECT task_id, local, remote, detected_at FROM conflicts ORDER BY detected_at ASC"
+        ).map_err(|e| format!("Failed to prepare: {}", e))?;
+        
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        }).map_err(|e| format!("Query error: {}", e))?;
+        
+        rows.map(|row| {
+            let (task_id, local, remote, detected_at) = row.map_err(|e| format!("Collect error: {}", e))?;
+            Ok(TaskConflict {
+                task_id,
+                local: serde_json::from_str(&local).map_err(|e| format!("Invalid conflict data: {}", e))?,
+                remote: serde_json::from_str(&remote).map_err(|e| format!("Invalid conflict data: {}", e))?,
+                detected_at,
+            })
+        }).collect()
+    }
+    
+    /// Keep one side of a conflict. The winner is written as a fresh local edit
+    /// so the next push makes it the latest revision everywhere.
+    pub fn resolve_conflict(&self, task_id: &str, winner: ConflictWinner) -> Result<Task, String> {
+        let conn = self.conn()?;
+        
+        let (local, remote): (String, String) = conn.query_row(
+            "SELECT local, remote FROM conflicts WHERE task_id = ?1",
+            params![task_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).map_err(|e| format!("Conflict not found: {}", e))?;
+        
+        let chosen: Task = serde_json::from_str(match winner {
+            ConflictWinner::Local => &local,
+            ConflictWinner::Remote => &remote,
+        }).map_err(|e| format!("Invalid conflict data: {}", e))?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let before = fetch_task(&tx, task_id)?;
+        let task = write_task(&tx, &chosen)?;
+        log_task_events(&tx, &[(before, task.clone())])?;
+        
+        tx.execute("DELETE FROM conflicts WHERE task_id = ?1", params![task_id])
+            .map_err(|e| format!("Failed to clear conflict: {}", e))?;
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        Ok(task)
+    }
+    
+    /// Permanently remove tombstones untouched for `millis`. Only rows the server
+    /// has acknowledged are eligible: pushed (not dirty), not in conflict, and older
+    /// than the last successful sync. Returns how many rows were removed.
+    pub fn purge_deleted_older_than(&self, millis: i64) -> Result<usize, String> {
+        let conn = self.conn()?;
+        
+        let last_synced_at: Option<i64> = conn
+            .query_row("SELECT last_synced_at FROM sync_state WHERE id = 1", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to read sync state: {}", e))?
+            .flatten();
+        
+        // Nothing has been acknowledged by a server yet
+        let Some(last_synced_at) = last_synced_at else {
+            return Ok(0);
+        };
+        let cutoff = (Utc::now().timestamp_millis() - millis).min(last_synced_at);
+        
+        conn.execute(
+            "DELETE FROM tasks 
+             WHERE deleted = 1 AND dirty = 0 AND updated_at < ?1 
+               AND id NOT IN (SELECT task_id FROM conflicts)",
+            params![cutoff],
+        ).map_err(|e| format!("Failed to purge deleted tasks: {}", e))
+    }
+    
+    pub fn get_last_sync_seq(&self) -> Result<Option<String>, String> {
+        let conn = self.conn()?;
+        
+        match conn.query_row(
+            "SELECT last_seq FROM sync_state WHERE id = 1",
+            [],
+            |row| row.get::<_, Option<String>>(0)
+        ) {
+            Ok(seq) => Ok(seq),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(format!("Query error: {}", e)),
+        }
+    }
+    
+    pub fn set_last_sync_seq(&self, seq: &str) -> Result<(), String> {
+        let conn = self.conn()?;
+        
+        let now = Utc::now().timestamp_millis();
+        
+        conn.execute(
+            "INSERT INTO sync_state (id, last_seq, last_synced_at) VALUES (1, ?1, ?2)
+             ON CONFLICT(id) DO UPDATE SET last_seq = ?1, last_synced_at = ?2",
+            params![seq, now],
+        ).map_err(|e| format!("Failed to update sync state: {}", e))?;
+        
+        Ok(())
+    }
+    
+    /// `updated_at` of the newest task known to be on the server, 0 before the
+    /// first push. Only a record of progress; `get_dirty_tasks` doesn't filter by it.
+    pub fn get_push_checkpoint(&self) -> Result<i64, String> {
+        let conn = self.conn()?;
+        
+        match conn.query_row(
+            "SELECT last_push_at FROM sync_state WHERE id = 1",
+            [],
+            |row| row.get::<_, Option<i64>>(0)
+        ) {
+            Ok(checkpoint) => Ok(checkpoint.unwrap_or(0)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+            Err(e) => Err(format!("Query error: {}", e)),
+        }
+    }
+    
+    pub fn set_push_checkpoint(&self, checkpoint: i64) -> Result<(), String> {
+        let conn = self.conn()?;
+        
+        conn.execute(
+            "INSERT INTO sync_state (id, last_push_at) VALUES (1, ?1)
+             ON CONFLICT(id) DO UPDATE SET last_push_at = ?1",
+            params![checkpoint],
+        ).map_err(|e| format!("Failed to update push checkpoint: {}", e))?;
+        
+        Ok(())
+    }
+    
+    /// Forget how far pulls and pushes got, for when sync moves to another server
+    pub fn reset_sync_checkpoints(&self) -> Result<(), String> {
+        let conn = self.conn()?;
+        
+        conn.execute(
+            "UPDATE sync_state SET last_seq = NULL, last_push_at = NULL WHERE id = 1",
+            [],
+        ).map_err(|e| format!("Failed to reset sync state: {}", e))?;
+        
+        Ok(())
+    }
+    
+    /// Rebuild the file with `VACUUM` to give space from deleted rows back to the
+    /// OS, then truncate the WAL. Blocks for as long as the rebuild takes, and
+    /// VACUUM needs every other connection idle, so callers should keep sync
+    /// cycles out for the duration (see `SyncManager::hold_cycles`); a write that
+    /// still overlaps waits out the busy timeout and then fails one side.
+    pub fn compact(&self) -> Result<CompactStats, String> {
+        let conn = self.conn()?;
+        
+        let path: String = conn
+            .query_row("SELECT file FROM pragma_database_list WHERE name = 'main'", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to find database file: {}", e))?;
+        let size = || -> u64 {
+            [path.clone(), format!("{}-wal", path)].iter()
+                .filter_map(|file| std::fs::metadata(file).ok())
+                .map(|meta| meta.len())
+                .sum()
+        };
+        let before_bytes = size();
+        
+        // VACUUM refuses to run inside a transaction, so this goes straight to the connection
+        conn.execute_batch("VACUUM")
+            .map_err(|e| format!("Failed to vacuum: {}", e))?;
+        
+        // VACUUM may renumber the implicit rowids the search index is keyed on
+        if self.fts_enabled {
+            conn.execute("INSERT INTO tasks_fts(tasks_fts) VALUES ('rebuild')", [])
+                .map_err(|e| format!("Failed to rebuild search index: {}", e))?;
+        }
+        
+        let journal_mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read journal mode: {}", e))?;
+        if journal_mode.eq_ignore_ascii_case("wal") {
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+                .map_err(|e| format!("Failed to checkpoint WAL: {}", e))?;
+        }
+        
+        Ok(CompactStats { before_bytes, after_bytes: size() })
+    }
+    
+    /// Run SQLite's `integrity_check`. `Ok(false)` means the file is damaged.
+    pub fn integrity_check(&self) -> Result<bool, String> {
+        let conn = self.conn()?;
+        run_integrity_check(&conn)
+    }
+    
+    /// Like `integrity_check`, for the file at `db_path` before it's opened.
+    /// Damage can make `new` itself fail, so startup checks this way.
+    pub fn check_file(db_path: &Path, key_hex: &str) -> Result<bool, String> {
+        run_integrity_check(&open_keyed(db_path, key_hex)?)
+    }
+    
+    /// Copy every row that can still be read from the damaged database at
+    /// `db_path` into a fresh file with the current schema, and swap it in. The
+    /// damaged file is kept next to it as `.db.damaged`. Nothing may have the
+    /// database open meanwhile.
+    pub fn recover(db_path: &Path, key_hex: &str) -> Result<RecoveryReport, String> {
+        let fresh_path = db_path.with_extension("db.recovering");
+        let damaged_path = db_path.with_extension("db.damaged");
+        let _ = std::fs::remove_file(&fresh_path);
+        
+        let (recovered_rows, incomplete_tables) = match copy_recoverable(db_path, &fresh_path, key_hex) {
+            Ok(copied) => copied,
+            Err(e) => {
+                let _ = std::fs::remove_file(&fresh_path);
+                return Err(e);
+            }
+        };
+        
+        // The WAL and shared memory belong to the damaged file and would be replayed into the new one
+        std::fs::rename(db_path, &damaged_path)
+            .map_err(|e| format!("Failed to move damaged database aside: {}", e))?;
+        let wal_path = |path: &Path| PathBuf::from(format!("{}-wal", path.display()));
+        let _ = std::fs::rename(wal_path(db_path), wal_path(&damaged_path));
+        let _ = std::fs::remove_file(format!("{}-shm", db_path.display()));
+        if let Err(e) = std::fs::rename(&fresh_path, db_path) {
+            let _ = std::fs::rename(&damaged_path, db_path);
+            return Err(format!("Failed to replace damaged database: {}", e));
+        }
+        
+        Ok(RecoveryReport {
+            recovered_rows,
+            incomplete_tables,
+            damaged_path: damaged_path.to_string_lossy().into_owned(),
+        })
+    }
+    
+    /// Serialize every task and project, including deleted ones, plus the sync
+    /// checkpoint into a versioned JSON backup.
+    pub fn export_all(&self) -> Result<String, String> {
+        let conn = self.conn()?;
+        
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM tasks ORDER BY task_rank ASC, id ASC", TASK_COLUMNS))
+            .map_err(|e| format!("Failed to prepare: {}", e))?;
+        let tasks = stmt.query_map([], task_from_row)
+            .map_err(|e| format!("Query error: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Collect error: {}", e))?;
+        
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM projects ORDER BY project_order ASC", PROJECT_COLUMNS))
+            .map_err(|e| format!("Failed to prepare: {}", e))?;
+        let projects = stmt.query_map([], project_from_row)
+            .map_err(|e| format!("Query error: {}", e))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Collect error: {}", e))?;
+        
+        let sync_state = conn.query_row(
+            "SELECT last_seq, last_synced_at FROM sync_state WHERE id = 1",
+            [],
+            |row| Ok(BackupSyncState { last_seq: row.get(0)?, last_synced_at: row.get(1)? }),
+        ).optional().map_err(|e| format!("Query error: {}", e))?;
+        
+        let backup = Backup {
+            version: BACKUP_VERSION,
+            exported_at: Utc::now().timestamp_millis(),
+            tasks,
+            projects,
+            sync_state,
+        };
+        
+        serde_json::to_string_pretty(&backup)
+            .map_err(|e| format!("Failed to serialize backup: {}", e))
+    }
+    
+    /// Restore a backup from `export_all`. Rows are merged like remote changes,
+    /// so anything newer locally is kept. Returns how many tasks were in the backup.
+    pub fn import_all(&self, json: &str) -> Result<usize, String> {
+        let mut value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| format!("Invalid backup: {}", e))?;
+        
+        // Version 1 positioned tasks with a float `order` instead of a rank
+        if value["version"].as_u64().is_some_and(|version| version < 2) {
+            let tasks = value["tasks"].as_array_mut().into_iter().flatten();
+            for task in tasks.filter_map(|task| task.as_object_mut()) {
+                if let Some(order) = task.get("order").and_then(|order| order.as_f64()) {
+                    task.insert("rank".to_string(), rank::from_order(order).into());
+                }
+            }
+        }
+        
+        let backup: Backup = serde_json::from_value(value)
+            .map_err(|e| format!("Invalid backup: {}", e))?;
+        
+        if backup.version > BACKUP_VERSION {
+            return Err(format!(
+                "Backup version {} is newer than this app supports ({})",
+                backup.version, BACKUP_VERSION
+            ));
+        }
+        
+        self.upsert_projects_from_remote(&backup.projects)?;
+        self.upsert_batch_from_remote(&backup.tasks, ConflictStrategy::default())?;
+        
+        // Only adopt the backup's checkpoint if this install has never synced
+        if let Some(BackupSyncState { last_seq: Some(seq), .. }) = &backup.sync_state {
+            if self.get_last_sync_seq()?.is_none() {
+                self.set_last_sync_seq(seq)?;
+            }
+        }
+        
+        Ok(backup.tasks.len())
+    }
+    
+    /// Fill the database with a few projects and tasks that look like real use,
+    /// for demo mode. Due dates are relative to today; nothing can be undone.
+    pub fn seed_demo_data(&self) -> Result<(), String> {
+        let today = Local::now().date_naive();
+        let day = |offset: i64| (today + chrono::Duration::days(offset)).format("%Y-%m-%d").to_string();
+        let task = |title: &str, project: &Project| NewTask {
+            title: title.to_string(),
+            project_id: Some(project.id.clone()),
+            ..NewTask::default()
+        };
+        
+        let work = self.add_project("Work".to_string(), "#3b82f6".to_string())?;
+        let home = self.add_project("Home".to_string(), "#22c55e".to_string())?;
+        
+        let roadmap = self.add_task(NewTask {
+            description: Some("Go through the open items before Thursday's planning meeting.".to_string()),
+            due_date: Some(day(0)),
+            priority: Priority::High,
+            tags: vec!["planning".to_string()],
+            estimate_minutes: Some(90),
+            ..task("Review the quarterly roadmap", &work)
+        })?;
+        for title in ["Collect feedback from design", "Update the release timeline"] {
+            self.add_task(NewTask { parent_id: Some(roadmap.id.clone()), ..task(title, &work) })?;
+        }
+        self.add_task(NewTask {
+            due_date: Some(day(-1)),
+            priority: Priority::Medium,
+            tags: vec!["support".to_string()],
+            ..task("Answer the open customer emails", &work)
+        })?;
+        self.add_task(NewTask {
+            due_date: Some(day(2)),
+            recurrence: Some("FREQ=WEEKLY".to_string()),
+            estimate_minutes: Some(30),
+            ..task("Write the weekly status update", &work)
+        })?;
+        self.add_task(NewTask {
+            due_date: Some(day(1)),
+            tags: vec!["errands".to_string()],
+            notes: Some("Milk, eggs, coffee beans, tomatoes".to_string()),
+            ..task("Buy groceries", &home)
+        })?;
+        self.add_task(NewTask {
+            due_date: Some(day(6)),
+            priority: Priority::Low,
+            recurrence: Some("FREQ=MONTHLY".to_string()),
+            ..task("Pay the electricity bill", &home)
+        })?;
+        let dentist = self.add_task(task("Book a dentist appointment", &home))?;
+        self.toggle_task_completion(&dentist.id)?;
+        
+        if let Ok(mut history) = self.history.lock() {
+            *history = History::new();
+        }
+        Ok(())
+    }
+    
+    /// Import open tasks from a Todoist JSON export. Each Todoist id is remembered,
+    /// so importing the same export again skips what's already here. Completed and
+    /// deleted items are left out. Returns how many tasks were created.
+    pub fn import_todoist(&self, json: &str) -> Result<usize, String> {
+        let items = match serde_json::from_str(json).map_err(|e| format!("Invalid Todoist export: {}", e))? {
+            TodoistExport::Items { items } | TodoistExport::List(items) => items,
+        };
+        
+        let conn = self.conn()?;
+        
+        let tx = conn.unchecked_transaction()
+            .map_err(|e| format!("Failed to begin transaction: {}", e))?;
+        
+        let mut imported = 0;
+        for item in items.into_iter().filter(|item| !item.checked && !item.is_deleted) {
+            let source_id = item.source_id();
+            let seen: bool = tx.query_row(
+                "SELECT EXISTS(SELECT 1 FROM import_sources WHERE source = 'todoist' AND source_id = ?1)",
+                params![source_id],
+                |row| row.get(0),
+            ).map_err(|e| format!("Query error: {}", e))?;
+            if seen {
+                continue;
+            }
+            
+            // An unparseable due date drops the date rather than the task
+            let due_date = item.due.and_then(|due| normalize_due_date(&due.date).ok());
+            let task = insert_task(&tx, NewTask {
+                title: item.content,
+                description: Some(item.description).filter(|d| !d.is_empty()),
+                due_date,
+                priority: todoist_priority(item.priority),
+                ..Default::default()
+            })?;
+            creation(&tx, &task)?;
+            
+            tx.execute(
+                "INSERT INTO import_sources (source, source_id, task_id) VALUES ('todoist', ?1, ?2)",
+                params![source_id, task.id],
+            ).map_err(|e| format!("Failed to record imported task: {}", e))?;
+            imported += 1;
+        }
+        
+        tx.commit().map_err(|e| format!("Failed to commit: {}", e))?;
+        
+        Ok(imported)
+    }
 }
 
-impl Database {
-    pub fn new(db_path: PathBuf) -> Result<Self, String> {
-        let conn = Connection::open(&db_path)
-            .map_err(|e| format!("Failed to open database: {}", e))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_dir;
+    
+    fn add(db: &Database, title: &str) -> Task {
+        db.add_task(NewTask { title: title.to_string(), ..NewTask::default() }).unwrap()
+    }
+    
+    /// The task as the database has it now
+    fn stored(db: &Database, id: &str) -> Task {
+        fetch_task(&db.conn().unwrap(), id).unwrap()
+    }
+    
+    fn titles(tasks: &[Task]) -> Vec<&str> {
+        tasks.iter().map(|task| task.title.as_str()).collect()
+    }
+    
+    #[test]
+    fn moves_into_a_shrinking_gap_only_rewrite_the_moved_task() {
+        let db = Database::new_in_memory().unwrap();
+        let first = add(&db, "first");
+        let a = add(&db, "a");
+        let b = add(&db, "b");
+        let last = add(&db, "last");
+        
+        // Each move lands right after `first`, halving the gap every time
+        for i in 0..200 {
+            let (moved, next) = if i % 2 == 0 { (&b, &a) } else { (&a, &b) };
+            db.move_task_between(&moved.id, Some(&first.id), Some(&next.id)).unwrap();
+        }
+        
+        let tasks = db.get_all_tasks().unwrap();
+        assert_eq!(titles(&tasks), ["first", "a", "b", "last"]);
+        assert_eq!(tasks[0].rank, first.rank);
+        assert_eq!(tasks[3].rank, last.rank);
+        assert!(tasks.windows(2).all(|pair| pair[0].rank < pair[1].rank));
+    }
+    
+    #[test]
+    fn concurrent_reads_and_a_write_hit_no_lock_errors() {
+        let db = Database::new_in_memory().unwrap();
+        
+        // A lock error panics its thread, failing the join
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                for i in 0..50 {
+                    add(&db, &format!("task {}", i));
+                }
+            });
+            for _ in 0..3 {
+                scope.spawn(|| {
+                    for _ in 0..50 {
+                        db.get_all_tasks().unwrap();
+                    }
+                });
+            }
+        });
+        
+        assert_eq!(db.get_all_tasks().unwrap().len(), 50);
+    }
+    
+    const TEST_KEY: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+    
+    #[test]
+    fn an_open_write_on_one_connection_does_not_block_reads() {
+        // WAL lets readers past a writer; the in-memory VFS has no WAL
+        let dir = temp_dir();
+        let db = Database::with_pool_size(dir.join("tasks.db"), TEST_KEY, 2).unwrap();
+        add(&db, "original");
+        
+        let writer = db.conn().unwrap();
+        writer.execute_batch("BEGIN IMMEDIATE; UPDATE tasks SET title = 'changed';").unwrap();
+        
+        // Well under the busy timeout, and without the uncommitted edit
+        let started = Instant::now();
+        let tasks = db.get_all_tasks().unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(titles(&tasks), ["original"]);
+        
+        writer.execute_batch("ROLLBACK").unwrap();
+        drop(writer);
+        db.close();
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn csv_export_quotes_commas_and_doubles_quotes() {
+        let db = Database::new_in_memory().unwrap();
+        let task = add(&db, r#"Buy milk, eggs and "bread""#);
+        
+        let csv = db.export_csv().unwrap();
+        let row = csv.split("\r\n").nth(1).unwrap();
+        assert!(row.starts_with(&format!(r#"{},"Buy milk, eggs and ""bread""",,false,"#, task.id)), "{}", row);
+    }
+    
+    #[test]
+    fn undoing_an_update_restores_the_previous_fields_exactly() {
+        let db = Database::new_in_memory().unwrap();
+        let original = db.add_task(NewTask {
+            title: "Write report".to_string(),
+            description: Some("Quarterly numbers".to_string()),
+            due_date: Some("2026-03-01".to_string()),
+            tags: vec!["work".to_string()],
+            priority: Priority::High,
+            ..NewTask::default()
+        }).unwrap();
+        
+        db.overwrite_task(&Task {
+            title: "Write the annual report".to_string(),
+            description: None,
+            due_date: Some("2026-12-31".to_string()),
+            tags: vec!["work".to_string(), "urgent".to_string()],
+            priority: Priority::Low,
+            ..original.clone()
+        }).unwrap();
+        db.undo_last().unwrap();
+        
+        let restored = stored(&db, &original.id);
+        assert_eq!(restored.title, original.title);
+        assert_eq!(restored.description, original.description);
+        assert_eq!(restored.due_date, original.due_date);
+        assert_eq!(restored.tags, original.tags);
+        assert_eq!(restored.priority, original.priority);
+        assert!(restored.updated_at >= original.updated_at);
+    }
+    
+    #[test]
+    fn undoing_a_delete_brings_the_task_back() {
+        let db = Database::new_in_memory().unwrap();
+        let task = add(&db, "keep me");
+        
+        db.delete_task(&task.id).unwrap();
+        db.undo_last().unwrap();
         
-        // Initialize the database schema
+        let restored = stored(&db, &task.id);
+        assert!(!restored.deleted);
+        assert_eq!(restored.title, "keep me");
+    }
+    
+    #[test]
+    fn a_database_from_before_versioning_migrates_without_losing_tasks() {
+        // The oldest shape: no tags, priority, projects or version number
+        let mut conn = Connection::open_in_memory().unwrap();
         conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS tasks (
+            "CREATE TABLE tasks (
                 id TEXT PRIMARY KEY,
                 rev TEXT,
                 title TEXT NOT NULL,
@@ -46,352 +4886,690 @@ impl Database {
                 task_order INTEGER NOT NULL,
                 deleted INTEGER NOT NULL DEFAULT 0
             );
-            
-            CREATE TABLE IF NOT EXISTS sync_state (
-                id INTEGER PRIMARY KEY CHECK (id = 1),
-                last_seq TEXT,
-                last_synced_at INTEGER
-            );
-            
-            CREATE INDEX IF NOT EXISTS idx_tasks_updated_at ON tasks(updated_at);
-            CREATE INDEX IF NOT EXISTS idx_tasks_deleted ON tasks(deleted);
-            "
-        ).map_err(|e| format!("Failed to create tables: {}", e))?;
+            INSERT INTO tasks (id, rev, title, description, completed, due_date, updated_at, task_order)
+            VALUES ('a', '3-x', 'Second', 'details', 1, '2024-05-01', 1000, 2.5),
+                   ('b', NULL, 'First', NULL, 0, NULL, 2000, 1);"
+        ).unwrap();
+        
+        run_migrations(&mut conn).unwrap();
+        
+        let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+        
+        let second = fetch_task(&conn, "a").unwrap();
+        assert_eq!(second.title, "Second");
+        assert_eq!(second.rev.as_deref(), Some("3-x"));
+        assert_eq!(second.description.as_deref(), Some("details"));
+        assert!(second.completed);
+        assert_eq!(second.due_date.as_deref(), Some("2024-05-01"));
+        assert_eq!(second.created_at, 1000);
         
-        Ok(Self { conn: Mutex::new(conn) })
+        let first = fetch_task(&conn, "b").unwrap();
+        assert_eq!(first.title, "First");
+        assert!(first.rank < second.rank);
     }
     
-    pub fn add_task(&self, title: String, description: Option<String>, due_date: Option<String>) -> Result<Task, String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    /// A due date `hours` from now, as the frontend sends it
+    fn due_in_hours(hours: i64) -> Option<String> {
+        Some((Utc::now() + chrono::Duration::hours(hours)).to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+    
+    fn add_due(db: &Database, title: &str, due_date: Option<String>) -> Task {
+        db.add_task(NewTask { title: title.to_string(), due_date, ..NewTask::default() }).unwrap()
+    }
+    
+    #[test]
+    fn stats_count_seeded_tasks() {
+        let db = Database::new_in_memory().unwrap();
+        add_due(&db, "overdue", due_in_hours(-1));
+        add_due(&db, "due soon", due_in_hours(2));
+        add_due(&db, "due next week", due_in_hours(24 * 7));
+        let done = add_due(&db, "done late", due_in_hours(-24));
+        db.toggle_task_completion(&done.id).unwrap();
+        let archived = add_due(&db, "archived and overdue", due_in_hours(-3));
+        db.archive_task(&archived.id).unwrap();
+        let deleted = add_due(&db, "deleted and overdue", due_in_hours(-2));
+        db.delete_task(&deleted.id).unwrap();
         
-        // Get max order
-        let max_order: i32 = conn
-            .query_row(
-                "SELECT COALESCE(MAX(task_order), 0) FROM tasks WHERE deleted = 0",
-                [],
-                |row| row.get(0)
-            )
-            .unwrap_or(0);
+        assert_eq!(db.get_stats().unwrap(), TaskStats {
+            total: 5,
+            completed: 1,
+            incomplete: 4,
+            overdue: 1,
+            due_soon: 1,
+            created_this_week: 5,
+        });
+    }
+    
+    #[test]
+    fn folded_search_ignores_case_and_accents() {
+        let db = Database::new_in_memory().unwrap();
+        add(&db, "Café with Sam");
+        add(&db, "Read about naïve Bayes");
+        add(&db, "Water the plants");
         
-        let id = Uuid::now_v7().to_string();
-        let rev = format!("1-{}", Uuid::new_v4().to_string().replace("-", "")[..32].to_string());
-        let updated_at = Utc::now().timestamp_millis();
-        let order = max_order + 1;
+        assert_eq!(titles(&db.search_tasks_folded("cafe").unwrap()), ["Café with Sam"]);
+        assert_eq!(titles(&db.search_tasks_folded("NAIVE").unwrap()), ["Read about naïve Bayes"]);
+        assert_eq!(titles(&db.search_tasks_folded("Naïve").unwrap()), ["Read about naïve Bayes"]);
+        assert!(db.search_tasks_folded("cafes").unwrap().is_empty());
+    }
+    
+    #[test]
+    fn pages_cover_every_task_exactly_once() {
+        let db = Database::new_in_memory().unwrap();
+        for i in 0..23 {
+            add(&db, &format!("task {}", i));
+        }
+        // Tied ranks, as when two devices append at once, must still split cleanly
+        db.conn().unwrap().execute("UPDATE tasks SET task_rank = 'V' WHERE rowid % 3 = 0", []).unwrap();
         
-        conn.execute(
-            "INSERT INTO tasks (id, rev, title, description, completed, due_date, updated_at, task_order, deleted)
-             VALUES (?1, ?2, ?3, ?4, 0, ?5, ?6, ?7, 0)",
-            params![id, rev, title, description, due_date, updated_at, order],
-        ).map_err(|e| format!("Failed to insert task: {}", e))?;
+        let mut paged = Vec::new();
+        for offset in (0..db.count_tasks().unwrap()).step_by(5) {
+            let page = db.get_tasks_page(offset, 5).unwrap();
+            assert!(page.len() <= 5);
+            paged.extend(page.into_iter().map(|task| task.id));
+        }
         
-        Ok(Task {
-            id,
-            rev: Some(rev),
-            title,
-            description,
-            completed: false,
-            due_date,
-            updated_at,
-            order,
-            deleted: false,
-        })
+        let all: Vec<String> = db.get_all_tasks().unwrap().into_iter().map(|task| task.id).collect();
+        assert_eq!(all.len(), 23);
+        assert_eq!(paged, all);
     }
     
-    pub fn get_all_tasks(&self) -> Result<Vec<Task>, String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn query_filters_combine() {
+        let db = Database::new_in_memory().unwrap();
+        let tagged = |title: &str, tag: &str, hours: i64| db.add_task(NewTask {
+            title: title.to_string(),
+            tags: vec![tag.to_string()],
+            due_date: due_in_hours(hours),
+            ..NewTask::default()
+        }).unwrap();
+        tagged("Send report", "work", 2);
+        tagged("Pay invoice", "work", 48);
+        tagged("Gym", "health", 2);
+        let old = tagged("Draft old report", "work", -1);
+        db.toggle_task_completion(&old.id).unwrap();
+        let now = Utc::now().timestamp_millis();
         
-        let mut stmt = conn.prepare(
-            "SELECT id, rev, title, description, completed, due_date, updated_at, task_order, deleted 
-             FROM tasks 
-             WHERE deleted = 0 
-             ORDER BY task_order ASC"
-        ).map_err(|e| format!("Failed to prepare statement: {}", e))?;
+        let open_work = TaskFilter { completed: Some(false), tag: Some("Work".to_string()), ..TaskFilter::default() };
+        assert_eq!(titles(&db.query_tasks(&open_work).unwrap()), ["Send report", "Pay invoice"]);
         
-        let tasks = stmt.query_map([], |row| {
-            Ok(Task {
-                id: row.get(0)?,
-                rev: row.get(1)?,
-                title: row.get(2)?,
-                description: row.get(3)?,
-                completed: row.get::<_, i32>(4)? != 0,
-                due_date: row.get(5)?,
-                updated_at: row.get(6)?,
-                order: row.get(7)?,
-                deleted: row.get::<_, i32>(8)? != 0,
-            })
-        }).map_err(|e| format!("Failed to query tasks: {}", e))?;
+        let due_today = TaskFilter { due_before: Some(now + 24 * 60 * 60 * 1000), ..open_work };
+        assert_eq!(titles(&db.query_tasks(&due_today).unwrap()), ["Send report"]);
         
-        tasks.collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Failed to collect tasks: {}", e))
+        let done_reports = TaskFilter {
+            completed: Some(true),
+            tag: Some("work".to_string()),
+            search: Some("report".to_string()),
+            ..TaskFilter::default()
+        };
+        assert_eq!(titles(&db.query_tasks(&done_reports).unwrap()), ["Draft old report"]);
+    }
+    
+    /// Unfolded content lines of an iCalendar document, checking the RFC 5545
+    /// line rules on the way
+    fn ics_lines(ics: &str) -> Vec<String> {
+        assert!(ics.ends_with("\r\n"));
+        let mut lines: Vec<String> = Vec::new();
+        for physical in ics.trim_end_matches("\r\n").split("\r\n") {
+            assert!(physical.len() <= 75, "line too long: {}", physical);
+            match physical.strip_prefix(' ') {
+                Some(continuation) => lines.last_mut().unwrap().push_str(continuation),
+                None => lines.push(physical.to_string()),
+            }
+        }
+        lines
     }
     
-    pub fn update_task(&self, task: &Task) -> Result<Task, String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn ics_export_is_one_well_formed_calendar() {
+        let db = Database::new_in_memory().unwrap();
+        let title = format!("Call Ana, Bo; and {}", "a very long title ".repeat(6));
+        db.add_task(NewTask {
+            title: title.clone(),
+            due_date: Some("2026-03-01".to_string()),
+            priority: Priority::High,
+            ..NewTask::default()
+        }).unwrap();
+        add_due(&db, "Timed", Some("2026-03-02T09:30:00Z".to_string()));
+        add(&db, "No due date");
         
-        // Increment revision
-        let rev_num: i32 = task.rev
-            .as_ref()
-            .and_then(|r| r.split('-').next())
-            .and_then(|n| n.parse().ok())
-            .unwrap_or(0) + 1;
-        let new_rev = format!("{}-{}", rev_num, Uuid::new_v4().to_string().replace("-", "")[..32].to_string());
-        let updated_at = Utc::now().timestamp_millis();
+        let lines = ics_lines(&db.export_ics().unwrap());
         
-        conn.execute(
-            "UPDATE tasks SET 
-                rev = ?1, 
-                title = ?2, 
-                description = ?3, 
-                completed = ?4, 
-                due_date = ?5, 
-                updated_at = ?6, 
-                task_order = ?7,
-                deleted = ?8
-             WHERE id = ?9",
-            params![
-                new_rev,
-                task.title,
-                task.description,
-                task.completed as i32,
-                task.due_date,
-                updated_at,
-                task.order,
-                task.deleted as i32,
-                task.id
-            ],
-        ).map_err(|e| format!("Failed to update task: {}", e))?;
-        
-        Ok(Task {
-            id: task.id.clone(),
-            rev: Some(new_rev),
-            title: task.title.clone(),
-            description: task.description.clone(),
-            completed: task.completed,
-            due_date: task.due_date.clone(),
-            updated_at,
-            order: task.order,
-            deleted: task.deleted,
-        })
+        // Components nest properly, with exactly one calendar around everything
+        let mut open: Vec<&str> = Vec::new();
+        let mut calendars = 0;
+        let mut todos: Vec<Vec<&str>> = Vec::new();
+        for line in &lines {
+            if let Some(component) = line.strip_prefix("BEGIN:") {
+                if component == "VCALENDAR" {
+                    assert!(open.is_empty());
+                    calendars += 1;
+                } else {
+                    assert_eq!(open, ["VCALENDAR"]);
+                    todos.push(Vec::new());
+                }
+                open.push(component);
+            } else if let Some(component) = line.strip_prefix("END:") {
+                assert_eq!(open.pop(), Some(component));
+            } else {
+                assert!(!open.is_empty(), "property outside the calendar: {}", line);
+                if open.len() == 2 {
+                    todos.last_mut().unwrap().push(line);
+                }
+            }
+        }
+        assert!(open.is_empty());
+        assert_eq!(calendars, 1);
+        assert!(lines.contains(&"VERSION:2.0".to_string()));
+        assert!(lines.iter().any(|line| line.starts_with("PRODID:")));
+        
+        assert_eq!(todos.len(), 2);
+        for todo in &todos {
+            for property in ["UID:", "DTSTAMP:", "SUMMARY:", "DUE"] {
+                assert_eq!(todo.iter().filter(|line| line.starts_with(property)).count(), 1, "{} in {:?}", property, todo);
+            }
+        }
+        assert!(todos[0].contains(&format!("SUMMARY:{}", title.replace(',', "\\,").replace(';', "\\;")).as_str()));
+        assert!(todos[0].contains(&"DUE;VALUE=DATE:20260301"));
+        assert!(todos[0].contains(&"PRIORITY:1"));
+        assert!(todos[1].contains(&"DUE:20260302T093000Z"));
     }
     
-    pub fn delete_task(&self, id: &str) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
-        
-        let updated_at = Utc::now().timestamp_millis();
+    /// A task that was pushed, then edited locally with `edit`, plus the pushed
+    /// version for building the remote side from
+    fn pushed_then_edited(db: &Database, edit: impl FnOnce(&mut Task)) -> (Task, Task) {
+        let pushed = add(db, "Plan trip");
+        db.mark_clean(&[(pushed.id.clone(), pushed.updated_at)]).unwrap();
+        let mut local = pushed.clone();
+        edit(&mut local);
+        (pushed, db.overwrite_task(&local).unwrap())
+    }
+    
+    /// `base` as another device saved it after editing `field` at `at`
+    fn remote_edit(base: &Task, at: i64, field: &str, edit: impl FnOnce(&mut Task)) -> Task {
+        let mut remote = Task { rev: Some("2-remote".to_string()), updated_at: at, ..base.clone() };
+        remote.field_updated_at.insert(field.to_string(), at);
+        edit(&mut remote);
+        remote
+    }
+    
+    #[test]
+    fn concurrent_edits_to_different_fields_both_survive() {
+        let db = Database::new_in_memory().unwrap();
+        let (pushed, local) = pushed_then_edited(&db, |task| task.title = "Plan the trip".to_string());
+        let remote = remote_edit(&pushed, local.updated_at + 60_000, "dueDate", |task| {
+            task.due_date = Some("2026-06-01".to_string());
+        });
         
-        // Soft delete for sync purposes
-        conn.execute(
-            "UPDATE tasks SET deleted = 1, updated_at = ?1 WHERE id = ?2",
-            params![updated_at, id],
-        ).map_err(|e| format!("Failed to delete task: {}", e))?;
+        let batch = db.upsert_batch_from_remote(&[remote], ConflictStrategy::NewestWins).unwrap();
         
-        Ok(())
+        assert_eq!(batch.conflicts, 0);
+        let merged = stored(&db, &local.id);
+        assert_eq!(merged.title, "Plan the trip");
+        assert_eq!(merged.due_date.as_deref(), Some("2026-06-01"));
+        // The merge is new to the server too
+        assert!(db.get_dirty_tasks().unwrap().iter().any(|task| task.id == local.id));
     }
     
-    pub fn toggle_task_completion(&self, id: &str) -> Result<Task, String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn concurrent_edits_to_the_same_field_keep_the_later_one() {
+        let db = Database::new_in_memory().unwrap();
+        let (pushed, local) = pushed_then_edited(&db, |task| task.title = "Plan the trip".to_string());
+        let remote = remote_edit(&pushed, local.updated_at + 60_000, "title", |task| {
+            task.title = "Plan the road trip".to_string();
+        });
         
-        // Get current task
-        let mut task: Task = conn.query_row(
-            "SELECT id, rev, title, description, completed, due_date, updated_at, task_order, deleted 
-             FROM tasks WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Task {
-                    id: row.get(0)?,
-                    rev: row.get(1)?,
-                    title: row.get(2)?,
-                    description: row.get(3)?,
-                    completed: row.get::<_, i32>(4)? != 0,
-                    due_date: row.get(5)?,
-                    updated_at: row.get(6)?,
-                    order: row.get(7)?,
-                    deleted: row.get::<_, i32>(8)? != 0,
-                })
-            }
-        ).map_err(|e| format!("Task not found: {}", e))?;
+        let batch = db.upsert_batch_from_remote(&[remote], ConflictStrategy::NewestWins).unwrap();
         
-        drop(conn); // Release lock before calling update_task
+        assert_eq!(batch.conflicts, 0);
+        assert_eq!(stored(&db, &local.id).title, "Plan the road trip");
+    }
+    
+    #[test]
+    fn today_view_lists_an_overdue_high_priority_task_once() {
+        let db = Database::new_in_memory().unwrap();
+        let with_priority = |title: &str, hours: Option<i64>, priority: Priority| db.add_task(NewTask {
+            title: title.to_string(),
+            due_date: hours.and_then(due_in_hours),
+            priority,
+            ..NewTask::default()
+        }).unwrap();
+        with_priority("overdue and urgent", Some(-2), Priority::High);
+        with_priority("due later today", Some(1), Priority::None);
+        with_priority("urgent, no date", None, Priority::High);
+        with_priority("next week", Some(24 * 7), Priority::Low);
         
-        task.completed = !task.completed;
-        self.update_task(&task)
+        let now = Utc::now().timestamp_millis();
+        let today = db.get_today_view(now - 60 * 60 * 1000, now + 2 * 60 * 60 * 1000).unwrap();
+        
+        assert_eq!(titles(&today), ["overdue and urgent", "due later today", "urgent, no date"]);
+    }
+    
+    /// A pushed task stored locally as revision `rev` saved at `at`
+    fn synced_at(db: &Database, rev: &str, at: i64) -> Task {
+        let task = add(db, "Plan trip");
+        db.conn().unwrap().execute(
+            "UPDATE tasks SET rev = ?1, updated_at = ?2, dirty = 0 WHERE id = ?3",
+            params![rev, at, task.id],
+        ).unwrap();
+        stored(db, &task.id)
     }
     
-    pub fn reorder_task(&self, task_id: &str, direction: &str) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn versions_saved_at_the_same_moment_are_ordered_by_revision() {
+        let db = Database::new_in_memory().unwrap();
+        let local = synced_at(&db, "2-aaa", 1_000_000);
+        let remote = Task { rev: Some("2-bbb".to_string()), title: "Plan the trip".to_string(), ..local.clone() };
         
-        // Get all tasks sorted by order
-        let mut stmt = conn.prepare(
-            "SELECT id, task_order FROM tasks WHERE deleted = 0 ORDER BY task_order ASC"
-        ).map_err(|e| format!("Failed to prepare: {}", e))?;
+        // Both devices agree on the winner whichever copy they hold
+        assert!(is_newer(&remote, &local));
+        assert!(!is_newer(&local, &remote));
         
-        let tasks: Vec<(String, i32)> = stmt.query_map([], |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        }).map_err(|e| format!("Query error: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Collect error: {}", e))?;
+        db.upsert_batch_from_remote(std::slice::from_ref(&remote), ConflictStrategy::NewestWins).unwrap();
+        assert_eq!(stored(&db, &local.id).title, "Plan the trip");
         
-        let current_index = tasks.iter().position(|(id, _)| id == task_id)
-            .ok_or_else(|| "Task not found".to_string())?;
+        // The losing version arriving later changes nothing
+        let stale = Task { rev: Some("2-aaa".to_string()), title: "Plan trip".to_string(), ..remote };
+        let batch = db.upsert_batch_from_remote(&[stale], ConflictStrategy::NewestWins).unwrap();
+        assert!(batch.upserted.is_empty());
+        assert_eq!(stored(&db, &local.id).title, "Plan the trip");
+    }
+    
+    #[test]
+    fn a_slow_clock_within_the_tolerance_does_not_hide_a_newer_revision() {
+        let db = Database::new_in_memory().unwrap();
+        let local = synced_at(&db, "2-aaa", 1_000_000);
+        // Saved later, by a device whose clock runs three seconds behind
+        let skewed = Task {
+            rev: Some("3-bbb".to_string()),
+            title: "Plan the trip".to_string(),
+            updated_at: local.updated_at - 3_000,
+            ..local.clone()
+        };
         
-        let target_index = if direction == "up" {
-            if current_index == 0 { return Ok(()); }
-            current_index - 1
-        } else {
-            if current_index >= tasks.len() - 1 { return Ok(()); }
-            current_index + 1
+        assert!(is_newer(&skewed, &local));
+        db.upsert_batch_from_remote(&[skewed], ConflictStrategy::NewestWins).unwrap();
+        assert_eq!(stored(&db, &local.id).title, "Plan the trip");
+    }
+    
+    #[test]
+    fn timestamps_further_apart_than_the_tolerance_decide() {
+        let db = Database::new_in_memory().unwrap();
+        let local = synced_at(&db, "2-aaa", 1_000_000);
+        let older = Task {
+            rev: Some("3-bbb".to_string()),
+            title: "Plan the trip".to_string(),
+            updated_at: local.updated_at - CLOCK_SKEW_TOLERANCE_MS - 1,
+            ..local.clone()
         };
         
-        let updated_at = Utc::now().timestamp_millis();
-        let current_order = tasks[current_index].1;
-        let target_order = tasks[target_index].1;
+        assert!(!is_newer(&older, &local));
+        let batch = db.upsert_batch_from_remote(&[older], ConflictStrategy::NewestWins).unwrap();
+        assert!(batch.upserted.is_empty());
+        assert_eq!(stored(&db, &local.id).title, "Plan trip");
+    }
+    
+    #[test]
+    fn moving_the_last_task_to_the_start_puts_it_first() {
+        let db = Database::new_in_memory().unwrap();
+        for title in ["a", "b", "c", "d"] {
+            add(&db, title);
+        }
+        let last = db.get_all_tasks().unwrap().pop().unwrap();
         
-        // Swap orders
-        conn.execute(
-            "UPDATE tasks SET task_order = ?1, updated_at = ?2 WHERE id = ?3",
-            params![target_order, updated_at, task_id],
-        ).map_err(|e| format!("Failed to update current task: {}", e))?;
+        let moved = db.move_task_to_index(&last.id, 0).unwrap();
         
-        conn.execute(
-            "UPDATE tasks SET task_order = ?1, updated_at = ?2 WHERE id = ?3",
-            params![current_order, updated_at, tasks[target_index].0],
-        ).map_err(|e| format!("Failed to update target task: {}", e))?;
+        let tasks = db.get_all_tasks().unwrap();
+        assert_eq!(titles(&tasks), ["d", "a", "b", "c"]);
+        assert_eq!(tasks[0].rank, moved.rank);
+        assert!(tasks.windows(2).all(|pair| pair[0].rank < pair[1].rank));
+    }
+    
+    #[test]
+    fn patches_keep_set_or_clear_each_field() {
+        let db = Database::new_in_memory().unwrap();
+        let task = db.add_task(NewTask {
+            title: "Plan trip".to_string(),
+            description: Some("Book flights".to_string()),
+            due_date: Some("2026-06-01".to_string()),
+            ..NewTask::default()
+        }).unwrap();
+        let patch = |json: &str| serde_json::from_str::<TaskPatch>(json).unwrap();
         
-        Ok(())
+        let kept = db.update_task_fields(&task.id, patch(r#"{"title": "Plan the trip"}"#)).unwrap();
+        assert_eq!(kept.description.as_deref(), Some("Book flights"));
+        assert_eq!(kept.due_date.as_deref(), Some("2026-06-01"));
+        
+        let set = db.update_task_fields(&task.id, patch(r#"{"description": "Book trains", "dueDate": "2026-07-01"}"#)).unwrap();
+        assert_eq!(set.description.as_deref(), Some("Book trains"));
+        assert_eq!(set.due_date.as_deref(), Some("2026-07-01"));
+        
+        let cleared = db.update_task_fields(&task.id, patch(r#"{"description": null, "dueDate": null}"#)).unwrap();
+        assert_eq!(cleared.description, None);
+        assert_eq!(cleared.due_date, None);
+        assert_eq!(stored(&db, &task.id).title, "Plan the trip");
     }
     
-    /// Move a task to a specific target position (by target task ID)
-    pub fn move_task_to_position(&self, task_id: &str, target_task_id: &str) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn updating_from_a_stale_copy_is_a_conflict() {
+        let db = Database::new_in_memory().unwrap();
+        let stale = add(&db, "Plan trip");
+        let saved = db.update_task(&Task { title: "Plan the trip".to_string(), ..stale.clone() }).unwrap();
         
-        // Get all tasks sorted by order
-        let mut stmt = conn.prepare(
-            "SELECT id, task_order FROM tasks WHERE deleted = 0 ORDER BY task_order ASC"
-        ).map_err(|e| format!("Failed to prepare: {}", e))?;
+        let result = db.update_task(&Task { title: "Plan a trip".to_string(), ..stale });
         
-        let tasks: Vec<(String, i32)> = stmt.query_map([], |row| {
-            Ok((row.get(0)?, row.get(1)?))
-        }).map_err(|e| format!("Query error: {}", e))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Collect error: {}", e))?;
+        match result {
+            Err(UpdateError::Conflict { current }) => assert_eq!(current.rev, saved.rev),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+        assert_eq!(stored(&db, &saved.id).title, "Plan the trip");
+    }
+    
+    #[test]
+    fn scattered_tasks_move_together_into_the_middle() {
+        let db = Database::new_in_memory().unwrap();
+        let tasks: Vec<Task> = ["a", "b", "c", "d", "e", "f", "g"].iter().map(|title| add(&db, title)).collect();
+        let ids = |titles: &[&str]| -> Vec<String> {
+            tasks.iter().filter(|task| titles.contains(&task.title.as_str())).map(|task| task.id.clone()).collect()
+        };
         
-        let current_index = tasks.iter().position(|(id, _)| id == task_id)
-            .ok_or_else(|| "Task not found".to_string())?;
+        let moved = db.move_tasks_to_index(&ids(&["b", "d", "g"]), 2).unwrap();
         
-        let target_index = tasks.iter().position(|(id, _)| id == target_task_id)
-            .ok_or_else(|| "Target task not found".to_string())?;
+        assert_eq!(titles(&moved), ["b", "d", "g"]);
+        assert!(moved.iter().all(|task| task.updated_at == moved[0].updated_at));
+        let after = db.get_all_tasks().unwrap();
+        assert_eq!(titles(&after), ["a", "c", "b", "d", "g", "e", "f"]);
+        assert!(after.windows(2).all(|pair| pair[0].rank < pair[1].rank));
+        // Only the moved tasks were rewritten
+        for task in after.iter().filter(|task| ["a", "c", "e", "f"].contains(&task.title.as_str())) {
+            let before = tasks.iter().find(|before| before.id == task.id).unwrap();
+            assert_eq!((&task.rank, task.updated_at), (&before.rank, before.updated_at));
+        }
+    }
+    
+    #[test]
+    fn completions_are_counted_on_the_local_day() {
+        let db = Database::new_in_memory().unwrap();
+        let utc = |day: u32, hour: u32, minute: u32| Utc.with_ymd_and_hms(2026, 3, day, hour, minute, 0).unwrap().timestamp_millis();
+        for at in [utc(1, 21, 30), utc(1, 22, 30), utc(1, 23, 0), utc(2, 0, 30)] {
+            let task = add(&db, "done");
+            db.conn().unwrap().execute(
+                "UPDATE tasks SET completed = 1, completed_at = ?1 WHERE id = ?2",
+                params![at, task.id],
+            ).unwrap();
+        }
+        let (start, end) = (utc(1, 0, 0), utc(3, 0, 0));
         
-        // If same position, nothing to do
-        if current_index == target_index {
-            return Ok(());
+        // Local midnight two hours east of UTC falls at 22:00 UTC
+        assert_eq!(db.completions_by_day(start, end, 120).unwrap(), [("2026-03-01".to_string(), 1), ("2026-03-02".to_string(), 3)]);
+        assert_eq!(db.completions_by_day(start, end, 0).unwrap(), [("2026-03-01".to_string(), 3), ("2026-03-02".to_string(), 1)]);
+        // and at 01:00 UTC the next day an hour west of it
+        assert_eq!(db.completions_by_day(start, end, -60).unwrap(), [("2026-03-01".to_string(), 4)]);
+    }
+    
+    #[test]
+    fn revisions_count_up_and_malformed_ones_start_over() {
+        let generation = |rev: &str| parse_rev(rev).map(|(n, _)| n);
+        
+        assert_eq!(generation(&next_rev(Some("1-abc"))), Some(2));
+        assert_eq!(generation(&next_rev(Some("41-abc"))), Some(42));
+        for malformed in [None, Some(""), Some("abc"), Some("x-abc"), Some("0-abc"), Some("-3-abc"), Some("2-")] {
+            assert_eq!(generation(&next_rev(malformed)), Some(1), "{:?}", malformed);
         }
         
-        let updated_at = Utc::now().timestamp_millis();
+        // Every save moves the stored task up one generation
+        let db = Database::new_in_memory().unwrap();
+        let task = add(&db, "Plan trip");
+        let saved = db.update_task(&Task { title: "Plan the trip".to_string(), ..task.clone() }).unwrap();
+        assert_eq!(rev_number(saved.rev.as_deref()), rev_number(task.rev.as_deref()) + 1);
+    }
+    
+    #[test]
+    fn adding_a_duplicate_title_finds_the_open_task() {
+        let db = Database::new_in_memory().unwrap();
+        let existing = add(&db, "Call Zoë");
+        let titled = |title: &str| NewTask { title: title.to_string(), ..NewTask::default() };
         
-        // Swap the two tasks' orders directly
-        let current_order = tasks[current_index].1;
-        let target_order = tasks[target_index].1;
+        let (found, added) = db.add_task_unique(titled("  call   ZOE "), DuplicatePolicy::ReturnExisting).unwrap();
+        assert!(!added);
+        assert_eq!(found.id, existing.id);
+        assert!(db.add_task_unique(titled("CALL ZOË"), DuplicatePolicy::Reject).is_err());
+        assert_eq!(db.get_all_tasks().unwrap().len(), 1);
+    }
+    
+    #[test]
+    fn adding_a_title_only_a_done_or_other_project_task_has_adds_it() {
+        let db = Database::new_in_memory().unwrap();
+        let done = add(&db, "Call Zoë");
+        db.toggle_task_completion(&done.id).unwrap();
+        let project = db.add_project("Home".to_string(), "#336699".to_string()).unwrap();
         
-        conn.execute(
-            "UPDATE tasks SET task_order = ?1, updated_at = ?2 WHERE id = ?3",
-            params![target_order, updated_at, task_id],
-        ).map_err(|e| format!("Failed to update dragged task: {}", e))?;
+        let (task, added) = db.add_task_unique(NewTask { title: "Call Zoë".to_string(), ..NewTask::default() }, DuplicatePolicy::Reject).unwrap();
+        assert!(added);
+        assert_ne!(task.id, done.id);
         
-        conn.execute(
-            "UPDATE tasks SET task_order = ?1, updated_at = ?2 WHERE id = ?3",
-            params![current_order, updated_at, target_task_id],
-        ).map_err(|e| format!("Failed to update target task: {}", e))?;
+        let (_, added) = db.add_task_unique(NewTask {
+            title: "Call Zoë".to_string(),
+            project_id: Some(project.id),
+            ..NewTask::default()
+        }, DuplicatePolicy::Reject).unwrap();
+        assert!(added);
+    }
+    
+    #[test]
+    fn a_truncated_file_is_reported_as_damaged() {
+        let dir = temp_dir();
+        let path = dir.join("tasks.db");
+        {
+            let db = Database::new(path.clone(), TEST_KEY).unwrap();
+            for i in 0..200 {
+                add(&db, &format!("task {} with a title long enough to fill pages", i));
+            }
+            db.conn().unwrap().execute_batch("PRAGMA wal_checkpoint(TRUNCATE)").unwrap();
+        }
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(len / 2).unwrap();
         
-        Ok(())
+        assert_eq!(Database::check_file(&path, TEST_KEY), Ok(false));
+        
+        std::fs::remove_dir_all(&dir).unwrap();
     }
     
-    // Sync-related methods
-    #[allow(dead_code)]
-    pub fn get_changes_since(&self, since: i64) -> Result<Vec<Task>, String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    /// A task edited locally to "Plan the trip" and remotely, later, to
+    /// "Plan the road trip", run through a batch with `strategy`
+    fn diverged_with(strategy: ConflictStrategy) -> (Database, RemoteBatch, Task) {
+        let db = Database::new_in_memory().unwrap();
+        let (pushed, local) = pushed_then_edited(&db, |task| task.title = "Plan the trip".to_string());
+        let remote = remote_edit(&pushed, local.updated_at + 60_000, "title", |task| {
+            task.title = "Plan the road trip".to_string();
+        });
+        let batch = db.upsert_batch_from_remote(&[remote], strategy).unwrap();
+        (db, batch, local)
+    }
+    
+    fn is_dirty(db: &Database, id: &str) -> bool {
+        db.get_dirty_tasks().unwrap().iter().any(|task| task.id == id)
+    }
+    
+    #[test]
+    fn newest_wins_takes_the_later_edit() {
+        let (db, batch, local) = diverged_with(ConflictStrategy::NewestWins);
         
-        let mut stmt = conn.prepare(
-            "SELECT id, rev, title, description, completed, due_date, updated_at, task_order, deleted 
-             FROM tasks 
-             WHERE updated_at > ?1 
-             ORDER BY updated_at ASC"
-        ).map_err(|e| format!("Failed to prepare: {}", e))?;
+        assert_eq!(batch.conflicts, 0);
+        assert_eq!(stored(&db, &local.id).title, "Plan the road trip");
+    }
+    
+    #[test]
+    fn local_wins_keeps_the_local_edit_to_push() {
+        let (db, batch, local) = diverged_with(ConflictStrategy::LocalWins);
         
-        let tasks = stmt.query_map(params![since], |row| {
-            Ok(Task {
-                id: row.get(0)?,
-                rev: row.get(1)?,
-                title: row.get(2)?,
-                description: row.get(3)?,
-                completed: row.get::<_, i32>(4)? != 0,
-                due_date: row.get(5)?,
-                updated_at: row.get(6)?,
-                order: row.get(7)?,
-                deleted: row.get::<_, i32>(8)? != 0,
-            })
-        }).map_err(|e| format!("Query error: {}", e))?;
+        assert_eq!(batch.conflicts, 0);
+        let kept = stored(&db, &local.id);
+        assert_eq!(kept.title, "Plan the trip");
+        // Newer than the remote version, so the server takes it
+        assert!(kept.updated_at > local.updated_at + 60_000);
+        assert!(is_dirty(&db, &local.id));
+    }
+    
+    #[test]
+    fn remote_wins_drops_the_local_edit() {
+        let (db, batch, local) = diverged_with(ConflictStrategy::RemoteWins);
         
-        tasks.collect::<Result<Vec<_>, _>>()
-            .map_err(|e| format!("Collect error: {}", e))
+        assert_eq!(batch.conflicts, 0);
+        assert_eq!(batch.upserted, std::slice::from_ref(&local.id));
+        assert_eq!(stored(&db, &local.id).title, "Plan the road trip");
+        assert!(!is_dirty(&db, &local.id));
     }
     
-    pub fn upsert_from_remote(&self, task: &Task) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn manual_records_a_conflict_and_changes_nothing() {
+        let (db, batch, local) = diverged_with(ConflictStrategy::Manual);
         
-        conn.execute(
-            "INSERT INTO tasks (id, rev, title, description, completed, due_date, updated_at, task_order, deleted)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
-             ON CONFLICT(id) DO UPDATE SET
-                rev = excluded.rev,
-                title = excluded.title,
-                description = excluded.description,
-                completed = excluded.completed,
-                due_date = excluded.due_date,
-                updated_at = excluded.updated_at,
-                task_order = excluded.task_order,
-                deleted = excluded.deleted
-             WHERE excluded.updated_at > tasks.updated_at",
-            params![
-                task.id,
-                task.rev,
-                task.title,
-                task.description,
-                task.completed as i32,
-                task.due_date,
-                task.updated_at,
-                task.order,
-                task.deleted as i32,
-            ],
-        ).map_err(|e| format!("Failed to upsert task: {}", e))?;
+        assert_eq!(batch.conflicts, 1);
+        assert_eq!(stored(&db, &local.id).title, "Plan the trip");
+        let conflicts = db.get_conflicts().unwrap();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].task_id, local.id);
+        assert_eq!(conflicts[0].local.title, "Plan the trip");
+        assert_eq!(conflicts[0].remote.title, "Plan the road trip");
+    }
+    
+    #[test]
+    fn instantiated_templates_are_due_at_their_offsets_from_now() {
+        let db = Database::new_in_memory().unwrap();
+        let step = |title: &str, due_offset_minutes: Option<i64>| TemplateTask {
+            title: title.to_string(),
+            description: None,
+            due_offset_minutes,
+        };
+        db.save_template(" Weekly review ", vec![
+            step("Clear inbox", Some(60)),
+            step("Plan next week", Some(24 * 60)),
+            step("Tidy desk", None),
+        ]).unwrap();
         
-        Ok(())
+        let before = Utc::now().timestamp_millis() / 1000 * 1000;
+        let tasks = db.instantiate_template("Weekly review").unwrap();
+        let after = Utc::now().timestamp_millis();
+        
+        assert_eq!(titles(&tasks), ["Clear inbox", "Plan next week", "Tidy desk"]);
+        let due = |task: &Task| task.due_date.as_deref().and_then(due_date_millis);
+        for (task, minutes) in tasks.iter().zip([60, 24 * 60]) {
+            let offset = minutes * 60_000;
+            let due = due(task).unwrap();
+            assert!((before + offset..=after + offset).contains(&due), "{} due at {}", task.title, due);
+        }
+        assert_eq!(tasks[2].due_date, None);
     }
     
-    pub fn get_last_sync_seq(&self) -> Result<Option<String>, String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    fn tagged(db: &Database, title: &str, tags: &[&str]) -> Task {
+        db.add_task(NewTask {
+            title: title.to_string(),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            ..NewTask::default()
+        }).unwrap()
+    }
+    
+    #[test]
+    fn tags_are_stored_normalized() {
+        let db = Database::new_in_memory().unwrap();
         
-        match conn.query_row(
-            "SELECT last_seq FROM sync_state WHERE id = 1",
-            [],
-            |row| row.get::<_, String>(0)
-        ) {
-            Ok(seq) => Ok(Some(seq)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(format!("Query error: {}", e)),
+        let task = tagged(&db, "Plan trip", &[" Work ", "work", "Side   Project", "  "]);
+        
+        assert_eq!(task.tags, ["work", "side project"]);
+        assert_eq!(stored(&db, &task.id).tags, ["work", "side project"]);
+    }
+    
+    #[test]
+    fn renaming_a_tag_merges_it_into_an_existing_one() {
+        let db = Database::new_in_memory().unwrap();
+        tagged(&db, "a", &["work"]);
+        tagged(&db, "b", &["work", "job"]);
+        tagged(&db, "c", &["home"]);
+        
+        let renamed = db.rename_tag(" WORK", "Job ").unwrap();
+        
+        assert_eq!(renamed.len(), 2);
+        assert!(renamed.iter().all(|task| task.tags == ["job"]));
+        let tags: Vec<(String, usize)> = db.get_all_tags().unwrap().into_iter().map(|tag| (tag.tag, tag.count)).collect();
+        assert_eq!(tags, [("home".to_string(), 1), ("job".to_string(), 2)]);
+        assert!(db.rename_tag("job", "  ").is_err());
+    }
+    
+    #[test]
+    fn projects_are_ordered_independently() {
+        let db = Database::new_in_memory().unwrap();
+        let home = db.add_project("Home".to_string(), "#336699".to_string()).unwrap();
+        let work = db.add_project("Work".to_string(), "#993366".to_string()).unwrap();
+        let mut added = Vec::new();
+        // Interleaved, so each project's neighbours have the other's tasks between them
+        for title in ["h1", "w1", "h2", "w2", "h3", "w3"] {
+            let project = if title.starts_with('h') { &home } else { &work };
+            added.push(db.add_task(NewTask {
+                title: title.to_string(),
+                project_id: Some(project.id.clone()),
+                ..NewTask::default()
+            }).unwrap());
         }
+        let id = |title: &str| added.iter().find(|task| task.title == title).unwrap().id.clone();
+        let listed = |project: &Project| db.get_tasks_for_project(&project.id).unwrap();
+        
+        db.move_task_to_index_in_project(&id("h3"), 0, &home.id).unwrap();
+        assert_eq!(titles(&listed(&home)), ["h3", "h1", "h2"]);
+        assert_eq!(titles(&listed(&work)), ["w1", "w2", "w3"]);
+        
+        db.reorder_task_in_project(&id("w1"), "down", &work.id).unwrap();
+        assert_eq!(titles(&listed(&work)), ["w2", "w1", "w3"]);
+        assert_eq!(titles(&listed(&home)), ["h3", "h1", "h2"]);
     }
     
-    pub fn set_last_sync_seq(&self, seq: &str) -> Result<(), String> {
-        let conn = self.conn.lock().map_err(|e| format!("Lock error: {}", e))?;
+    #[test]
+    fn creating_editing_and_completing_are_three_events() {
+        let db = Database::new_in_memory().unwrap();
+        let task = add(&db, "Plan trip");
+        db.update_task_fields(&task.id, TaskPatch { title: Some("Plan the trip".to_string()), ..TaskPatch::default() }).unwrap();
+        db.toggle_task_completion(&task.id).unwrap();
         
-        let now = Utc::now().timestamp_millis();
+        let history = db.get_task_history(&task.id).unwrap();
         
-        conn.execute(
-            "INSERT INTO sync_state (id, last_seq, last_synced_at) VALUES (1, ?1, ?2)
-             ON CONFLICT(id) DO UPDATE SET last_seq = ?1, last_synced_at = ?2",
-            params![seq, now],
-        ).map_err(|e| format!("Failed to update sync state: {}", e))?;
+        let kinds: Vec<TaskEventKind> = history.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds, [TaskEventKind::Created, TaskEventKind::Updated, TaskEventKind::Completed]);
+        assert_eq!(history[1].details["title"], serde_json::json!(["Plan trip", "Plan the trip"]));
+        assert!(history.windows(2).all(|pair| pair[0].at <= pair[1].at));
+    }
+    
+    fn event_kinds(db: &Database, id: &str) -> Vec<TaskEventKind> {
+        db.get_task_history(id).unwrap().iter().map(|event| event.kind).collect()
+    }
+    
+    #[test]
+    fn imports_project_deletion_and_conflict_resolution_are_in_the_history() {
+        let db = Database::new_in_memory().unwrap();
         
-        Ok(())
+        db.import_legacy_json(vec![LegacyTask { id: "legacy".to_string(), title: "Old task".to_string(), completed: false }]).unwrap();
+        assert_eq!(event_kinds(&db, "legacy"), [TaskEventKind::Created]);
+        
+        db.import_todoist(r#"[{"id": "1", "content": "From Todoist"}]"#).unwrap();
+        let imported = db.get_all_tasks().unwrap().into_iter().find(|task| task.title == "From Todoist").unwrap();
+        assert_eq!(event_kinds(&db, &imported.id), [TaskEventKind::Created]);
+        
+        let project = db.add_project("Home".to_string(), "#336699".to_string()).unwrap();
+        let task = db.add_task(NewTask {
+            title: "Fix the shelf".to_string(),
+            project_id: Some(project.id.clone()),
+            ..NewTask::default()
+        }).unwrap();
+        db.delete_project(&project.id).unwrap();
+        let history = db.get_task_history(&task.id).unwrap();
+        assert_eq!(history.last().unwrap().kind, TaskEventKind::Updated);
+        assert_eq!(history.last().unwrap().details["projectId"], serde_json::json!([project.id, null]));
+        
+        let (db, _, local) = diverged_with(ConflictStrategy::Manual);
+        let logged = event_kinds(&db, &local.id).len();
+        db.resolve_conflict(&local.id, ConflictWinner::Remote).unwrap();
+        let history = db.get_task_history(&local.id).unwrap();
+        assert_eq!(history.len(), logged + 1);
+        assert_eq!(history.last().unwrap().details["title"], serde_json::json!(["Plan the trip", "Plan the road trip"]));
     }
 }
@@ -1,3 +1,5 @@
+use crate::database::ConflictStrategy;
+use crate::util::{self, sibling_path, FileError};
 use aes_gcm::{
     aead::{Aead, KeyInit},
     Aes256Gcm, Nonce,
@@ -5,11 +7,15 @@ use aes_gcm::{
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard};
 
 const KEY_SIZE: usize = 32; // AES-256
 const NONCE_SIZE: usize = 12; // GCM standard nonce size
+#[cfg(feature = "keychain")]
+const KEYCHAIN_SERVICE: &str = "taskist-sync";
 
 /// Sync mode options
 /// - "local" = SQLite only, no sync
@@ -24,12 +30,108 @@ pub struct SyncSettings {
     pub sync_username: String,
     pub sync_password: String,
     pub sync_db_name: String,
+    /// Upper bound for the retry backoff after consecutive sync errors
+    #[serde(default = "default_max_backoff_secs")]
+    pub max_backoff_secs: u64,
+    /// Wait on CouchDB's long-poll changes feed instead of polling every few seconds
+    #[serde(default = "default_long_poll")]
+    pub long_poll: bool,
+    /// Seconds between sync cycles; 0 means manual only (see `sync_interval`)
+    #[serde(default = "default_sync_interval_secs")]
+    pub sync_interval_secs: u64,
+    /// Keep syncing on a metered connection; when false, cycles are skipped
+    /// while the OS reports one (see `metered::is_metered`)
+    #[serde(default = "default_sync_on_metered")]
+    pub sync_on_metered: bool,
+    /// Changes fetched per `_changes` request when pulling. Each page is applied
+    /// and checkpointed on its own, so an interrupted first sync resumes.
+    #[serde(default = "default_pull_batch_size")]
+    pub pull_batch_size: usize,
+    /// CouchDB filter (`design/name`) applied to the pulled changes feed. A
+    /// missing design document is created with `sync::TASK_FILTER_SOURCE`,
+    /// which passes only `type == "task"` documents (and untyped legacy ones).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_filter: Option<String>,
+    /// What a pull does with remote versions that diverge from unpushed local edits
+    #[serde(default)]
+    pub conflict_strategy: ConflictStrategy,
+    /// Keychain account holding `sync_password` when it's kept out of `settings.enc`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keychain_account: Option<String>,
+    /// Set on load when the stored password couldn't be recovered
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub credential_error: Option<String>,
+    /// PEM file with an extra CA to trust, for servers behind a private CA
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_cert_path: Option<String>,
+    /// PEM client certificate and key for servers that require mutual TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_cert_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_key_path: Option<String>,
+    /// Skip server certificate verification entirely. Only for self-signed
+    /// setups the user explicitly opted into; it allows interception.
+    #[serde(default)]
+    pub accept_invalid_certs: bool,
+    /// `http://`, `https://` or `socks5://` proxy for sync traffic. When unset the
+    /// usual `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` environment variables apply.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_url: Option<String>,
+    /// API token for `cloud` mode, sent as a bearer token instead of basic auth
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    /// Set by `redacted` in place of the secrets it blanks out
+    #[serde(default, skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    pub has_password: bool,
+    #[serde(default, skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    pub has_auth_token: bool,
 }
 
 fn default_sync_mode() -> String {
     "local".to_string()
 }
 
+fn default_max_backoff_secs() -> u64 {
+    300
+}
+
+fn default_long_poll() -> bool {
+    true
+}
+
+fn default_sync_interval_secs() -> u64 {
+    5
+}
+
+fn default_sync_on_metered() -> bool {
+    true
+}
+
+fn default_pull_batch_size() -> usize {
+    500
+}
+
+/// CouchDB's rule for database names: `^[a-z][a-z0-9_$()+/-]*$`
+fn is_valid_db_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_lowercase())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "_$()+/-".contains(c))
+}
+
+/// Split a `design/name` filter into its design document and function name
+pub fn split_filter(filter: &str) -> Option<(&str, &str)> {
+    let (design, name) = filter.split_once('/')?;
+    let valid = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || "_-".contains(c));
+    (valid(design) && valid(name)).then_some((design, name))
+}
+
+/// Largest `pull_batch_size`; bigger pages risk the request timing out
+const MAX_PULL_BATCH_SIZE: usize = 10_000;
+
+/// Bounds for a non-zero `sync_interval_secs`
+const MIN_SYNC_INTERVAL_SECS: u64 = 2;
+const MAX_SYNC_INTERVAL_SECS: u64 = 24 * 60 * 60;
+
 impl SyncSettings {
     pub fn default_settings() -> Self {
         Self {
@@ -38,17 +140,249 @@ impl SyncSettings {
             sync_username: "admin".to_string(),
             sync_password: "admin".to_string(),
             sync_db_name: "tasks_db".to_string(),
+            max_backoff_secs: default_max_backoff_secs(),
+            sync_interval_secs: default_sync_interval_secs(),
+            long_poll: default_long_poll(),
+            sync_on_metered: default_sync_on_metered(),
+            pull_batch_size: default_pull_batch_size(),
+            sync_filter: None,
+            conflict_strategy: ConflictStrategy::default(),
+            keychain_account: None,
+            credential_error: None,
+            ca_cert_path: None,
+            client_cert_path: None,
+            client_key_path: None,
+            accept_invalid_certs: false,
+            proxy_url: None,
+            auth_token: None,
+            has_password: false,
+            has_auth_token: false,
+        }
+    }
+    
+    /// Copy without the password or API token, safe to hand to the frontend
+    pub fn redacted(&self) -> Self {
+        Self {
+            sync_password: String::new(),
+            auth_token: None,
+            has_password: !self.sync_password.is_empty(),
+            has_auth_token: self.auth_token.is_some(),
+            ..self.clone()
+        }
+    }
+    
+    /// Fill in secrets left blank by the frontend from the stored settings, so
+    /// saving a redacted copy doesn't wipe them
+    pub fn keep_secrets_from(&mut self, stored: &SyncSettings) {
+        if self.sync_password.is_empty() {
+            self.sync_password = stored.sync_password.clone();
+        }
+        if self.auth_token.as_deref().is_none_or(str::is_empty) {
+            self.auth_token = stored.auth_token.clone();
         }
     }
     
     pub fn is_sync_enabled(&self) -> bool {
         self.sync_mode != "local"
     }
+    
+    /// Check the settings are usable before they're saved, collecting every problem
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut errors = Vec::new();
+        
+        let url = self.sync_url.trim();
+        if url.is_empty() {
+            if self.is_sync_enabled() {
+                errors.push("Sync URL is required".to_string());
+            }
+        } else {
+            // Same default scheme the sync client applies
+            let full_url = if url.starts_with("http://") || url.starts_with("https://") {
+                url.to_string()
+            } else {
+                format!("http://{}", url)
+            };
+            if let Err(e) = reqwest::Url::parse(&full_url) {
+                errors.push(format!("Sync URL '{}' is invalid: {}", url, e));
+            }
+        }
+        
+        if !is_valid_db_name(&self.sync_db_name) {
+            errors.push(format!(
+                "Database name '{}' must start with a lowercase letter and contain only a-z, 0-9 and _$()+-/",
+                self.sync_db_name
+            ));
+        }
+        
+        if let Some(filter) = self.sync_filter() {
+            if split_filter(filter).is_none() {
+                errors.push(format!("Sync filter '{}' must look like design/name", filter));
+            }
+        }
+        
+        if !(1..=MAX_PULL_BATCH_SIZE).contains(&self.pull_batch_size) {
+            errors.push(format!("Pull batch size must be between 1 and {}", MAX_PULL_BATCH_SIZE));
+        }
+        
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+    
+    /// `sync_filter`, treating a blank one as unset
+    pub fn sync_filter(&self) -> Option<&str> {
+        self.sync_filter.as_deref().map(str::trim).filter(|filter| !filter.is_empty())
+    }
+    
+    /// `sync_interval_secs` clamped to a sane range, keeping 0 for manual only
+    pub fn clamped_sync_interval_secs(&self) -> u64 {
+        match self.sync_interval_secs {
+            0 => 0,
+            secs => secs.clamp(MIN_SYNC_INTERVAL_SECS, MAX_SYNC_INTERVAL_SECS),
+        }
+    }
+}
+
+/// Profile that settings saved before profiles existed are moved into
+pub const DEFAULT_PROFILE: &str = "Default";
+
+/// Sync settings saved under a name, e.g. one per server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProfile {
+    pub name: String,
+    pub settings: SyncSettings,
+}
+
+/// Everything stored in `settings.enc`: the saved profiles and the one sync uses
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProfiles {
+    pub active: String,
+    pub profiles: Vec<SyncProfile>,
+}
+
+impl SyncProfiles {
+    fn single(settings: SyncSettings) -> Self {
+        Self {
+            active: DEFAULT_PROFILE.to_string(),
+            profiles: vec![SyncProfile { name: DEFAULT_PROFILE.to_string(), settings }],
+        }
+    }
+    
+    pub fn get(&self, name: &str) -> Option<&SyncProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+}
+
+/// `settings.enc` held a single `SyncSettings` before profiles
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSyncSettings {
+    Profiles(SyncProfiles),
+    Single(Box<SyncSettings>),
+}
+
+/// App-wide preferences that aren't tied to a sync server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct AppSettings {
+    /// How long before a task's due date to emit a reminder
+    #[serde(default = "default_reminder_minutes")]
+    pub reminder_minutes: u32,
+    /// Global accelerator that opens the quick add window, `None` to disable
+    #[serde(default = "default_quick_add_shortcut")]
+    pub quick_add_shortcut: Option<String>,
+    /// Receives a POST for every added, completed or deleted task
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook_url: Option<String>,
+    /// How long edits sent through `queue_task_update` wait for the next keystroke
+    #[serde(default = "default_update_debounce_ms")]
+    pub update_debounce_ms: u64,
+    /// Most verbose level written to the log: `error`, `warn`, `info`, `debug` or `trace`
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+    /// Run SQLite's integrity check when the app starts, repairing the database if it fails
+    #[serde(default = "default_check_integrity_on_startup")]
+    pub check_integrity_on_startup: bool,
+    /// Set when the quick add shortcut couldn't be registered
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub shortcut_error: Option<String>,
+}
+
+fn default_reminder_minutes() -> u32 {
+    30
+}
+
+fn default_update_debounce_ms() -> u64 {
+    1000
+}
+
+fn default_check_integrity_on_startup() -> bool {
+    true
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+fn default_quick_add_shortcut() -> Option<String> {
+    Some("CommandOrControl+Shift+Space".to_string())
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            reminder_minutes: default_reminder_minutes(),
+            quick_add_shortcut: default_quick_add_shortcut(),
+            webhook_url: None,
+            update_debounce_ms: default_update_debounce_ms(),
+            log_level: default_log_level(),
+            check_integrity_on_startup: default_check_integrity_on_startup(),
+            shortcut_error: None,
+        }
+    }
+}
+
+/// Why sync settings couldn't be loaded
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "kind", content = "message", rename_all = "camelCase")]
+pub enum SettingsError {
+    /// The file is there but couldn't be read at all
+    Io(String),
+    /// Truncated, tampered with or encrypted under another key;
+    /// `reset_to_defaults` recovers from this
+    Unreadable(String),
+}
+
+impl std::fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::Io(e) => write!(f, "{}", e),
+            SettingsError::Unreadable(e) => write!(f, "Sync settings are unreadable: {}", e),
+        }
+    }
+}
+
+impl From<SettingsError> for String {
+    fn from(e: SettingsError) -> Self {
+        e.to_string()
+    }
+}
+
+impl From<String> for SettingsError {
+    fn from(e: String) -> Self {
+        SettingsError::Io(e)
+    }
 }
 
 pub struct EncryptedStorage {
+    key_path: PathBuf,
     storage_path: PathBuf,
-    key: [u8; KEY_SIZE],
+    app_settings_path: PathBuf,
+    database_key_path: PathBuf,
+    /// Write-locked for the whole of `rotate_key`, so nothing is saved under
+    /// the old key after its files were re-encrypted
+    key: RwLock<[u8; KEY_SIZE]>,
+    database_key: String,
 }
 
 impl EncryptedStorage {
@@ -58,104 +392,642 @@ impl EncryptedStorage {
         
         let key_path = app_data_dir.join("encryption.key");
         let storage_path = app_data_dir.join("settings.enc");
+        let app_settings_path = app_data_dir.join("app_settings.enc");
+        let database_key_path = app_data_dir.join("database.key.enc");
+        
+        finish_rotation(&key_path, &[&storage_path, &app_settings_path, &database_key_path])?;
         
         let key = if key_path.exists() {
-            // Load existing key
-            let key_data = fs::read(&key_path)
-                .map_err(|e| format!("Failed to read encryption key: {}", e))?;
-            
-            if key_data.len() != KEY_SIZE {
-                return Err("Invalid encryption key length".to_string());
-            }
-            
-            let mut key = [0u8; KEY_SIZE];
-            key.copy_from_slice(&key_data);
-            key
+            read_key_file(&key_path)?
         } else {
             // Generate new key
             let mut key = [0u8; KEY_SIZE];
             rand::thread_rng().fill(&mut key);
             
-            fs::write(&key_path, &key)
+            util::write_atomic(&key_path, &key)
                 .map_err(|e| format!("Failed to write encryption key: {}", e))?;
             
             key
         };
         
-        Ok(Self { storage_path, key })
+        // Once the key has been rotated the database key no longer follows from it
+        let database_key = if database_key_path.exists() {
+            read_encrypted(&key, &database_key_path)?
+        } else {
+            derive_database_key(&key)
+        };
+        
+        Ok(Self {
+            key_path,
+            storage_path,
+            app_settings_path,
+            database_key_path,
+            key: RwLock::new(key),
+            database_key,
+        })
+    }
+    
+    /// Hex-encoded SQLCipher key for the tasks database
+    pub fn database_key(&self) -> String {
+        self.database_key.clone()
     }
     
-    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
-        let cipher = Aes256Gcm::new_from_slice(&self.key)
-            .map_err(|e| format!("Failed to create cipher: {}", e))?;
+    /// Whether every settings file on disk holds ciphertext under the current key
+    pub fn is_settings_encrypted(&self) -> bool {
+        let Ok(key) = self.read_key() else {
+            return false;
+        };
+        [&self.storage_path, &self.app_settings_path, &self.database_key_path].into_iter()
+            .filter(|path| path.exists())
+            .all(|path| read_base64(path).and_then(|data| decrypt(&key, &data)).is_ok())
+    }
+    
+    /// Whether the key is unlocked with a passphrase. It's always read from
+    /// `encryption.key` for now, so anyone who can read the data folder has it.
+    pub fn is_passphrase_mode(&self) -> bool {
+        false
+    }
+    
+    /// Whether the active profile's sync password is kept in the OS keychain
+    /// and can be read back from it
+    pub fn is_password_in_keychain(&self) -> bool {
+        self.load_sync_settings()
+            .is_ok_and(|settings| settings.keychain_account.is_some() && settings.credential_error.is_none())
+    }
+    
+    /// Replace `encryption.key` with a fresh key and re-encrypt everything
+    /// under it. The new files are staged next to the old ones and flushed to
+    /// disk, then the new key is saved as `encryption.key.new` and everything is
+    /// renamed into place. A failure before that key is saved leaves the old key
+    /// and ciphertext in use; a crash after it is finished by `finish_rotation`
+    /// at the next start.
+    ///
+    /// The tasks database keeps its key, which is stored under the new key
+    /// from here on instead of being derived from it.
+    pub fn rotate_key(&self) -> Result<(), String> {
+        let mut key = self.key.write()
+            .map_err(|_| "Encryption key lock poisoned".to_string())?;
         
-        let mut nonce_bytes = [0u8; NONCE_SIZE];
-        rand::thread_rng().fill(&mut nonce_bytes);
-        #[allow(deprecated)]
-        let nonce = Nonce::from_slice(&nonce_bytes);
+        let mut new_key = [0u8; KEY_SIZE];
+        rand::thread_rng().fill(&mut new_key);
         
-        let ciphertext = cipher
-            .encrypt(nonce, plaintext)
-            .map_err(|e| format!("Encryption failed: {}", e))?;
+        let new_key_path = sibling_path(&self.key_path, ".new");
+        let mut staged = Vec::new();
+        let result = (|| {
+            for path in [&self.storage_path, &self.app_settings_path] {
+                if !path.exists() {
+                    continue;
+                }
+                let plaintext = decrypt(&key, &read_base64(path)?)?;
+                let tmp = sibling_path(path, ".new");
+                write_base64(&tmp, &encrypt(&new_key, &plaintext)?)?;
+                staged.push((tmp, path.clone()));
+            }
+            
+            let tmp = sibling_path(&self.database_key_path, ".new");
+            write_encrypted(&new_key, &tmp, &self.database_key)?;
+            staged.push((tmp, self.database_key_path.clone()));
+            
+            // Only once everything is staged, so its presence means the rotation can be finished
+            util::write_atomic(&new_key_path, &new_key)
+                .map_err(|e| format!("Failed to write encryption key: {}", e))?;
+            
+            replace_files(&staged)
+        })();
         
-        // Prepend nonce to ciphertext
-        let mut result = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
-        result.extend_from_slice(&nonce_bytes);
-        result.extend_from_slice(&ciphertext);
+        if let Err(e) = result {
+            for (tmp, _) in &staged {
+                let _ = fs::remove_file(tmp);
+            }
+            let _ = fs::remove_file(&new_key_path);
+            return Err(e);
+        }
+        
+        // Everything is under the new key now; if this fails the next start retries it
+        if let Err(e) = fs::rename(&new_key_path, &self.key_path) {
+            log::warn!("Failed to replace encryption key, finishing at the next start: {}", e);
+        }
         
-        Ok(result)
+        *key = new_key;
+        log::info!("Rotated encryption key");
+        Ok(())
+    }
+    
+    fn read_key(&self) -> Result<RwLockReadGuard<'_, [u8; KEY_SIZE]>, String> {
+        self.key.read().map_err(|_| "Encryption key lock poisoned".to_string())
+    }
+    
+    fn write_encrypted<T: Serialize>(&self, path: &Path, value: &T) -> Result<(), String> {
+        write_encrypted(&*self.read_key()?, path, value)
+    }
+    
+    fn read_encrypted<T: for<'de> Deserialize<'de>>(&self, path: &Path) -> Result<T, String> {
+        read_encrypted(&*self.read_key()?, path)
+    }
+    
+    /// Save sync settings to the active profile. With the `keychain` feature the
+    /// password goes to the OS secret store and only the keychain account is
+    /// written to disk.
+    pub fn save_sync_settings(&self, settings: &SyncSettings) -> Result<(), String> {
+        let active = self.read_profiles()?.active;
+        self.save_profile(&active, settings)
+    }
+    
+    /// Load the active profile's sync settings, or the defaults when none were saved yet
+    pub fn load_sync_settings(&self) -> Result<SyncSettings, SettingsError> {
+        let profiles = self.read_profiles()?;
+        let mut settings = profiles.get(&profiles.active)
+            .map(|profile| profile.settings.clone())
+            .unwrap_or_else(SyncSettings::default_settings);
+        resolve_password(&mut settings);
+        
+        Ok(settings)
     }
     
-    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, String> {
-        if data.len() < NONCE_SIZE {
-            return Err("Data too short".to_string());
+    /// Every sync profile, passwords included, and which one is active
+    pub fn list_profiles(&self) -> Result<SyncProfiles, SettingsError> {
+        let mut profiles = self.read_profiles()?;
+        for profile in &mut profiles.profiles {
+            resolve_password(&mut profile.settings);
         }
         
-        let cipher = Aes256Gcm::new_from_slice(&self.key)
-            .map_err(|e| format!("Failed to create cipher: {}", e))?;
+        Ok(profiles)
+    }
+    
+    /// Create the profile called `name`, or replace its settings
+    pub fn save_profile(&self, name: &str, settings: &SyncSettings) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("Profile name is required".to_string());
+        }
         
-        #[allow(deprecated)]
-        let nonce = Nonce::from_slice(&data[..NONCE_SIZE]);
-        let ciphertext = &data[NONCE_SIZE..];
+        let mut profiles = self.read_profiles()?;
+        let settings = store_password(settings)?;
+        match profiles.profiles.iter_mut().find(|profile| profile.name == name) {
+            Some(profile) => profile.settings = settings,
+            None => profiles.profiles.push(SyncProfile { name: name.to_string(), settings }),
+        }
         
-        cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))
+        self.write_encrypted(&self.storage_path, &profiles)
     }
     
-    pub fn save_sync_settings(&self, settings: &SyncSettings) -> Result<(), String> {
-        let json = serde_json::to_string(settings)
-            .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    /// Remove a profile; the active one has to be switched away from first
+    pub fn delete_profile(&self, name: &str) -> Result<(), String> {
+        let mut profiles = self.read_profiles()?;
+        if profiles.active == name {
+            return Err("Switch to another sync profile before deleting this one".to_string());
+        }
         
-        let encrypted = self.encrypt(json.as_bytes())?;
-        let encoded = BASE64.encode(&encrypted);
+        let index = profiles.profiles.iter()
+            .position(|profile| profile.name == name)
+            .ok_or_else(|| format!("No sync profile named '{}'", name))?;
+        let removed = profiles.profiles.remove(index);
+        self.write_encrypted(&self.storage_path, &profiles)?;
         
-        fs::write(&self.storage_path, encoded)
-            .map_err(|e| format!("Failed to write settings: {}", e))
+        // Profiles for the same user and server share a keychain entry
+        if let Some(account) = removed.settings.keychain_account {
+            if !profiles.profiles.iter().any(|profile| profile.settings.keychain_account.as_ref() == Some(&account)) {
+                delete_keychain_password(&account);
+            }
+        }
+        Ok(())
     }
     
-    pub fn load_sync_settings(&self) -> Result<SyncSettings, String> {
+    /// Point sync at another saved profile
+    pub fn set_active_profile(&self, name: &str) -> Result<(), String> {
+        let mut profiles = self.read_profiles()?;
+        if profiles.get(name).is_none() {
+            return Err(format!("No sync profile named '{}'", name));
+        }
+        
+        profiles.active = name.to_string();
+        self.write_encrypted(&self.storage_path, &profiles)
+    }
+    
+    /// Read `settings.enc`. Settings saved before profiles existed are moved into
+    /// the default profile the first time they're read.
+    fn read_profiles(&self) -> Result<SyncProfiles, SettingsError> {
         if !self.storage_path.exists() {
-            return Ok(SyncSettings::default_settings());
+            return Ok(SyncProfiles::single(SyncSettings::default_settings()));
         }
         
         let encoded = fs::read_to_string(&self.storage_path)
-            .map_err(|e| format!("Failed to read settings: {}", e))?;
-        
-        let encrypted = BASE64.decode(encoded.trim())
-            .map_err(|e| format!("Failed to decode settings: {}", e))?;
+            .map_err(|e| SettingsError::Io(format!("Failed to read settings: {}", e)))?;
+        let stored: StoredSyncSettings = decode_encrypted(&*self.read_key()?, &encoded)
+            .map_err(SettingsError::Unreadable)?;
+        match stored {
+            StoredSyncSettings::Profiles(profiles) => Ok(profiles),
+            StoredSyncSettings::Single(settings) => {
+                let profiles = SyncProfiles::single(*settings);
+                self.write_encrypted(&self.storage_path, &profiles)?;
+                log::info!("Moved sync settings into the '{}' profile", DEFAULT_PROFILE);
+                Ok(profiles)
+            }
+        }
+    }
+    
+    /// Move an unreadable `settings.enc` aside to `settings.enc.corrupt` and
+    /// start over with default sync settings
+    pub fn reset_to_defaults(&self) -> Result<SyncSettings, String> {
+        if self.storage_path.exists() {
+            fs::rename(&self.storage_path, sibling_path(&self.storage_path, ".corrupt"))
+                .map_err(|e| format!("Failed to back up settings: {}", e))?;
+            log::warn!("Moved unreadable settings to settings.enc.corrupt");
+        }
         
-        let decrypted = self.decrypt(&encrypted)?;
+        let settings = SyncSettings::default_settings();
+        self.write_encrypted(&self.storage_path, &SyncProfiles::single(settings.clone()))?;
+        Ok(settings)
+    }
+    
+    pub fn save_app_settings(&self, settings: &AppSettings) -> Result<(), String> {
+        self.write_encrypted(&self.app_settings_path, settings)
+    }
+    
+    pub fn load_app_settings(&self) -> Result<AppSettings, String> {
+        if !self.app_settings_path.exists() {
+            return Ok(AppSettings::default());
+        }
         
-        serde_json::from_slice(&decrypted)
-            .map_err(|e| format!("Failed to parse settings: {}", e))
+        self.read_encrypted(&self.app_settings_path)
     }
     
+    /// Remove every sync profile
     pub fn clear_sync_settings(&self) -> Result<(), String> {
+        if let Ok(profiles) = self.read_profiles() {
+            for profile in profiles.profiles {
+                if let Some(account) = profile.settings.keychain_account {
+                    delete_keychain_password(&account);
+                }
+            }
+        }
+        
         if self.storage_path.exists() {
             fs::remove_file(&self.storage_path)
                 .map_err(|e| format!("Failed to remove settings: {}", e))?;
         }
         Ok(())
     }
+    
+    /// Delete the keys, settings and keychain entries. The key files are
+    /// overwritten first; without them the database can't be decrypted either.
+    pub fn wipe(&self) -> Vec<FileError> {
+        if let Ok(profiles) = self.read_profiles() {
+            for account in profiles.profiles.into_iter().filter_map(|profile| profile.settings.keychain_account) {
+                delete_keychain_password(&account);
+            }
+        }
+        
+        let mut failed = util::shred_files(&[self.key_path.clone(), self.database_key_path.clone()]);
+        failed.extend(util::remove_files(&[
+            self.storage_path.clone(),
+            sibling_path(&self.storage_path, ".corrupt"),
+            self.app_settings_path.clone(),
+        ]));
+        failed
+    }
+}
+
+/// SQLCipher key derived from the settings key so the two are never the same bytes
+fn derive_database_key(key: &[u8; KEY_SIZE]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"taskist-database-key");
+    hasher.update(key);
+    
+    hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn encrypt(key: &[u8; KEY_SIZE], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+    
+    let mut nonce_bytes = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill(&mut nonce_bytes);
+    #[allow(deprecated)]
+    let nonce = Nonce::from_slice(&nonce_bytes);
+    
+    let ciphertext = cipher
+        .encrypt(nonce, plaintext)
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+    
+    // Prepend nonce to ciphertext
+    let mut result = Vec::with_capacity(NONCE_SIZE + ciphertext.len());
+    result.extend_from_slice(&nonce_bytes);
+    result.extend_from_slice(&ciphertext);
+    
+    Ok(result)
+}
+
+fn decrypt(key: &[u8; KEY_SIZE], data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < NONCE_SIZE {
+        return Err("Data too short".to_string());
+    }
+    
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|e| format!("Failed to create cipher: {}", e))?;
+    
+    #[allow(deprecated)]
+    let nonce = Nonce::from_slice(&data[..NONCE_SIZE]);
+    let ciphertext = &data[NONCE_SIZE..];
+    
+    cipher
+        .decrypt(nonce, ciphertext)
+        .map_err(|e| format!("Decryption failed: {}", e))
+}
+
+fn write_base64(path: &Path, data: &[u8]) -> Result<(), String> {
+    util::write_synced(path, BASE64.encode(data).as_bytes())
+        .map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+fn read_base64(path: &Path) -> Result<Vec<u8>, String> {
+    let encoded = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
+    
+    BASE64.decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode settings: {}", e))
+}
+
+/// Decode, decrypt and parse the contents of an encrypted settings file
+fn decode_encrypted<T: for<'de> Deserialize<'de>>(key: &[u8; KEY_SIZE], encoded: &str) -> Result<T, String> {
+    let encrypted = BASE64.decode(encoded.trim())
+        .map_err(|e| format!("Failed to decode settings: {}", e))?;
+    let decrypted = decrypt(key, &encrypted)?;
+    
+    serde_json::from_slice(&decrypted)
+        .map_err(|e| format!("Failed to parse settings: {}", e))
+}
+
+fn write_encrypted<T: Serialize>(key: &[u8; KEY_SIZE], path: &Path, value: &T) -> Result<(), String> {
+    let json = serde_json::to_string(value)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    
+    let encoded = BASE64.encode(encrypt(key, json.as_bytes())?);
+    // Make sure what hit the disk decrypts before it replaces a good file
+    util::write_atomic_checked(path, encoded.as_bytes(), |tmp| {
+        decrypt(key, &read_base64(tmp)?).map(|_| ())
+    })
+    .map_err(|e| format!("Failed to write settings: {}", e))
+}
+
+fn read_encrypted<T: for<'de> Deserialize<'de>>(key: &[u8; KEY_SIZE], path: &Path) -> Result<T, String> {
+    let encoded = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read settings: {}", e))?;
+    
+    decode_encrypted(key, &encoded)
+}
+
+fn read_key_file(path: &Path) -> Result<[u8; KEY_SIZE], String> {
+    let key_data = fs::read(path)
+        .map_err(|e| format!("Failed to read encryption key: {}", e))?;
+    
+    if key_data.len() != KEY_SIZE {
+        return Err("Invalid encryption key length".to_string());
+    }
+    
+    let mut key = [0u8; KEY_SIZE];
+    key.copy_from_slice(&key_data);
+    Ok(key)
+}
+
+/// Complete a `rotate_key` that was cut short. Once `encryption.key.new` exists
+/// every file was staged under it, so staged files still waiting are renamed into
+/// place and the new key replaces the old. A file already renamed may be under
+/// either key, so both are tried and it ends up under the new one. Without the
+/// new key the rotation never committed and whatever was staged is dropped.
+fn finish_rotation(key_path: &Path, targets: &[&Path]) -> Result<(), String> {
+    let new_key_path = sibling_path(key_path, ".new");
+    let Some(new_key) = read_key_file(&new_key_path).ok() else {
+        let mut leftovers: Vec<PathBuf> = targets.iter().map(|target| sibling_path(target, ".new")).collect();
+        leftovers.push(new_key_path);
+        util::remove_files(&leftovers);
+        return Ok(());
+    };
+    let old_key = read_key_file(key_path).ok();
+    
+    log::warn!("Finishing an interrupted encryption key rotation");
+    for target in targets {
+        let staged = sibling_path(target, ".new");
+        if staged.exists() {
+            fs::rename(&staged, target)
+                .map_err(|e| format!("Failed to replace {}: {}", target.display(), e))?;
+        } else if target.exists() {
+            let data = read_base64(target)?;
+            if decrypt(&new_key, &data).is_err() {
+                let plaintext = old_key.as_ref()
+                    .ok_or_else(|| format!("{} can't be decrypted", target.display()))
+                    .and_then(|old_key| decrypt(old_key, &data))?;
+                util::write_atomic(target, BASE64.encode(encrypt(&new_key, &plaintext)?).as_bytes())?;
+            }
+        }
+        let _ = fs::remove_file(sibling_path(target, ".old"));
+    }
+    
+    fs::rename(&new_key_path, key_path)
+        .map_err(|e| format!("Failed to replace encryption key: {}", e))
+}
+
+/// Rename each staged file over its target. The originals are moved aside
+/// first and put back if any rename fails.
+fn replace_files(staged: &[(PathBuf, PathBuf)]) -> Result<(), String> {
+    let mut done: Vec<(&Path, Option<PathBuf>)> = Vec::new();
+    
+    let result = staged.iter().try_for_each(|(tmp, target)| {
+        let backup = if target.exists() {
+            let backup = sibling_path(target, ".old");
+            fs::rename(target, &backup)
+                .map_err(|e| format!("Failed to back up {}: {}", target.display(), e))?;
+            Some(backup)
+        } else {
+            None
+        };
+        
+        let renamed = fs::rename(tmp, target)
+            .map_err(|e| format!("Failed to replace {}: {}", target.display(), e));
+        done.push((target, backup));
+        renamed
+    });
+    
+    if result.is_err() {
+        for (target, backup) in done.iter().rev() {
+            match backup {
+                Some(backup) => { let _ = fs::rename(backup, target); }
+                None => { let _ = fs::remove_file(target); }
+            }
+        }
+        return result;
+    }
+    
+    for (_, backup) in done {
+        if let Some(backup) = backup {
+            let _ = fs::remove_file(backup);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "keychain")]
+fn keychain_entry(account: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, account)
+        .map_err(|e| format!("Failed to open keychain entry: {}", e))
+}
+
+/// Copy of `settings` as written to disk. With the `keychain` feature the
+/// password goes to the OS secret store and only the keychain account is kept.
+fn store_password(settings: &SyncSettings) -> Result<SyncSettings, String> {
+    let mut settings = settings.clone();
+    settings.credential_error = None;
+    settings.has_password = false;
+    settings.has_auth_token = false;
+    
+    #[cfg(feature = "keychain")]
+    {
+        let account = format!("{}@{}", settings.sync_username, settings.sync_url);
+        keychain_entry(&account)?
+            .set_password(&settings.sync_password)
+            .map_err(|e| format!("Failed to store password in keychain: {}", e))?;
+        
+        settings.sync_password = String::new();
+        settings.keychain_account = Some(account);
+    }
+    
+    Ok(settings)
+}
+
+/// Fill in a password kept in the keychain, recording why when it can't be read
+fn resolve_password(settings: &mut SyncSettings) {
+    if let Some(account) = settings.keychain_account.clone() {
+        match read_keychain_password(&account) {
+            Ok(password) => settings.sync_password = password,
+            Err(e) => {
+                log::error!("{}", e);
+                settings.sync_password = String::new();
+                settings.credential_error = Some(e);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "keychain")]
+fn read_keychain_password(account: &str) -> Result<String, String> {
+    match keychain_entry(account)?.get_password() {
+        Ok(password) => Ok(password),
+        Err(keyring::Error::NoEntry) => Err(format!(
+            "Sync password for {} is missing from the keychain, please enter it again",
+            account
+        )),
+        Err(e) => Err(format!("Failed to read password from keychain: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+fn read_keychain_password(account: &str) -> Result<String, String> {
+    Err(format!(
+        "Sync password for {} is stored in the keychain, but this build has no keychain support",
+        account
+    ))
+}
+
+#[cfg(feature = "keychain")]
+fn delete_keychain_password(account: &str) {
+    if let Ok(entry) = keychain_entry(account) {
+        let _ = entry.delete_password();
+    }
+}
+
+#[cfg(not(feature = "keychain"))]
+fn delete_keychain_password(_account: &str) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_dir;
+    
+    fn custom_settings() -> AppSettings {
+        AppSettings { reminder_minutes: 42, ..AppSettings::default() }
+    }
+    
+    #[test]
+    fn rotated_files_only_decrypt_under_the_new_key() {
+        let dir = temp_dir();
+        let storage = EncryptedStorage::new(dir.clone()).unwrap();
+        storage.save_app_settings(&custom_settings()).unwrap();
+        let database_key = storage.database_key();
+        let old_key = read_key_file(&dir.join("encryption.key")).unwrap();
+        
+        storage.rotate_key().unwrap();
+        
+        let new_key = read_key_file(&dir.join("encryption.key")).unwrap();
+        assert_ne!(old_key, new_key);
+        let settings_path = dir.join("app_settings.enc");
+        assert!(read_encrypted::<AppSettings>(&old_key, &settings_path).is_err());
+        assert_eq!(read_encrypted::<AppSettings>(&new_key, &settings_path).unwrap().reminder_minutes, 42);
+        assert!(storage.is_settings_encrypted());
+        
+        // The database keeps its key across the rotation and a restart
+        let reopened = EncryptedStorage::new(dir.clone()).unwrap();
+        assert_eq!(reopened.database_key(), database_key);
+        assert_eq!(reopened.load_app_settings().unwrap().reminder_minutes, 42);
+        
+        fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[test]
+    fn a_rotation_cut_short_after_saving_the_new_key_is_finished_on_start() {
+        let dir = temp_dir();
+        let storage = EncryptedStorage::new(dir.clone()).unwrap();
+        storage.save_app_settings(&custom_settings()).unwrap();
+        let database_key = storage.database_key();
+        drop(storage);
+        
+        // Everything staged and the new key saved, but nothing renamed yet
+        let new_key = [7u8; KEY_SIZE];
+        let settings_path = dir.join("app_settings.enc");
+        let old_key = read_key_file(&dir.join("encryption.key")).unwrap();
+        let plaintext = decrypt(&old_key, &read_base64(&settings_path).unwrap()).unwrap();
+        write_base64(&sibling_path(&settings_path, ".new"), &encrypt(&new_key, &plaintext).unwrap()).unwrap();
+        write_encrypted(&new_key, &dir.join("database.key.enc.new"), &database_key).unwrap();
+        util::write_atomic(&dir.join("encryption.key.new"), &new_key).unwrap();
+        
+        let storage = EncryptedStorage::new(dir.clone()).unwrap();
+        
+        assert_eq!(read_key_file(&dir.join("encryption.key")).unwrap(), new_key);
+        assert!(!dir.join("encryption.key.new").exists());
+        assert!(!sibling_path(&settings_path, ".new").exists());
+        assert_eq!(storage.database_key(), database_key);
+        assert_eq!(storage.load_app_settings().unwrap().reminder_minutes, 42);
+        
+        fs::remove_dir_all(dir).unwrap();
+    }
+    
+    fn settings_with_db_name(name: &str) -> SyncSettings {
+        SyncSettings { sync_db_name: name.to_string(), ..SyncSettings::default_settings() }
+    }
+    
+    #[test]
+    fn database_names_couchdb_rejects_fail_validation() {
+        for name in ["", "Tasks", "1tasks", "_tasks", "my tasks", "tasks!"] {
+            let errors = settings_with_db_name(name).validate().unwrap_err();
+            assert_eq!(errors.len(), 1, "{:?}", name);
+            assert!(errors[0].starts_with(&format!("Database name '{}'", name)));
+        }
+    }
+    
+    #[test]
+    fn valid_settings_pass_validation() {
+        assert_eq!(settings_with_db_name("tasks_db").validate(), Ok(()));
+        assert_eq!(settings_with_db_name("team/tasks-2($+)").validate(), Ok(()));
+    }
+    
+    #[test]
+    fn validation_reports_every_problem_at_once() {
+        let settings = SyncSettings {
+            sync_mode: "couchdb".to_string(),
+            sync_url: String::new(),
+            sync_filter: Some("no-slash".to_string()),
+            pull_batch_size: 0,
+            ..settings_with_db_name("Tasks")
+        };
+        assert_eq!(settings.validate().unwrap_err().len(), 4);
+    }
 }
@@ -0,0 +1,57 @@
+use crate::logging;
+
+/// Tell the user why the app can't start. Runs before any window exists, so this
+/// uses the platform's own message box, falling back to stderr where there is none.
+pub fn show_startup_error(message: &str) {
+    // The logger writes to stderr too once it's running
+    match logging::log_path() {
+        Some(_) => log::error!("{}", message),
+        None => eprintln!("[startup] {}", message),
+    }
+    platform::show(&format!("Taskist couldn't start.\n\n{}", message));
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::core::HSTRING;
+    use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR, MB_OK};
+    
+    pub fn show(message: &str) {
+        unsafe {
+            MessageBoxW(None, &HSTRING::from(message), &HSTRING::from("Taskist"), MB_OK | MB_ICONERROR);
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::process::Command;
+    
+    pub fn show(message: &str) {
+        // Passed as an argument rather than spliced into the script, so quotes in it are harmless
+        let _ = Command::new("osascript")
+            .args(["-e", "on run argv", "-e", "display alert \"Taskist\" message (item 1 of argv) as critical", "-e", "end run"])
+            .arg(message)
+            .status();
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+mod platform {
+    use std::process::Command;
+    
+    pub fn show(message: &str) {
+        // Whichever dialog tool the desktop has; stderr already has the message otherwise
+        let shown = Command::new("zenity")
+            .args(["--error", "--title=Taskist", "--no-markup", "--text"])
+            .arg(message)
+            .status()
+            .is_ok();
+        if !shown {
+            let _ = Command::new("kdialog")
+                .args(["--title", "Taskist", "--error"])
+                .arg(message)
+                .status();
+        }
+    }
+}
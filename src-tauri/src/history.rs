@@ -0,0 +1,53 @@
+use crate::database::Task;
+
+/// How many actions can be undone before the oldest is forgotten
+const MAX_DEPTH: usize = 100;
+
+/// One user action, as every task it touched before and after
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub changes: Vec<(Task, Task)>,
+}
+
+/// In-memory undo/redo stacks for task edits. Creating a task is recorded with a
+/// deleted copy as its "before" state, so undoing it is just another soft delete.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Record a new action. Anything that was undone can no longer be redone.
+    pub fn record(&mut self, entry: Entry) {
+        if entry.changes.is_empty() {
+            return;
+        }
+        
+        self.redo.clear();
+        self.push_undo(entry);
+    }
+    
+    pub fn pop_undo(&mut self) -> Option<Entry> {
+        self.undo.pop()
+    }
+    
+    pub fn pop_redo(&mut self) -> Option<Entry> {
+        self.redo.pop()
+    }
+    
+    pub fn push_undo(&mut self, entry: Entry) {
+        self.undo.push(entry);
+        if self.undo.len() > MAX_DEPTH {
+            self.undo.remove(0);
+        }
+    }
+    
+    pub fn push_redo(&mut self, entry: Entry) {
+        self.redo.push(entry);
+    }
+}
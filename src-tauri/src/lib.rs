@@ -1,127 +1,1050 @@
 mod database;
 mod encryption;
+mod fatal;
+mod history;
+mod logging;
+mod metered;
+mod quick_add;
+mod rank;
+mod recurrence;
+mod reminders;
 mod sync;
+mod tray;
+mod util;
+mod webhooks;
 
-use database::{Database, Task};
-use encryption::{EncryptedStorage, SyncSettings};
-use sync::{SyncManager, SyncState};
+use database::{CompactStats, ConflictWinner, Database, DuplicatePolicy, LegacyTask, NewTask, Project, RecoveryReport, TagCount, Task, TaskConflict, TaskEvent, TaskFilter, TaskPatch, TaskStats, Template, TemplateTask, TimeEntry, UpdateError};
+use encryption::{AppSettings, EncryptedStorage, SettingsError, SyncProfiles, SyncSettings};
+use reminders::ReminderScheduler;
+use sync::{CredentialCheck, SyncErrorEntry, SyncManager, SyncState};
+use util::FileError;
 
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tauri::{AppHandle, Emitter, Manager, State, WebviewUrl, WebviewWindowBuilder};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, ExitRequestApi, Listener, Manager, RunEvent, State, WebviewUrl, WebviewWindowBuilder};
 use tokio::sync::RwLock;
+use webhooks::{WebhookEvent, WebhookNotifier};
 
 // App state to hold our database and sync manager
 pub struct AppState {
     db: Arc<Database>,
     storage: Arc<EncryptedStorage>,
     sync_manager: Arc<SyncManager>,
+    reminders: Arc<ReminderScheduler>,
+    webhooks: Arc<WebhookNotifier>,
 }
 
+/// What `get_security_status` found protecting the data at rest
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SecurityStatus {
+    /// Settings files are AES-256-GCM ciphertext under `encryption.key`
+    settings_encrypted: bool,
+    /// The tasks database is a SQLCipher file
+    database_encrypted: bool,
+    /// The key needs a passphrase; otherwise it sits unprotected in `encryption.key`
+    passphrase_mode: bool,
+    sync_password_in_keychain: bool,
+    /// This build can keep the sync password in the OS keychain
+    keychain_available: bool,
+}
+
+/// Tasks loaded during setup, sent once as `tasks-initial` when the frontend is
+/// listening. Dropped if tasks change first, since the snapshot would be stale.
+struct StartupSnapshot(std::sync::Mutex<Option<Vec<Task>>>);
+
+impl StartupSnapshot {
+    fn take(&self) -> Option<Vec<Task>> {
+        self.0.lock().ok().and_then(|mut tasks| tasks.take())
+    }
+}
+
+/// Sent as `integrity-warning` when the startup check found the database damaged
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct IntegrityWarning {
+    /// What was salvaged, `None` when recovery failed and the damaged file is still in use
+    recovery: Option<RecoveryReport>,
+    error: Option<String>,
+}
+
+/// The startup check's warning, held like `StartupSnapshot` until the frontend listens
+struct StartupIntegrityWarning(std::sync::Mutex<Option<IntegrityWarning>>);
+
 // ============ Task Commands ============
 
 #[tauri::command]
-async fn get_all_tasks(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Task>, String> {
+async fn get_all_tasks(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_all_tasks()
+}
+
+/// Called once the frontend's listeners are registered, so the startup snapshot
+/// can't be missed. Sends `integrity-warning` and `tasks-initial` at most once.
+#[tauri::command]
+async fn frontend_ready(
+    snapshot: State<'_, StartupSnapshot>,
+    integrity: State<'_, StartupIntegrityWarning>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if let Some(warning) = integrity.0.lock().ok().and_then(|mut warning| warning.take()) {
+        app_handle.emit("integrity-warning", warning)
+            .map_err(|e| format!("Failed to emit integrity warning: {}", e))?;
+    }
+    if let Some(tasks) = snapshot.take() {
+        app_handle.emit("tasks-initial", tasks)
+            .map_err(|e| format!("Failed to emit initial tasks: {}", e))?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_root_tasks(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_root_tasks()
+}
+
+#[tauri::command]
+async fn get_subtasks(
+    parent_id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_subtasks(&parent_id)
+}
+
+#[tauri::command]
+async fn get_all_tasks_sorted_by_priority(
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_all_tasks_sorted_by_priority()
+}
+
+#[tauri::command]
+async fn get_all_tasks_by_created(
+    ascending: bool,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_all_tasks_by_created(ascending)
+}
+
+#[tauri::command]
+async fn search_tasks(
+    query: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.search_tasks(&query)
+}
+
+#[tauri::command]
+async fn get_tasks_by_tag(
+    tag: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_tasks_by_tag(&tag)
+}
+
+/// Every tag in use with how many tasks carry it, for autocomplete
+#[tauri::command]
+async fn get_all_tags(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<TagCount>, String> {
+    let state = state.read().await;
+    state.db.get_all_tags()
+}
+
+/// Rename a tag on every task carrying it, returning those tasks
+#[tauri::command]
+async fn rename_tag(
+    old: String,
+    new: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.rename_tag(&old, &new)?;
+    if !tasks.is_empty() {
+        let _ = app_handle.emit("tasks-changed", ());
+    }
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn get_tasks_due_between(
+    start: i64,
+    end: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_tasks_due_between(start, end)
+}
+
+#[tauri::command]
+async fn get_completed_between(
+    start: i64,
+    end: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_completed_between(start, end)
+}
+
+#[tauri::command]
+async fn duplicate_task(
+    id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.duplicate_task(&id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+/// Push a task's due date later by `add_millis`; presets are computed by the frontend
+#[tauri::command]
+async fn snooze_task(
+    id: String,
+    add_millis: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.snooze_task(&id, add_millis)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn get_tasks_page(
+    offset: i64,
+    limit: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_tasks_page(offset, limit)
+}
+
+#[tauri::command]
+async fn count_tasks(state: State<'_, Arc<RwLock<AppState>>>) -> Result<i64, String> {
+    let state = state.read().await;
+    state.db.count_tasks()
+}
+
+#[tauri::command]
+async fn query_tasks(
+    filter: TaskFilter,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.query_tasks(&filter)
+}
+
+#[tauri::command]
+async fn get_task_stats(state: State<'_, Arc<RwLock<AppState>>>) -> Result<TaskStats, String> {
+    let state = state.read().await;
+    state.db.get_stats()
+}
+
+/// Completions per local day for the heatmap; days without any are left out
+#[tauri::command]
+async fn get_completions_by_day(
+    start: i64,
+    end: i64,
+    tz_offset_minutes: i32,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<(String, i64)>, String> {
+    let state = state.read().await;
+    state.db.completions_by_day(start, end, tz_offset_minutes)
+}
+
+/// Tasks for the daily view; the day bounds come from the frontend's timezone
+#[tauri::command]
+async fn get_today_tasks(
+    day_start: i64,
+    day_end: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_today_view(day_start, day_end)
+}
+
+#[tauri::command]
+async fn get_overdue_tasks(
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_overdue_tasks(chrono::Utc::now().timestamp_millis())
+}
+
+#[tauri::command]
+async fn add_task(
+    new_task: NewTask,
+    duplicate_policy: Option<DuplicatePolicy>,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let (task, added) = match duplicate_policy {
+        Some(policy) => state.db.add_task_unique(new_task, policy)?,
+        None => (state.db.add_task(new_task)?, true),
+    };
+    if added {
+        let _ = app_handle.emit("tasks-changed", ());
+        state.webhooks.notify(WebhookEvent::Added, task.clone());
+    }
+    Ok(task)
+}
+
+/// Add a task from the quick add window with just a title
+#[tauri::command]
+async fn quick_add_task(
+    title: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err("Task title cannot be empty".to_string());
+    }
+    
+    let state = state.read().await;
+    let task = state.db.add_task(NewTask {
+        title: title.to_string(),
+        ..Default::default()
+    })?;
+    let _ = app_handle.emit("tasks-changed", ());
+    state.webhooks.notify(WebhookEvent::Added, task.clone());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn update_task(
+    task: Task,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, UpdateError> {
+    let state = state.read().await;
+    // The frontend sends the whole task, so an edit still queued for it is stale
+    state.db.discard_queued(&task.id);
+    let updated = state.db.update_task(&task)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(updated)
+}
+
+/// Change some fields of a task without sending the rest; see `TaskPatch`
+#[tauri::command]
+async fn update_task_fields(
+    id: String,
+    patch: TaskPatch,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let updated = state.db.update_task_fields(&id, patch)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(updated)
+}
+
+/// Save an edit without writing it yet; for per-keystroke changes. Later edits
+/// to the same task replace it, and it's written once they stop.
+#[tauri::command]
+async fn queue_task_update(
+    task: Task,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    state.db.queue_update(task)
+}
+
+#[tauri::command]
+async fn set_task_notes(
+    id: String,
+    notes: Option<String>,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.set_task_notes(&id, notes)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn set_task_color(
+    id: String,
+    color: Option<String>,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.set_task_color(&id, color)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+/// Time entries are local, so the timer commands don't emit `tasks-changed`
+#[tauri::command]
+async fn start_task_timer(id: String, state: State<'_, Arc<RwLock<AppState>>>) -> Result<TimeEntry, String> {
+    let state = state.read().await;
+    state.db.start_timer(&id)
+}
+
+#[tauri::command]
+async fn stop_task_timer(id: String, state: State<'_, Arc<RwLock<AppState>>>) -> Result<TimeEntry, String> {
+    let state = state.read().await;
+    state.db.stop_timer(&id)
+}
+
+#[tauri::command]
+async fn get_task_time_spent(id: String, state: State<'_, Arc<RwLock<AppState>>>) -> Result<i64, String> {
+    let state = state.read().await;
+    state.db.get_time_spent(&id)
+}
+
+#[tauri::command]
+async fn get_task_history(id: String, state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<TaskEvent>, String> {
+    let state = state.read().await;
+    state.db.get_task_history(&id)
+}
+
+#[tauri::command]
+async fn delete_task(
+    id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let deleted = state.db.delete_task(&id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    state.webhooks.notify(WebhookEvent::Deleted, deleted[0].clone());
+    Ok(deleted)
+}
+
+#[tauri::command]
+async fn toggle_task_completion(
+    id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.toggle_task_completion(&id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    let event = if task.completed { WebhookEvent::Completed } else { WebhookEvent::Reopened };
+    state.webhooks.notify(event, task.clone());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn bulk_complete_tasks(
+    ids: Vec<String>,
+    completed: bool,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.bulk_set_completed(&ids, completed)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn bulk_delete_tasks(
+    ids: Vec<String>,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.bulk_delete(&ids)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn get_archived_tasks(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_archived_tasks()
+}
+
+#[tauri::command]
+async fn archive_task(
+    id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.archive_task(&id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn unarchive_task(
+    id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.unarchive_task(&id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn archive_completed_older_than(
+    millis: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.archive_completed_older_than(millis)?;
+    if !tasks.is_empty() {
+        let _ = app_handle.emit("tasks-changed", ());
+    }
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn get_trash(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_deleted_tasks()
+}
+
+#[tauri::command]
+async fn restore_task(
+    id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.restore_task(&id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn purge_deleted_older_than(
+    millis: i64,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<usize, String> {
+    let state = state.read().await;
+    state.db.purge_deleted_older_than(millis)
+}
+
+#[tauri::command]
+async fn undo(
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.undo_last()?;
+    if !tasks.is_empty() {
+        let _ = app_handle.emit("tasks-changed", ());
+    }
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn redo(
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.redo_last()?;
+    if !tasks.is_empty() {
+        let _ = app_handle.emit("tasks-changed", ());
+    }
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn reorder_task(
+    task_id: String,
+    direction: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.reorder_task(&task_id, &direction)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn reorder_task_in_project(
+    task_id: String,
+    direction: String,
+    project_id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.reorder_task_in_project(&task_id, &direction, &project_id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn move_task_to_position(
+    task_id: String,
+    target_task_id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.move_task_to_position(&task_id, &target_task_id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn move_task_to_index(
+    task_id: String,
+    new_index: usize,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.move_task_to_index(&task_id, new_index)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn move_task_to_index_in_project(
+    task_id: String,
+    new_index: usize,
+    project_id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.move_task_to_index_in_project(&task_id, new_index, &project_id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn move_tasks_to_index(
+    task_ids: Vec<String>,
+    new_index: usize,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    let tasks = state.db.move_tasks_to_index(&task_ids, new_index)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(tasks)
+}
+
+#[tauri::command]
+async fn move_task_between(
+    task_id: String,
+    before_id: Option<String>,
+    after_id: Option<String>,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.move_task_between(&task_id, before_id.as_deref(), after_id.as_deref())?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
+#[tauri::command]
+async fn export_tasks_csv(state: State<'_, Arc<RwLock<AppState>>>) -> Result<String, String> {
+    let state = state.read().await;
+    state.db.export_csv()
+}
+
+#[tauri::command]
+async fn export_tasks_markdown(state: State<'_, Arc<RwLock<AppState>>>) -> Result<String, String> {
+    let state = state.read().await;
+    state.db.export_markdown()
+}
+
+#[tauri::command]
+async fn export_tasks_ics(state: State<'_, Arc<RwLock<AppState>>>) -> Result<String, String> {
+    let state = state.read().await;
+    state.db.export_ics()
+}
+
+#[tauri::command]
+async fn backup_tasks(state: State<'_, Arc<RwLock<AppState>>>) -> Result<String, String> {
+    let state = state.read().await;
+    state.db.export_all()
+}
+
+/// Reclaim space left by deleted rows. Sync cycles wait until it's done.
+#[tauri::command]
+async fn compact_database(state: State<'_, Arc<RwLock<AppState>>>) -> Result<CompactStats, String> {
+    let state = state.read().await;
+    let _cycles = state.sync_manager.hold_cycles().await;
+    
+    let db = state.db.clone();
+    tauri::async_runtime::spawn_blocking(move || db.compact())
+        .await
+        .map_err(|e| format!("Compaction failed: {}", e))?
+}
+
+/// Run the integrity check on demand. Repairs happen at the next start.
+#[tauri::command]
+async fn check_database_integrity(state: State<'_, Arc<RwLock<AppState>>>) -> Result<bool, String> {
+    let db = state.read().await.db.clone();
+    tauri::async_runtime::spawn_blocking(move || db.integrity_check())
+        .await
+        .map_err(|e| format!("Integrity check failed: {}", e))?
+}
+
+#[tauri::command]
+async fn restore_tasks(
+    json: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<usize, String> {
+    let state = state.read().await;
+    let restored = state.db.import_all(&json)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(restored)
+}
+
+#[tauri::command]
+async fn import_todoist(
+    json: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<usize, String> {
+    let state = state.read().await;
+    let imported = state.db.import_todoist(&json)?;
+    if imported > 0 {
+        let _ = app_handle.emit("tasks-changed", ());
+    }
+    Ok(imported)
+}
+
+// ============ Project Commands ============
+
+#[tauri::command]
+async fn get_all_projects(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Project>, String> {
+    let state = state.read().await;
+    state.db.get_all_projects()
+}
+
+#[tauri::command]
+async fn add_project(
+    name: String,
+    color: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Project, String> {
+    let state = state.read().await;
+    let project = state.db.add_project(name, color)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(project)
+}
+
+#[tauri::command]
+async fn rename_project(
+    project_id: String,
+    name: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Project, String> {
+    let state = state.read().await;
+    let project = state.db.rename_project(&project_id, name)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(project)
+}
+
+#[tauri::command]
+async fn delete_project(
+    project_id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let state = state.read().await;
+    state.db.delete_project(&project_id)?;
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_tasks_for_project(
+    project_id: String,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Vec<Task>, String> {
+    let state = state.read().await;
+    state.db.get_tasks_for_project(&project_id)
+}
+
+// ============ Template Commands ============
+
+#[tauri::command]
+async fn save_template(
+    name: String,
+    tasks: Vec<TemplateTask>,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<Template, String> {
     let state = state.read().await;
-    state.db.get_all_tasks()
+    state.db.save_template(&name, tasks)
 }
 
 #[tauri::command]
-async fn add_task(
-    title: String,
-    description: Option<String>,
-    due_date: Option<String>,
-    state: State<'_, Arc<RwLock<AppState>>>,
-    app_handle: AppHandle,
-) -> Result<Task, String> {
+async fn list_templates(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<Template>, String> {
     let state = state.read().await;
-    let task = state.db.add_task(title, description, due_date)?;
-    let _ = app_handle.emit("tasks-changed", ());
-    Ok(task)
+    state.db.list_templates()
 }
 
 #[tauri::command]
-async fn update_task(
-    task: Task,
+async fn delete_template(name: String, state: State<'_, Arc<RwLock<AppState>>>) -> Result<(), String> {
+    let state = state.read().await;
+    state.db.delete_template(&name)
+}
+
+/// Add a template's tasks, due dates counted from now
+#[tauri::command]
+async fn instantiate_template(
+    name: String,
     state: State<'_, Arc<RwLock<AppState>>>,
     app_handle: AppHandle,
-) -> Result<Task, String> {
+) -> Result<Vec<Task>, String> {
     let state = state.read().await;
-    let updated = state.db.update_task(&task)?;
+    let tasks = state.db.instantiate_template(&name)?;
     let _ = app_handle.emit("tasks-changed", ());
-    Ok(updated)
+    Ok(tasks)
 }
 
+// ============ Settings Commands ============
+
 #[tauri::command]
-async fn delete_task(
-    id: String,
+async fn get_sync_settings(state: State<'_, Arc<RwLock<AppState>>>) -> Result<SyncSettings, SettingsError> {
+    let state = state.read().await;
+    state.storage.load_sync_settings().map(|settings| settings.redacted())
+}
+
+/// Save sync settings; a blank password or token keeps the stored one
+#[tauri::command]
+async fn save_sync_settings(
+    mut settings: SyncSettings,
     state: State<'_, Arc<RwLock<AppState>>>,
     app_handle: AppHandle,
-) -> Result<(), String> {
+) -> Result<(), Vec<String>> {
     let state = state.read().await;
-    state.db.delete_task(&id)?;
-    let _ = app_handle.emit("tasks-changed", ());
+    let stored = state.storage.load_sync_settings().ok();
+    if let Some(stored) = &stored {
+        settings.keep_secrets_from(stored);
+    }
+    settings.validate()?;
+    
+    let filter_changed = stored.is_some_and(|stored| stored.sync_filter() != settings.sync_filter());
+    if !filter_changed {
+        state.storage.save_sync_settings(&settings).map_err(|e| vec![e])?;
+        state.sync_manager.set_interval(&settings).await;
+        return Ok(());
+    }
+    
+    // The running loop keeps the filter it started with, so restart it, and keep
+    // cycles out while the checkpoints are reset so none saves the old `last_seq`
+    let was_running = state.sync_manager.is_running().await;
+    state.sync_manager.stop_sync(&app_handle).await;
+    {
+        let _cycles = state.sync_manager.hold_cycles().await;
+        state.storage.save_sync_settings(&settings).map_err(|e| vec![e])?;
+        // Pull from the start again so documents the old filter left out come in
+        state.db.reset_sync_checkpoints().map_err(|e| vec![e])?;
+    }
+    state.sync_manager.set_interval(&settings).await;
+    if was_running {
+        state.sync_manager.start_sync(settings, state.db.clone(), app_handle).await;
+    }
     Ok(())
 }
 
 #[tauri::command]
-async fn toggle_task_completion(
-    id: String,
-    state: State<'_, Arc<RwLock<AppState>>>,
-    app_handle: AppHandle,
-) -> Result<Task, String> {
+async fn clear_sync_settings(state: State<'_, Arc<RwLock<AppState>>>) -> Result<(), String> {
     let state = state.read().await;
-    let task = state.db.toggle_task_completion(&id)?;
-    let _ = app_handle.emit("tasks-changed", ());
-    Ok(task)
+    state.storage.clear_sync_settings()
 }
 
+/// Recover from unreadable sync settings by starting over with the defaults
 #[tauri::command]
-async fn reorder_task(
-    task_id: String,
-    direction: String,
+async fn reset_sync_settings(state: State<'_, Arc<RwLock<AppState>>>) -> Result<SyncSettings, String> {
+    let state = state.read().await;
+    state.storage.reset_to_defaults()
+}
+
+/// Saved sync profiles with their secrets blanked out, like `get_sync_settings`
+#[tauri::command]
+async fn list_sync_profiles(state: State<'_, Arc<RwLock<AppState>>>) -> Result<SyncProfiles, SettingsError> {
+    let state = state.read().await;
+    let mut profiles = state.storage.list_profiles()?;
+    for profile in &mut profiles.profiles {
+        profile.settings = profile.settings.redacted();
+    }
+    Ok(profiles)
+}
+
+/// Create or update a sync profile; a blank password or token keeps the stored one
+#[tauri::command]
+async fn save_sync_profile(
+    name: String,
+    mut settings: SyncSettings,
     state: State<'_, Arc<RwLock<AppState>>>,
-    app_handle: AppHandle,
-) -> Result<(), String> {
+) -> Result<(), Vec<String>> {
     let state = state.read().await;
-    state.db.reorder_task(&task_id, &direction)?;
-    let _ = app_handle.emit("tasks-changed", ());
+    let profiles = state.storage.list_profiles().map_err(|e| vec![e.to_string()])?;
+    if let Some(stored) = profiles.get(&name) {
+        settings.keep_secrets_from(&stored.settings);
+    }
+    settings.validate()?;
+    
+    state.storage.save_profile(&name, &settings).map_err(|e| vec![e])?;
+    if profiles.active == name {
+        state.sync_manager.set_interval(&settings).await;
+    }
     Ok(())
 }
 
 #[tauri::command]
-async fn move_task_to_position(
-    task_id: String,
-    target_task_id: String,
+async fn delete_sync_profile(name: String, state: State<'_, Arc<RwLock<AppState>>>) -> Result<(), String> {
+    let state = state.read().await;
+    state.storage.delete_profile(&name)
+}
+
+/// Switch sync to another profile. The new server is synced from scratch, since
+/// the old one's checkpoints mean nothing to it.
+#[tauri::command]
+async fn set_active_sync_profile(
+    name: String,
     state: State<'_, Arc<RwLock<AppState>>>,
     app_handle: AppHandle,
 ) -> Result<(), String> {
     let state = state.read().await;
-    state.db.move_task_to_position(&task_id, &target_task_id)?;
-    let _ = app_handle.emit("tasks-changed", ());
+    if state.storage.list_profiles()?.active == name {
+        return Ok(());
+    }
+    
+    state.sync_manager.stop_sync(&app_handle).await;
+    {
+        // Wait out a cycle still in flight so it can't save the old server's `last_seq`
+        let _cycles = state.sync_manager.hold_cycles().await;
+        state.storage.set_active_profile(&name)?;
+        state.db.reset_sync_checkpoints()?;
+    }
+    
+    let settings = state.storage.load_sync_settings()?;
+    state.sync_manager.set_interval(&settings).await;
+    if !settings.sync_url.is_empty() {
+        state.sync_manager.start_sync(settings, state.db.clone(), app_handle).await;
+    }
     Ok(())
 }
 
-// ============ Settings Commands ============
+#[tauri::command]
+async fn rotate_encryption_key(state: State<'_, Arc<RwLock<AppState>>>) -> Result<(), String> {
+    let state = state.read().await;
+    state.storage.rotate_key()
+}
 
+/// Whether data at rest is encrypted, for the settings screen
 #[tauri::command]
-async fn get_sync_settings(state: State<'_, Arc<RwLock<AppState>>>) -> Result<SyncSettings, String> {
+async fn get_security_status(state: State<'_, Arc<RwLock<AppState>>>) -> Result<SecurityStatus, String> {
     let state = state.read().await;
-    state.storage.load_sync_settings()
+    Ok(SecurityStatus {
+        settings_encrypted: state.storage.is_settings_encrypted(),
+        database_encrypted: state.db.is_encrypted()?,
+        passphrase_mode: state.storage.is_passphrase_mode(),
+        sync_password_in_keychain: state.storage.is_password_in_keychain(),
+        keychain_available: cfg!(feature = "keychain"),
+    })
 }
 
+/// Argument `wipe_all_data` must be given, so a stray call can't erase anything
+const WIPE_CONFIRMATION: &str = "DELETE ALL DATA";
+
+/// Erase the database, keys, settings and keychain entries. Sync stops and the
+/// database is closed, so the app has to restart afterwards. Safe to repeat;
+/// returns the files that couldn't be removed.
 #[tauri::command]
-async fn save_sync_settings(
-    settings: SyncSettings,
+async fn wipe_all_data(
+    confirmation: String,
     state: State<'_, Arc<RwLock<AppState>>>,
-) -> Result<(), String> {
+    app_handle: AppHandle,
+) -> Result<Vec<FileError>, String> {
+    if confirmation != WIPE_CONFIRMATION {
+        return Err(format!("Type \"{}\" to confirm", WIPE_CONFIRMATION));
+    }
+    
+    let state = state.write().await;
+    state.sync_manager.stop_sync(&app_handle).await;
+    let _cycles = state.sync_manager.hold_cycles().await;
+    
+    state.db.close();
+    let mut failed = util::remove_files(&state.db.files());
+    failed.extend(state.storage.wipe());
+    
+    if failed.is_empty() {
+        log::warn!("Wiped all data");
+    } else {
+        log::error!("Wiped all data except {} files", failed.len());
+    }
+    Ok(failed)
+}
+
+#[tauri::command]
+async fn get_app_settings(
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<AppSettings, String> {
     let state = state.read().await;
-    state.storage.save_sync_settings(&settings)
+    let mut settings = state.storage.load_app_settings()?;
+    settings.shortcut_error = quick_add::shortcut_error(&app_handle, settings.quick_add_shortcut.as_deref());
+    Ok(settings)
 }
 
 #[tauri::command]
-async fn clear_sync_settings(state: State<'_, Arc<RwLock<AppState>>>) -> Result<(), String> {
+async fn save_app_settings(
+    mut settings: AppSettings,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    settings.webhook_url = webhooks::normalize_url(settings.webhook_url.as_deref())?;
+    logging::set_level(&settings.log_level)?;
+    
     let state = state.read().await;
-    state.storage.clear_sync_settings()
+    let previous = state.storage.load_app_settings()?;
+    if settings.quick_add_shortcut != previous.quick_add_shortcut {
+        if let Err(e) = quick_add::register_shortcut(&app_handle, settings.quick_add_shortcut.as_deref()) {
+            // Keep the old shortcut working instead of leaving none
+            let _ = quick_add::register_shortcut(&app_handle, previous.quick_add_shortcut.as_deref());
+            return Err(e);
+        }
+    }
+    
+    state.storage.save_app_settings(&settings)?;
+    state.reminders.refresh();
+    state.db.set_update_debounce(Duration::from_millis(settings.update_debounce_ms));
+    state.webhooks.set_url(settings.webhook_url);
+    Ok(())
+}
+
+/// Where the log file is, for attaching to bug reports
+#[tauri::command]
+fn get_log_path() -> Result<String, String> {
+    logging::log_path()
+        .map(|path| path.to_string_lossy().into_owned())
+        .ok_or_else(|| "Logging to a file is not available".to_string())
+}
+
+/// Whether this is demo mode, where nothing is kept after the app quits
+#[tauri::command]
+async fn is_demo_mode(state: State<'_, Arc<RwLock<AppState>>>) -> Result<bool, String> {
+    Ok(state.read().await.db.is_in_memory())
 }
 
 // ============ Sync Commands ============
@@ -132,6 +1055,12 @@ async fn get_sync_state(state: State<'_, Arc<RwLock<AppState>>>) -> Result<SyncS
     Ok(state.sync_manager.get_state().await)
 }
 
+#[tauri::command]
+async fn get_sync_errors(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<SyncErrorEntry>, String> {
+    let state = state.read().await;
+    Ok(state.sync_manager.get_errors().await)
+}
+
 #[tauri::command]
 async fn start_sync(
     state: State<'_, Arc<RwLock<AppState>>>,
@@ -158,6 +1087,21 @@ async fn stop_sync(
     Ok(())
 }
 
+#[tauri::command]
+async fn pause_sync(
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let state = state.read().await;
+    state.sync_manager.pause(&app_handle).await
+}
+
+#[tauri::command]
+async fn resume_sync(state: State<'_, Arc<RwLock<AppState>>>) -> Result<(), String> {
+    let state = state.read().await;
+    state.sync_manager.resume().await
+}
+
 #[tauri::command]
 async fn restart_sync(
     state: State<'_, Arc<RwLock<AppState>>>,
@@ -179,6 +1123,77 @@ async fn restart_sync(
     Ok(())
 }
 
+/// Check the given (possibly unsaved) settings point at a CouchDB server
+#[tauri::command]
+async fn test_sync_connection(
+    mut settings: SyncSettings,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<String, String> {
+    if settings.sync_url.is_empty() {
+        return Err("Sync URL is not configured".to_string());
+    }
+    
+    if let Ok(stored) = state.read().await.storage.load_sync_settings() {
+        settings.keep_secrets_from(&stored);
+    }
+    
+    sync::test_connection(&settings).await
+}
+
+/// Check the given (possibly unsaved) settings reach the server, authenticate
+/// and find the sync database. Nothing is saved or created.
+#[tauri::command]
+async fn test_sync_credentials(
+    mut settings: SyncSettings,
+    state: State<'_, Arc<RwLock<AppState>>>,
+) -> Result<CredentialCheck, String> {
+    if settings.sync_url.is_empty() {
+        return Err("Sync URL is not configured".to_string());
+    }
+    
+    if let Ok(stored) = state.read().await.storage.load_sync_settings() {
+        settings.keep_secrets_from(&stored);
+    }
+    
+    sync::test_credentials(&settings).await
+}
+
+#[tauri::command]
+async fn trigger_sync(
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    let state = state.read().await;
+    let settings = state.storage.load_sync_settings()?;
+    
+    if settings.sync_url.is_empty() {
+        return Err("Sync URL is not configured".to_string());
+    }
+    
+    state.sync_manager.sync_once(settings, state.db.clone(), app_handle).await
+}
+
+#[tauri::command]
+async fn get_conflicts(state: State<'_, Arc<RwLock<AppState>>>) -> Result<Vec<TaskConflict>, String> {
+    let state = state.read().await;
+    state.db.get_conflicts()
+}
+
+#[tauri::command]
+async fn resolve_conflict(
+    task_id: String,
+    winner: ConflictWinner,
+    state: State<'_, Arc<RwLock<AppState>>>,
+    app_handle: AppHandle,
+) -> Result<Task, String> {
+    let state = state.read().await;
+    let task = state.db.resolve_conflict(&task_id, winner)?;
+    let remaining = state.db.get_conflicts()?.len();
+    let _ = app_handle.emit("sync-conflict", remaining);
+    let _ = app_handle.emit("tasks-changed", ());
+    Ok(task)
+}
+
 // ============ Date Picker Popup Commands ============
 
 const POPUP_WIDTH: f64 = 288.0;
@@ -197,7 +1212,7 @@ async fn open_date_picker_popup(
     if let Some(existing) = app_handle.get_webview_window("datepicker") {
         let _ = existing.close();
     }
-
+    
     // Calculate if we should open above or below
     let space_below = screen_height - button_bottom;
     let space_above = button_top;
@@ -212,12 +1227,12 @@ async fn open_date_picker_popup(
         // Not enough space either way, prefer below
         (button_bottom + 4, false)
     };
-
+    
     let url = match &current_date {
         Some(date) => format!("/popup/datepicker?date={}&above={}", date, open_above),
         None => format!("/popup/datepicker?above={}", open_above),
     };
-
+    
     let _popup = WebviewWindowBuilder::new(
         &app_handle,
         "datepicker",
@@ -235,7 +1250,7 @@ async fn open_date_picker_popup(
     .shadow(true)
     .build()
     .map_err(|e| format!("Failed to create popup: {}", e))?;
-
+    
     Ok(())
 }
 
@@ -261,34 +1276,264 @@ async fn emit_date_selected(app_handle: AppHandle, date: Option<String>) -> Resu
     Ok(())
 }
 
+/// Write queued edits once they've been left alone for the update debounce
+fn spawn_update_flusher(db: Arc<Database>, app_handle: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(db.update_debounce()).await;
+            
+            match db.flush_queued(false) {
+                Ok(written) if !written.is_empty() => {
+                    let _ = app_handle.emit("tasks-changed", ());
+                }
+                Ok(_) => {}
+                Err(e) => log::error!("Failed to write queued updates: {}", e),
+            }
+        }
+    });
+}
+
+// ============ Legacy Import ============
+
+/// Move tasks from the pre-SQLite `tasks.json` into the database on first launch.
+/// The file is renamed to `tasks.json.bak` afterwards so this only happens once.
+fn import_legacy_tasks(app_dir: &Path, db: &Database) -> Result<(), String> {
+    let json_path = app_dir.join("tasks.json");
+    if !json_path.exists() {
+        return Ok(());
+    }
+    
+    let contents = std::fs::read_to_string(&json_path)
+        .map_err(|e| format!("Failed to read tasks.json: {}", e))?;
+    let legacy: Vec<LegacyTask> = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse tasks.json: {}", e))?;
+    
+    let imported = db.import_legacy_json(legacy)?;
+    log::info!("Migrated {} task(s) from tasks.json", imported);
+    
+    std::fs::rename(&json_path, app_dir.join("tasks.json.bak"))
+        .map_err(|e| format!("Failed to move tasks.json aside: {}", e))
+}
+
+/// Upper bound on the final push when the app exits
+const EXIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Set once the exit flush has started, so quitting again exits straight away
+static EXIT_FLUSH_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// Hold the first exit request back while unsynced changes get one last push
+fn flush_before_exit(app: &AppHandle, api: &ExitRequestApi, code: Option<i32>) {
+    let Some(state) = app.try_state::<Arc<RwLock<AppState>>>() else {
+        return;
+    };
+    if EXIT_FLUSH_STARTED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    api.prevent_exit();
+    
+    let state = state.inner().clone();
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        match tokio::time::timeout(EXIT_FLUSH_TIMEOUT, flush_pending(&state, &app)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log::error!(status = "flushing"; "Exit flush failed: {}", e),
+            Err(_) => log::warn!(status = "flushing"; "Exit flush timed out, exiting anyway"),
+        }
+        app.exit(code.unwrap_or(0));
+    });
+}
+
+async fn flush_pending(state: &RwLock<AppState>, app: &AppHandle) -> Result<(), String> {
+    let state = state.read().await;
+    // Queued edits are written even when there's no server to push them to
+    if let Err(e) = state.db.flush_queued(true) {
+        log::error!("Failed to write queued updates: {}", e);
+    }
+    
+    let settings = state.storage.load_sync_settings()?;
+    
+    if !settings.is_sync_enabled() || settings.sync_url.is_empty() || !state.db.has_unpushed_changes()? {
+        return Ok(());
+    }
+    
+    state.sync_manager.flush(settings, state.db.clone(), app.clone()).await
+}
+
 // ============ App Entry Point ============
 
+/// Directory for the database, keys and settings. Portable mode (a `portable.txt`
+/// next to the executable, see `util::portable_dir`) takes precedence over the
+/// OS app data directory. The webview's own cache stays in the OS location.
+fn data_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let exe = std::env::current_exe().ok();
+    if let Some(dir) = exe.as_deref().and_then(util::portable_dir) {
+        return Ok(dir);
+    }
+    
+    app.path().app_data_dir().map_err(|e| format!("Failed to get app directory: {}", e))
+}
+
+/// Check the database file's integrity before opening it and, when that fails,
+/// rebuild it from what can still be read
+fn check_database(db_path: &Path, key_hex: &str) -> Option<IntegrityWarning> {
+    match Database::check_file(db_path, key_hex) {
+        Ok(true) => return None,
+        Ok(false) => {}
+        Err(e) => {
+            log::warn!("{}", e);
+            return None;
+        }
+    }
+    
+    log::error!("Database failed its integrity check, recovering");
+    match Database::recover(db_path, key_hex) {
+        Ok(report) => {
+            log::info!(damaged_path:% = report.damaged_path; "Recovered {} rows", report.recovered_rows);
+            Some(IntegrityWarning { recovery: Some(report), error: None })
+        }
+        Err(e) => {
+            log::error!("Recovery failed: {}", e);
+            Some(IntegrityWarning { recovery: None, error: Some(e) })
+        }
+    }
+}
+
+/// Demo mode, chosen with `--demo` or `TASKIST_DEMO=1`: sample tasks in an
+/// in-memory database, with settings and logs in a temporary folder that's
+/// removed on exit. Nothing the user had before is read or changed.
+fn demo_mode() -> bool {
+    std::env::args().any(|arg| arg == "--demo")
+        || std::env::var("TASKIST_DEMO").is_ok_and(|value| value == "1")
+}
+
+fn demo_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("taskist-demo-{}", std::process::id()))
+}
+
+/// Create the data directory, start logging there and open the settings storage
+/// and database in it
+fn open_data(app: &AppHandle) -> Result<(PathBuf, EncryptedStorage, Database, Option<IntegrityWarning>), String> {
+    let demo = demo_mode();
+    let app_dir = if demo { demo_dir() } else { data_dir(app)? };
+    std::fs::create_dir_all(&app_dir)
+        .map_err(|e| format!("Failed to create the data folder {}: {}", app_dir.display(), e))?;
+    
+    // Without a log file the app still runs, logging to stderr only
+    if let Err(e) = logging::init(&app_dir) {
+        eprintln!("[log] {}", e);
+    }
+    log::info!(data_dir:% = app_dir.display(); "Starting Taskist {}", app.package_info().version);
+    
+    let (storage, db, integrity_warning) = open_storage(&app_dir, demo)?;
+    Ok((app_dir, storage, db, integrity_warning))
+}
+
+/// Open the settings storage and database in `app_dir`
+fn open_storage(app_dir: &Path, demo: bool) -> Result<(EncryptedStorage, Database, Option<IntegrityWarning>), String> {
+    let storage = EncryptedStorage::new(app_dir.to_path_buf())
+        .map_err(|e| format!("Failed to initialize encrypted storage: {}", e))?;
+    
+    if demo {
+        log::info!("Demo mode: changes are discarded on exit");
+        let db = Database::new_in_memory()
+            .and_then(|db| db.seed_demo_data().map(|()| db))
+            .map_err(|e| format!("Failed to set up demo data: {}", e))?;
+        return Ok((storage, db, None));
+    }
+    
+    let db_path = app_dir.join("tasks.db");
+    let check_integrity = db_path.exists() && storage.load_app_settings().unwrap_or_default().check_integrity_on_startup;
+    let integrity_warning = if check_integrity {
+        check_database(&db_path, &storage.database_key())
+    } else {
+        None
+    };
+    
+    let db = Database::new(db_path, &storage.database_key())
+        .map_err(|e| format!("Failed to initialize database: {}", e))?;
+    
+    Ok((storage, db, integrity_warning))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_os::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .setup(|app| {
-            let app_dir = app.path().app_data_dir().expect("Failed to get app directory");
-            std::fs::create_dir_all(&app_dir).expect("Failed to create app directory");
-            
-            let db_path = app_dir.join("tasks.db");
-            let db = Database::new(db_path).expect("Failed to initialize database");
+            // Tauri panics on a failed setup hook, so explain and exit instead
+            let (app_dir, storage, db, integrity_warning) = match open_data(app.handle()) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    fatal::show_startup_error(&e);
+                    std::process::exit(1);
+                }
+            };
             
-            let storage = EncryptedStorage::new(app_dir.clone())
-                .expect("Failed to initialize encrypted storage");
+            if let Err(e) = import_legacy_tasks(&app_dir, &db) {
+                log::warn!("Legacy import failed: {}", e);
+            }
             
             let sync_manager = SyncManager::new();
+            let app_settings = storage.load_app_settings().unwrap_or_default();
+            db.set_update_debounce(Duration::from_millis(app_settings.update_debounce_ms));
+            if let Err(e) = logging::set_level(&app_settings.log_level) {
+                log::warn!("{}", e);
+            }
             
             let state = AppState {
                 db: Arc::new(db),
                 storage: Arc::new(storage),
                 sync_manager: Arc::new(sync_manager),
+                reminders: Arc::new(ReminderScheduler::new()),
+                webhooks: Arc::new(WebhookNotifier::new(app_settings.webhook_url)),
             };
             
+            // Some Linux desktops lack the appindicator library the tray needs, which
+            // panics inside the tray backend rather than returning an error
+            let tray_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                tray::setup(app.handle())
+            }));
+            match tray_result {
+                Ok(Ok(())) => tray::refresh(app.handle(), &state.db),
+                Ok(Err(e)) => log::warn!("Tray icon unavailable: {}", e),
+                Err(_) => log::warn!("Tray icon not supported on this system"),
+            }
+            
+            // A taken shortcut is reported through get_app_settings, not fatal
+            let shortcut = state.storage.load_app_settings()
+                .unwrap_or_default()
+                .quick_add_shortcut;
+            if let Err(e) = quick_add::register_shortcut(app.handle(), shortcut.as_deref()) {
+                log::warn!("Quick add: {}", e);
+            }
+            
+            app.manage(StartupSnapshot(std::sync::Mutex::new(state.db.get_all_tasks().ok())));
+            app.manage(StartupIntegrityWarning(std::sync::Mutex::new(integrity_warning)));
+            
+            state.webhooks.clone().spawn();
+            spawn_update_flusher(state.db.clone(), app.handle().clone());
+            
+            // Due-date reminders, re-evaluated whenever tasks change
+            state.reminders.clone().spawn(state.db.clone(), state.storage.clone(), app.handle().clone());
+            let reminders = state.reminders.clone();
+            let sync_manager = state.sync_manager.clone();
+            let db = state.db.clone();
+            let app_handle = app.handle().clone();
+            app.listen("tasks-changed", move |_| {
+                // Whatever changed, the frontend has to fetch it anyway
+                if let Some(snapshot) = app_handle.try_state::<StartupSnapshot>() {
+                    snapshot.take();
+                }
+                reminders.refresh();
+                sync_manager.notify_local_change();
+                tray::refresh(&app_handle, &db);
+            });
+            
             app.manage(Arc::new(RwLock::new(state)));
-
+            
             // Check for updates on startup (optional - comment out if you want manual checks only)
             // UNCOMMENT THE LINES BELOW FOR AUTOMATIC UPDATE CHECKS ON STARTUP:
             let app_handle = app.handle().clone();
@@ -302,26 +1547,139 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             // Task commands
             get_all_tasks,
+            frontend_ready,
+            get_root_tasks,
+            get_subtasks,
+            get_all_tasks_sorted_by_priority,
+            get_all_tasks_by_created,
+            search_tasks,
+            get_tasks_by_tag,
+            get_all_tags,
+            rename_tag,
+            get_tasks_due_between,
+            get_completed_between,
+            get_overdue_tasks,
+            get_today_tasks,
+            get_completions_by_day,
+            query_tasks,
+            get_tasks_page,
+            count_tasks,
+            get_task_stats,
+            duplicate_task,
+            snooze_task,
             add_task,
+            quick_add_task,
             update_task,
+            update_task_fields,
+            queue_task_update,
+            set_task_notes,
+            set_task_color,
+            start_task_timer,
+            stop_task_timer,
+            get_task_time_spent,
+            get_task_history,
             delete_task,
             toggle_task_completion,
+            bulk_complete_tasks,
+            bulk_delete_tasks,
+            get_archived_tasks,
+            archive_task,
+            unarchive_task,
+            archive_completed_older_than,
+            get_trash,
+            restore_task,
+            purge_deleted_older_than,
+            undo,
+            redo,
             reorder_task,
+            reorder_task_in_project,
             move_task_to_position,
+            move_task_to_index,
+            move_task_to_index_in_project,
+            move_tasks_to_index,
+            move_task_between,
+            export_tasks_csv,
+            export_tasks_markdown,
+            export_tasks_ics,
+            backup_tasks,
+            compact_database,
+            check_database_integrity,
+            restore_tasks,
+            import_todoist,
+            // Project commands
+            get_all_projects,
+            add_project,
+            rename_project,
+            delete_project,
+            get_tasks_for_project,
+            // Template commands
+            save_template,
+            list_templates,
+            delete_template,
+            instantiate_template,
             // Settings commands
             get_sync_settings,
             save_sync_settings,
             clear_sync_settings,
+            reset_sync_settings,
+            list_sync_profiles,
+            save_sync_profile,
+            delete_sync_profile,
+            set_active_sync_profile,
+            rotate_encryption_key,
+            get_security_status,
+            wipe_all_data,
+            get_app_settings,
+            save_app_settings,
+            get_log_path,
+            is_demo_mode,
             // Sync commands
             get_sync_state,
+            get_sync_errors,
             start_sync,
             stop_sync,
+            pause_sync,
+            resume_sync,
             restart_sync,
+            trigger_sync,
+            test_sync_connection,
+            test_sync_credentials,
+            get_conflicts,
+            resolve_conflict,
             // Date picker commands
             open_date_picker_popup,
             close_date_picker_popup,
             emit_date_selected
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
\ No newline at end of file
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| match event {
+            RunEvent::ExitRequested { api, code, .. } => flush_before_exit(app, &api, code),
+            RunEvent::Exit if demo_mode() => {
+                let _ = std::fs::remove_dir_all(demo_dir());
+            }
+            _ => {}
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn an_unusable_data_folder_is_an_error_not_a_panic() {
+        let dir = util::temp_dir();
+        
+        // A file where the folder should be can't be written into
+        let file = dir.join("not-a-folder");
+        std::fs::write(&file, b"").unwrap();
+        assert!(open_storage(&file.join("data"), false).is_err());
+        
+        // Nor can a folder where the database file should be
+        let app_dir = dir.join("data");
+        std::fs::create_dir_all(app_dir.join("tasks.db")).unwrap();
+        assert!(open_storage(&app_dir, false).is_err());
+        
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use chrono::{SecondsFormat, Utc};
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// Size at which the log is rotated
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// Rotated logs kept next to the current one, `taskist.log.1` being the newest
+const KEPT_LOGS: usize = 3;
+
+const LOG_FILE: &str = "taskist.log";
+
+static LOG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Writes every record to stderr and to `logs/taskist.log` in the data directory,
+/// rotating the file once it reaches `MAX_LOG_BYTES`
+struct FileLogger {
+    path: PathBuf,
+    file: Mutex<LogFile>,
+}
+
+struct LogFile {
+    file: File,
+    len: u64,
+}
+
+/// Start logging into `data_dir`, at info until `set_level` says otherwise.
+/// Returns the log file's path.
+pub fn init(data_dir: &Path) -> Result<PathBuf, String> {
+    let dir = data_dir.join("logs");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create log directory: {}", e))?;
+    
+    let path = dir.join(LOG_FILE);
+    let file = open(&path)?;
+    let len = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+    
+    let logger = FileLogger { path: path.clone(), file: Mutex::new(LogFile { file, len }) };
+    log::set_boxed_logger(Box::new(logger)).map_err(|e| format!("Failed to install logger: {}", e))?;
+    log::set_max_level(LevelFilter::Info);
+    
+    let _ = LOG_PATH.set(path.clone());
+    Ok(path)
+}
+
+/// Apply a level from settings: `error`, `warn`, `info`, `debug`, `trace` or `off`
+pub fn set_level(level: &str) -> Result<(), String> {
+    let level: LevelFilter = level.parse().map_err(|_| format!("Unknown log level '{}'", level))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// The current log file, once `init` succeeded
+pub fn log_path() -> Option<PathBuf> {
+    LOG_PATH.get().cloned()
+}
+
+fn open(path: &Path) -> Result<File, String> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("Failed to open log file: {}", e))
+}
+
+impl FileLogger {
+    /// Shift `taskist.log.N` to `.N+1`, dropping the oldest, and start a new file
+    fn rotate(&self, current: &mut LogFile) -> Result<(), String> {
+        let rotated = |n: usize| PathBuf::from(format!("{}.{}", self.path.display(), n));
+        for n in (1..KEPT_LOGS).rev() {
+            let _ = fs::rename(rotated(n), rotated(n + 1));
+        }
+        fs::rename(&self.path, rotated(1)).map_err(|e| format!("Failed to rotate log: {}", e))?;
+        
+        *current = LogFile { file: open(&self.path)?, len: 0 };
+        Ok(())
+    }
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+    
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        
+        // Module name without the crate, e.g. `sync`
+        let target = record.target().rsplit("::").next().unwrap_or_default();
+        let mut line = format!(
+            "{} {:<5} {}: {}",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            record.level(),
+            target,
+            record.args(),
+        );
+        let _ = record.key_values().visit(&mut KeyValues(&mut line));
+        line.push('\n');
+        
+        eprint!("{}", line);
+        
+        let Ok(mut current) = self.file.lock() else {
+            return;
+        };
+        if current.len + line.len() as u64 > MAX_LOG_BYTES {
+            if let Err(e) = self.rotate(&mut current) {
+                eprintln!("[log] {}", e);
+            }
+        }
+        if current.file.write_all(line.as_bytes()).is_ok() {
+            current.len += line.len() as u64;
+        }
+    }
+    
+    fn flush(&self) {
+        if let Ok(mut current) = self.file.lock() {
+            let _ = current.file.flush();
+        }
+    }
+}
+
+/// Appends structured fields to a line as ` key=value`
+struct KeyValues<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        self.0.push_str(&format!(" {}={}", key, value));
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+/// Whether the OS reports the current connection as metered. Platforms without
+/// a way to tell, or where the lookup fails, count as unmetered so sync never
+/// stops because detection broke.
+pub async fn is_metered() -> bool {
+    platform::is_metered().await.unwrap_or(false)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    /// NetworkManager's `NMMetered` values for "yes" and "guessed yes"
+    const METERED_YES: u32 = 1;
+    const METERED_GUESS_YES: u32 = 3;
+    
+    pub async fn is_metered() -> Option<bool> {
+        let connection = zbus::Connection::system().await.ok()?;
+        let proxy = zbus::Proxy::new(
+            &connection,
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+        )
+        .await
+        .ok()?;
+        let metered: u32 = proxy.get_property("Metered").await.ok()?;
+        Some(matches!(metered, METERED_YES | METERED_GUESS_YES))
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use windows::Networking::Connectivity::{NetworkCostType, NetworkInformation};
+    
+    pub async fn is_metered() -> Option<bool> {
+        let profile = NetworkInformation::GetInternetConnectionProfile().ok()?;
+        let cost = profile.GetConnectionCost().ok()?;
+        let cost_type = cost.NetworkCostType().ok()?;
+        Some(cost_type == NetworkCostType::Fixed || cost_type == NetworkCostType::Variable)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    pub async fn is_metered() -> Option<bool> {
+        None
+    }
+}
@@ -0,0 +1,62 @@
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+
+const QUICK_ADD_WIDTH: f64 = 420.0;
+const QUICK_ADD_HEIGHT: f64 = 64.0;
+
+/// Open the small always-on-top quick add window, or focus it if already open
+pub fn open_window(app: &AppHandle) -> Result<(), String> {
+    if let Some(existing) = app.get_webview_window("quickadd") {
+        return existing.set_focus().map_err(|e| format!("Failed to focus quick add: {}", e));
+    }
+    
+    WebviewWindowBuilder::new(app, "quickadd", WebviewUrl::App("/popup/quickadd".into()))
+        .title("Quick Add")
+        .inner_size(QUICK_ADD_WIDTH, QUICK_ADD_HEIGHT)
+        .center()
+        .decorations(false)
+        .resizable(false)
+        .always_on_top(true)
+        .skip_taskbar(true)
+        .focused(true)
+        .shadow(true)
+        .build()
+        .map_err(|e| format!("Failed to create quick add window: {}", e))?;
+    
+    Ok(())
+}
+
+/// Replace the global quick add shortcut. Fails if the accelerator is invalid or
+/// already taken by another application.
+pub fn register_shortcut(app: &AppHandle, accelerator: Option<&str>) -> Result<(), String> {
+    let shortcuts = app.global_shortcut();
+    shortcuts.unregister_all()
+        .map_err(|e| format!("Failed to unregister shortcuts: {}", e))?;
+    
+    let Some(accelerator) = accelerator else {
+        return Ok(());
+    };
+    
+    shortcuts
+        .on_shortcut(accelerator, |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed {
+                if let Err(e) = open_window(app) {
+                    log::error!("{}", e);
+                }
+            }
+        })
+        .map_err(|e| format!("Could not register shortcut {}: {}", accelerator, e))
+}
+
+/// Explain why the configured shortcut isn't active, if it isn't
+pub fn shortcut_error(app: &AppHandle, accelerator: Option<&str>) -> Option<String> {
+    let accelerator = accelerator?;
+    if app.global_shortcut().is_registered(accelerator) {
+        return None;
+    }
+    
+    Some(format!(
+        "Shortcut {} is unavailable; it may already be in use by another application",
+        accelerator
+    ))
+}
@@ -0,0 +1,101 @@
+/// Digits of a rank in ascending byte order, so comparing ranks as strings
+/// compares the fractions they stand for
+const DIGITS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// Width of a rank converted from a float order; 62^11 covers every `u64`
+const ORDER_WIDTH: usize = 11;
+
+/// A task's position as a base-62 fraction without the leading "0.", e.g. `V`
+/// sits halfway down the list. There's always room for another rank between two
+/// different ones, so a move only rewrites the moved task and concurrent moves on
+/// two devices never need renumbering. Ranks never end in `0`, which keeps string
+/// order and numeric order the same.
+///
+/// Returns a rank after `before` and before `after`, where `None` is the start or
+/// end of the list. Neighbours that tie or are out of order (e.g. two devices
+/// appended at once) get a rank directly after `before`.
+pub fn between(before: Option<&str>, after: Option<&str>) -> String {
+    let before = before.unwrap_or("");
+    match after {
+        Some(after) if before.is_empty() => decrement(after.as_bytes()),
+        Some(after) if after > before => midpoint(before.as_bytes(), Some(after.as_bytes())),
+        Some(_) => format!("{}{}", before, midpoint(b"", None)),
+        None => increment(before.as_bytes()),
+    }
+}
+
+/// The rank a float order from before ranks existed maps to. Order-preserving
+/// and deterministic, so every device converts the same order to the same rank.
+pub fn from_order(order: f64) -> String {
+    // Flip the bits so unsigned comparison matches float comparison
+    let bits = order.to_bits();
+    let mut key = if order.is_sign_negative() { !bits } else { bits | (1 << 63) };
+    
+    let mut digits = [b'0'; ORDER_WIDTH];
+    for digit in digits.iter_mut().rev() {
+        *digit = DIGITS[(key % 62) as usize];
+        key /= 62;
+    }
+    
+    let rank = String::from_utf8_lossy(&digits).trim_end_matches('0').to_string();
+    if rank.is_empty() {
+        between(None, None)
+    } else {
+        rank
+    }
+}
+
+fn digit_value(c: u8) -> usize {
+    DIGITS.iter().position(|&d| d == c).unwrap_or(0)
+}
+
+/// Shortest rank above `rank`, stepping a single digit where possible so
+/// repeatedly appending to the list grows ranks slowly
+fn increment(rank: &[u8]) -> String {
+    match rank.iter().position(|&c| digit_value(c) + 1 < DIGITS.len()) {
+        Some(i) => {
+            let mut next = rank[..i].to_vec();
+            next.push(DIGITS[digit_value(rank[i]) + 1]);
+            String::from_utf8_lossy(&next).into_owned()
+        }
+        None => format!("{}{}", String::from_utf8_lossy(rank), midpoint(b"", None)),
+    }
+}
+
+/// Shortest rank below `rank`, the counterpart of `increment` for prepending
+fn decrement(rank: &[u8]) -> String {
+    match rank.iter().position(|&c| digit_value(c) > 1) {
+        Some(i) => {
+            let mut prev = rank[..i].to_vec();
+            prev.push(DIGITS[digit_value(rank[i]) - 1]);
+            String::from_utf8_lossy(&prev).into_owned()
+        }
+        None => midpoint(b"", Some(rank)),
+    }
+}
+
+/// A fraction strictly between `a` and `b` (`None` being 1), given `a < b`
+fn midpoint(a: &[u8], b: Option<&[u8]>) -> String {
+    if let Some(b) = b {
+        // Keep any shared prefix, reading `a` as padded with zeros
+        let shared = (0..b.len())
+            .take_while(|&i| a.get(i).copied().unwrap_or(b'0') == b[i])
+            .count();
+        if shared > 0 {
+            let rest = midpoint(a.get(shared..).unwrap_or(&[]), Some(&b[shared..]));
+            return format!("{}{}", String::from_utf8_lossy(&b[..shared]), rest);
+        }
+    }
+    
+    let digit_a = a.first().map_or(0, |&c| digit_value(c));
+    let digit_b = b.and_then(|b| b.first()).map_or(DIGITS.len(), |&c| digit_value(c));
+    if digit_b > digit_a + 1 {
+        return (DIGITS[(digit_a + digit_b).div_ceil(2)] as char).to_string();
+    }
+    
+    match b {
+        // `b` continues past its first digit, so that digit alone is already below it
+        Some(b) if b.len() > 1 => (b[0] as char).to_string(),
+        _ => format!("{}{}", DIGITS[digit_a] as char, midpoint(a.get(1..).unwrap_or(&[]), None)),
+    }
+}
@@ -0,0 +1,168 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, Months, NaiveDate, NaiveDateTime, Weekday};
+
+/// The subset of iCal RRULE (RFC 5545) supported for repeating tasks:
+/// `FREQ` (required), `INTERVAL`, `BYDAY` (weekly rules only) and `UNTIL`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecurrenceRule {
+    pub freq: Frequency,
+    pub interval: u32,
+    pub by_day: Vec<Weekday>,
+    pub until: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl RecurrenceRule {
+    pub fn parse(rule: &str) -> Result<Self, String> {
+        let body = rule.trim();
+        let body = body.strip_prefix("RRULE:").unwrap_or(body);
+        
+        let mut freq = None;
+        let mut interval = 1;
+        let mut by_day = Vec::new();
+        let mut until = None;
+        
+        for part in body.split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid recurrence rule: expected KEY=VALUE, got \"{}\"", part))?;
+            
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    freq = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(format!("Invalid recurrence rule: unsupported FREQ \"{}\"", other)),
+                    });
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("Invalid recurrence rule: INTERVAL must be a positive integer, got \"{}\"", value))?;
+                }
+                "BYDAY" => {
+                    by_day = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Result<Vec<_>, _>>()?;
+                }
+                "UNTIL" => {
+                    // Only the date portion matters for task due dates
+                    let date = value.get(..8).unwrap_or(value);
+                    until = Some(
+                        NaiveDate::parse_from_str(date, "%Y%m%d")
+                            .map_err(|_| format!("Invalid recurrence rule: UNTIL must be YYYYMMDD, got \"{}\"", value))?,
+                    );
+                }
+                other => return Err(format!("Invalid recurrence rule: unsupported part \"{}\"", other)),
+            }
+        }
+        
+        let freq = freq.ok_or_else(|| "Invalid recurrence rule: FREQ is required".to_string())?;
+        
+        if !by_day.is_empty() && freq != Frequency::Weekly {
+            return Err("Invalid recurrence rule: BYDAY is only supported with FREQ=WEEKLY".to_string());
+        }
+        
+        Ok(Self { freq, interval, by_day, until })
+    }
+    
+    /// The first occurrence after `from`
+    fn step(&self, from: NaiveDateTime) -> Option<NaiveDateTime> {
+        match self.freq {
+            Frequency::Daily => from.checked_add_signed(Duration::days(i64::from(self.interval))),
+            Frequency::Weekly if self.by_day.is_empty() => {
+                from.checked_add_signed(Duration::weeks(i64::from(self.interval)))
+            }
+            Frequency::Weekly => {
+                let week_start = |d: NaiveDate| d - Duration::days(i64::from(d.weekday().num_days_from_monday()));
+                let base_week = week_start(from.date());
+                (1..=i64::from(self.interval) * 7).find_map(|offset| {
+                    let candidate = from.checked_add_signed(Duration::days(offset))?;
+                    let weeks = (week_start(candidate.date()) - base_week).num_weeks();
+                    (weeks % i64::from(self.interval) == 0 && self.by_day.contains(&candidate.weekday()))
+                        .then_some(candidate)
+                })
+            }
+            Frequency::Monthly => from.checked_add_months(Months::new(self.interval)),
+            Frequency::Yearly => from.checked_add_months(Months::new(self.interval.checked_mul(12)?)),
+        }
+    }
+    
+    /// The next occurrence after `from` that is later than `now`, or `None` once
+    /// the rule's `UNTIL` date has passed.
+    pub fn next_after(&self, from: NaiveDateTime, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut next = self.step(from)?;
+        while next <= now {
+            next = self.step(next)?;
+        }
+        
+        match self.until {
+            Some(until) if next.date() > until => None,
+            _ => Some(next),
+        }
+    }
+}
+
+fn parse_weekday(value: &str) -> Result<Weekday, String> {
+    match value.trim().to_ascii_uppercase().as_str() {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        other => Err(format!("Invalid recurrence rule: unknown BYDAY value \"{}\"", other)),
+    }
+}
+
+/// Compute the due date of the next occurrence, keeping the format of the
+/// current due date (date-only, local date-time, or RFC 3339 with offset).
+/// Tasks without a due date repeat from today.
+pub fn next_due_date(rule: &RecurrenceRule, due_date: Option<&str>) -> Result<Option<String>, String> {
+    let now = Local::now();
+    
+    let Some(due_date) = due_date else {
+        let today = now.date_naive().and_hms_opt(0, 0, 0).expect("midnight is valid");
+        return Ok(rule
+            .next_after(today, today)
+            .map(|next| next.format("%Y-%m-%d").to_string()));
+    };
+    
+    if let Ok(dt) = DateTime::<FixedOffset>::parse_from_rfc3339(due_date) {
+        let offset = *dt.offset();
+        let now_there = now.with_timezone(&offset).naive_local();
+        return Ok(rule.next_after(dt.naive_local(), now_there).and_then(|next| {
+            next.and_local_timezone(offset).single().map(|dt| dt.to_rfc3339())
+        }));
+    }
+    
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(due_date, format) {
+            return Ok(rule
+                .next_after(naive, now.naive_local())
+                .map(|next| next.format(format).to_string()));
+        }
+    }
+    
+    if let Ok(date) = NaiveDate::parse_from_str(due_date, "%Y-%m-%d") {
+        let start = date.and_hms_opt(0, 0, 0).expect("midnight is valid");
+        let today = now.date_naive().and_hms_opt(0, 0, 0).expect("midnight is valid");
+        return Ok(rule
+            .next_after(start, today)
+            .map(|next| next.format("%Y-%m-%d").to_string()));
+    }
+    
+    Err(format!("Cannot repeat task with unrecognized due date \"{}\"", due_date))
+}
@@ -0,0 +1,152 @@
+use crate::database::{Database, Task};
+use crate::encryption::EncryptedStorage;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex, Notify};
+
+/// How often the scheduler re-checks due dates when nothing else wakes it
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Emits `task-due-soon` for open tasks whose due date enters the reminder window.
+/// Each task is announced once while it stays in the window; re-checked on every
+/// `tasks-changed`, so clearing the due date or completing the task cancels it.
+/// Also emits `overdue-count-changed` whenever the number of overdue tasks changes.
+pub struct ReminderScheduler {
+    notified: Mutex<HashSet<String>>,
+    overdue_count: Mutex<Option<usize>>,
+    wake: Notify,
+}
+
+impl ReminderScheduler {
+    pub fn new() -> Self {
+        Self {
+            notified: Mutex::new(HashSet::new()),
+            overdue_count: Mutex::new(None),
+            wake: Notify::new(),
+        }
+    }
+    
+    /// Re-evaluate reminders now instead of waiting for the next tick
+    pub fn refresh(&self) {
+        self.wake.notify_one();
+    }
+    
+    pub fn spawn(
+        self: Arc<Self>,
+        db: Arc<Database>,
+        storage: Arc<EncryptedStorage>,
+        app_handle: AppHandle,
+    ) {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                if let Err(e) = self.check(&db, &storage, &app_handle).await {
+                    log::error!("{}", e);
+                }
+                
+                tokio::select! {
+                    _ = tokio::time::sleep(CHECK_INTERVAL) => {}
+                    _ = self.wake.notified() => {}
+                }
+            }
+        });
+    }
+    
+    async fn check(
+        &self,
+        db: &Database,
+        storage: &EncryptedStorage,
+        app_handle: &AppHandle,
+    ) -> Result<(), String> {
+        let settings = storage.load_app_settings()?;
+        let now = chrono::Utc::now().timestamp_millis();
+        let window = i64::from(settings.reminder_minutes) * 60_000;
+        
+        let due_soon = db.get_tasks_due_within(now, now + window)?;
+        
+        let mut notified = self.notified.lock().await;
+        for task in take_unannounced(&mut notified, due_soon) {
+            let _ = app_handle.emit("task-due-soon", task);
+        }
+        
+        let overdue = db.get_overdue_tasks(now)?.len();
+        let mut overdue_count = self.overdue_count.lock().await;
+        if *overdue_count != Some(overdue) {
+            *overdue_count = Some(overdue);
+            let _ = app_handle.emit("overdue-count-changed", overdue);
+        }
+        
+        Ok(())
+    }
+}
+
+/// Tasks in `due_soon` that weren't announced yet. Tasks that left the window,
+/// e.g. because their due date was cleared or they were completed, are forgotten
+/// so they're announced again if they come back.
+fn take_unannounced(notified: &mut HashSet<String>, due_soon: Vec<Task>) -> Vec<Task> {
+    notified.retain(|id| due_soon.iter().any(|task| &task.id == id));
+    due_soon.into_iter()
+        .filter(|task| notified.insert(task.id.clone()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{FieldUpdate, NewTask, TaskPatch};
+    
+    const HOUR: i64 = 60 * 60 * 1000;
+    
+    fn in_hours(hours: i64) -> String {
+        let at = chrono::Utc::now() + chrono::Duration::hours(hours);
+        at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+    }
+    
+    /// One scheduler check with a two hour window, returning the titles announced
+    fn check(db: &Database, notified: &mut HashSet<String>) -> Vec<String> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let due_soon = db.get_tasks_due_within(now, now + 2 * HOUR).unwrap();
+        take_unannounced(notified, due_soon).into_iter().map(|task| task.title).collect()
+    }
+    
+    #[test]
+    fn clearing_the_due_date_cancels_the_reminder() {
+        let db = Database::new_in_memory().unwrap();
+        let task = db.add_task(NewTask {
+            title: "Call the dentist".to_string(),
+            due_date: Some(in_hours(1)),
+            ..NewTask::default()
+        }).unwrap();
+        let mut notified = HashSet::new();
+        
+        assert_eq!(check(&db, &mut notified), ["Call the dentist"]);
+        // Announced once while it stays due soon
+        assert!(check(&db, &mut notified).is_empty());
+        
+        db.update_task_fields(&task.id, TaskPatch { due_date: FieldUpdate::Clear, ..TaskPatch::default() }).unwrap();
+        assert!(check(&db, &mut notified).is_empty());
+        assert!(notified.is_empty());
+        
+        // Due again, so reminded again
+        db.update_task_fields(&task.id, TaskPatch { due_date: FieldUpdate::Set(in_hours(1)), ..TaskPatch::default() }).unwrap();
+        assert_eq!(check(&db, &mut notified), ["Call the dentist"]);
+    }
+    
+    #[test]
+    fn completing_the_task_cancels_the_reminder() {
+        let db = Database::new_in_memory().unwrap();
+        let task = db.add_task(NewTask {
+            title: "Call the dentist".to_string(),
+            due_date: Some(in_hours(1)),
+            ..NewTask::default()
+        }).unwrap();
+        let mut notified = HashSet::new();
+        check(&db, &mut notified);
+        
+        db.toggle_task_completion(&task.id).unwrap();
+        
+        assert!(check(&db, &mut notified).is_empty());
+        assert!(notified.is_empty());
+    }
+}
@@ -1,20 +1,82 @@
-use crate::database::{Database, Task};
-use crate::encryption::SyncSettings;
-use reqwest::Client;
+use crate::database::{Attachment, ConflictStrategy, Database, Priority, Project, Task};
+use crate::encryption::{split_filter, SyncSettings};
+use crate::metered;
+use crate::rank;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{Certificate, Client, Identity, Proxy, RequestBuilder, Response, StatusCode};
 use serde::{Deserialize, Serialize};
+use rand::Rng;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tauri::{AppHandle, Emitter};
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, MutexGuard, Notify, RwLock};
 use tokio::time::sleep;
 
+/// Base delay for the retry backoff after a failed cycle
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long the server may hold a long-poll `_changes` request open, kept
+/// under the HTTP client's 30s timeout
+const LONGPOLL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// How long acknowledged tombstones are kept before being purged
+const TOMBSTONE_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Documents per `_bulk_docs` request when pushing
+const SYNC_BATCH_SIZE: usize = 500;
+
+/// Minimum gap between progress events, so large syncs don't flood the frontend
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How often to look again while holding off on a metered connection
+const METERED_RECHECK: Duration = Duration::from_secs(15);
+
+/// Filter function put in a missing design document named by `sync_filter`.
+/// Passes task documents, including untyped ones from before projects existed.
+pub const TASK_FILTER_SOURCE: &str = "function (doc, req) { return !doc.type || doc.type === 'task'; }";
+
+/// Most errors `SyncManager::get_errors` remembers
+const ERROR_HISTORY_LIMIT: usize = 50;
+
+/// Cycles in a row that must fail to reach the server before sync counts as offline
+const OFFLINE_THRESHOLD: u32 = 3;
+
+/// What a pull brought in
+#[derive(Default)]
+struct PullSummary {
+    /// Documents applied from the server
+    applied: usize,
+    /// Remote edits recorded as conflicts instead
+    conflicts: usize,
+    /// Ids of tasks the pull inserted or updated
+    upserted: Vec<String>,
+    /// Ids of tasks the pull deleted
+    deleted: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
 pub enum SyncStatus {
     Idle,
     Connecting,
     Syncing,
+    /// Connected and up to date, waiting for the next cycle
+    Synced,
+    /// Loop still running but skipping cycles until resumed
     Paused,
+    /// Loop ended by `stop_sync`
+    Stopped,
+    /// Pushing the last local changes before the app exits
+    Flushing,
+    /// The server has been unreachable for `OFFLINE_THRESHOLD` cycles in a row.
+    /// Further failures are quiet until it answers again (`sync-reconnected`).
+    Offline,
     Error,
     Disabled,
 }
@@ -26,6 +88,47 @@ pub struct SyncState {
     pub last_synced: Option<i64>,
     pub error: Option<String>,
     pub sync_mode: Option<String>,
+    /// When the loop will retry after an error (epoch millis)
+    pub next_retry_at: Option<i64>,
+    /// Documents handled so far and in total during a push or pull
+    pub progress: Option<(usize, usize)>,
+    /// Why the loop is holding off when that isn't an error, e.g. a metered connection
+    pub note: Option<String>,
+}
+
+/// A past sync error, kept for diagnostics after `SyncState::error` has moved on
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncErrorEntry {
+    /// Epoch millis
+    pub at: i64,
+    pub message: String,
+}
+
+/// Outcome of `test_credentials`, one flag per step so the UI can tell a server
+/// it can't reach from rejected credentials from a missing database
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CredentialCheck {
+    pub reachable: bool,
+    pub authenticated: bool,
+    pub db_exists: bool,
+    /// CouchDB version, once the server answered
+    pub version: Option<String>,
+    /// Why the first failing step failed
+    pub error: Option<String>,
+}
+
+/// Remember `message` in the error history, dropping the oldest entry when full
+async fn record_error(errors: &Mutex<VecDeque<SyncErrorEntry>>, message: &str) {
+    let mut errors = errors.lock().await;
+    if errors.len() == ERROR_HISTORY_LIMIT {
+        errors.pop_front();
+    }
+    errors.push_back(SyncErrorEntry {
+        at: chrono::Utc::now().timestamp_millis(),
+        message: message.to_string(),
+    });
 }
 
 impl Default for SyncState {
@@ -35,10 +138,26 @@ impl Default for SyncState {
             last_synced: None,
             error: None,
             sync_mode: Some("local".to_string()),
+            next_retry_at: None,
+            progress: None,
+            note: None,
         }
     }
 }
 
+/// Delay before retrying after `failures` consecutive errors: the base interval
+/// doubled per failure up to `cap`, plus up to 10% random jitter.
+fn backoff_delay(failures: u32, cap: Duration) -> Duration {
+    let cap = cap.max(RETRY_INTERVAL);
+    let delay = RETRY_INTERVAL
+        .checked_mul(2u32.saturating_pow(failures))
+        .map_or(cap, |delay| delay.min(cap));
+    
+    let max_jitter = delay.as_millis() as u64 / 10;
+    let jitter = rand::thread_rng().gen_range(0..=max_jitter);
+    delay + Duration::from_millis(jitter)
+}
+
 // CouchDB document structure
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct CouchDoc {
@@ -47,11 +166,20 @@ struct CouchDoc {
     #[serde(rename = "_rev", skip_serializing_if = "Option::is_none")]
     rev: Option<String>,
     #[serde(flatten)]
-    task: TaskData,
+    body: DocBody,
     #[serde(rename = "_deleted", skip_serializing_if = "Option::is_none")]
     deleted: Option<bool>,
 }
 
+/// Document payload, discriminated by the `type` field. Documents written
+/// before projects existed have no `type` and are treated as tasks.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum DocBody {
+    Task(Box<TaskData>),
+    Project(ProjectData),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 struct TaskData {
@@ -60,9 +188,51 @@ struct TaskData {
     description: Option<String>,
     completed: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    completed_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     due_date: Option<String>,
     updated_at: i64,
-    order: i32,
+    /// Missing on documents written before creation time was tracked
+    #[serde(default)]
+    created_at: i64,
+    /// Missing on documents written before ranks; derived from `order` instead
+    #[serde(default)]
+    rank: String,
+    #[serde(default, skip_serializing)]
+    order: Option<f64>,
+    #[serde(default)]
+    archived: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recurrence: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    project_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    /// Metadata only; attached files are never uploaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Attachment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    color: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimate_minutes: Option<i32>,
+    /// Missing on documents from versions that merged whole tasks only
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    field_updated_at: BTreeMap<String, i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ProjectData {
+    name: String,
+    color: String,
+    order: f64,
+    updated_at: i64,
 }
 
 #[allow(dead_code)]
@@ -75,26 +245,41 @@ struct CouchResponse {
     reason: Option<String>,
 }
 
-#[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 struct AllDocsResponse {
     rows: Vec<AllDocsRow>,
-    total_rows: Option<usize>,
 }
 
-#[allow(dead_code)]
+/// A row from `_all_docs` queried by `keys`; missing documents come back
+/// with only `key` and `error: "not_found"`.
 #[derive(Deserialize, Debug)]
 struct AllDocsRow {
-    id: String,
-    #[serde(rename = "value")]
-    _value: serde_json::Value,
-    doc: Option<CouchDoc>,
+    key: String,
+    value: Option<AllDocsValue>,
+    /// Only with `include_docs`; `None` for deleted documents
+    #[serde(default)]
+    doc: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AllDocsValue {
+    rev: String,
+    #[serde(default)]
+    deleted: bool,
+}
+
+#[derive(Serialize, Debug)]
+struct BulkDocsRequest<'a> {
+    docs: &'a [CouchDoc],
 }
 
 #[derive(Deserialize, Debug)]
 struct ChangesResponse {
     results: Vec<ChangesResult>,
     last_seq: String,
+    /// Changes left after this page; only sent when a `limit` was given
+    #[serde(default)]
+    pending: Option<usize>,
 }
 
 #[allow(dead_code)]
@@ -103,7 +288,7 @@ struct ChangesResult {
     id: String,
     seq: String,
     changes: Vec<ChangesRev>,
-    doc: Option<CouchDoc>,
+    doc: Option<serde_json::Value>,
     deleted: Option<bool>,
 }
 
@@ -116,27 +301,159 @@ struct ChangesRev {
 pub struct SyncManager {
     state: Arc<RwLock<SyncState>>,
     running: Arc<RwLock<bool>>,
-    client: Client,
+    /// Bumped on every stop so a loop from an earlier start exits instead of resuming
+    generation: Arc<AtomicU64>,
+    /// Wakes a loop sleeping between cycles
+    wake: Arc<Notify>,
+    /// Cuts a blocked long-poll short so local edits are pushed right away
+    local_change: Arc<Notify>,
+    /// Seconds between cycles, re-read every iteration. 0 is manual only: after
+    /// the cycle run by `start_sync`, cycles only happen through `sync_once`.
+    interval_secs: Arc<AtomicU64>,
+    /// Held for the duration of a cycle so the loop and `sync_once` never overlap
+    cycle_lock: Arc<Mutex<()>>,
+    /// Set by `pause`, makes the loop skip cycles until `resume`
+    paused: Arc<AtomicBool>,
+    /// Recent errors, oldest first, capped at `ERROR_HISTORY_LIMIT`
+    errors: Arc<Mutex<VecDeque<SyncErrorEntry>>>,
 }
 
 impl SyncManager {
     pub fn new() -> Self {
-        let client = Client::builder()
-            .timeout(Duration::from_secs(30))
-            .build()
-            .expect("Failed to create HTTP client");
-        
         Self {
             state: Arc::new(RwLock::new(SyncState::default())),
             running: Arc::new(RwLock::new(false)),
-            client,
+            generation: Arc::new(AtomicU64::new(0)),
+            wake: Arc::new(Notify::new()),
+            local_change: Arc::new(Notify::new()),
+            interval_secs: Arc::new(AtomicU64::new(0)),
+            cycle_lock: Arc::new(Mutex::new(())),
+            paused: Arc::new(AtomicBool::new(false)),
+            errors: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+    
+    /// Tell a running loop that tasks changed locally and should be pushed
+    pub fn notify_local_change(&self) {
+        self.local_change.notify_one();
+    }
+    
+    /// Apply a changed sync interval to the running loop from its next iteration
+    pub async fn set_interval(&self, settings: &SyncSettings) {
+        let secs = settings.clamped_sync_interval_secs();
+        let changed = self.interval_secs.swap(secs, Ordering::SeqCst) != secs;
+        if changed && *self.running.read().await {
+            // Don't leave the loop parked on the old delay, or forever when it was manual
+            self.wake.notify_one();
+        }
+    }
+    
+    /// Whether the background loop is running, paused or not
+    pub async fn is_running(&self) -> bool {
+        *self.running.read().await
+    }
+    
+    /// Run one cycle right now, whether or not the background loop is running.
+    /// Waits for a cycle already in progress instead of overlapping it.
+    pub async fn sync_once(
+        &self,
+        settings: SyncSettings,
+        db: Arc<Database>,
+        app_handle: AppHandle,
+    ) -> Result<(), String> {
+        if !settings.is_sync_enabled() {
+            return Err("Sync is disabled".to_string());
         }
+        
+        let remote = Remote::from_settings(&settings)?;
+        
+        // Don't queue behind a long-poll that could sit idle for its full timeout
+        self.local_change.notify_waiters();
+        let _cycle = self.cycle_lock.lock().await;
+        
+        let last_synced = self.state.read().await.last_synced;
+        self.set_state(SyncState {
+            status: SyncStatus::Syncing,
+            last_synced,
+            error: None,
+            sync_mode: Some(settings.sync_mode.clone()),
+            next_retry_at: None,
+            progress: None,
+            note: None,
+        }, &app_handle).await;
+        
+        let result = match remote.connect().await {
+            Ok(()) => sync_cycle(&remote, &db, false, &self.state, &app_handle, std::future::pending()).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(summary) => {
+                finish_cycle(&db, summary, &settings.sync_mode, &self.state, &app_handle).await;
+                Ok(())
+            }
+            Err(e) => {
+                log::error!(db_url:% = remote.log_url, status = "error"; "Sync failed: {}", e);
+                record_error(&self.errors, &e).await;
+                self.set_state(SyncState {
+                    status: SyncStatus::Error,
+                    last_synced,
+                    error: Some(e.clone()),
+                    sync_mode: Some(settings.sync_mode.clone()),
+                    next_retry_at: None,
+                    progress: None,
+                    note: None,
+                }, &app_handle).await;
+                Err(e)
+            }
+        }
+    }
+    
+    /// Wait for a running cycle to finish and keep new ones from starting until
+    /// the guard is dropped, e.g. while the database is compacted
+    pub async fn hold_cycles(&self) -> MutexGuard<'_, ()> {
+        // Don't wait out an idle long-poll
+        self.local_change.notify_waiters();
+        self.cycle_lock.lock().await
+    }
+    
+    /// Push pending local changes without pulling, for a last flush before exit.
+    /// The caller bounds how long this may take.
+    pub async fn flush(
+        &self,
+        settings: SyncSettings,
+        db: Arc<Database>,
+        app_handle: AppHandle,
+    ) -> Result<(), String> {
+        let remote = Remote::from_settings(&settings)?;
+        
+        self.local_change.notify_waiters();
+        let _cycle = self.cycle_lock.lock().await;
+        
+        let last_synced = self.state.read().await.last_synced;
+        self.set_state(SyncState {
+            status: SyncStatus::Flushing,
+            last_synced,
+            error: None,
+            sync_mode: Some(settings.sync_mode.clone()),
+            next_retry_at: None,
+            progress: None,
+            note: None,
+        }, &app_handle).await;
+        
+        let (client, db_url) = (&remote.client, remote.db_url.as_str());
+        let mut progress = Progress::new(&self.state, &app_handle);
+        push_changes(client, db_url, remote.auth.as_ref(), remote.proxy.as_deref(), &db, &mut progress).await
     }
     
     pub async fn get_state(&self) -> SyncState {
         self.state.read().await.clone()
     }
     
+    /// Recent sync errors, oldest first
+    pub async fn get_errors(&self) -> Vec<SyncErrorEntry> {
+        self.errors.lock().await.iter().cloned().collect()
+    }
+    
     async fn set_state(&self, state: SyncState, app_handle: &AppHandle) {
         *self.state.write().await = state.clone();
         let _ = app_handle.emit("sync-state-changed", state);
@@ -155,6 +472,9 @@ impl SyncManager {
                 last_synced: None,
                 error: None,
                 sync_mode: Some(settings.sync_mode.clone()),
+                next_retry_at: None,
+                progress: None,
+                note: None,
             };
             *self.state.write().await = new_state.clone();
             let _ = app_handle.emit("sync-state-changed", new_state);
@@ -172,16 +492,41 @@ impl SyncManager {
         
         let running = self.running.clone();
         let state = self.state.clone();
-        let client = self.client.clone();
         let sync_mode = settings.sync_mode.clone();
+        let generation = self.generation.clone();
+        let loop_generation = generation.load(Ordering::SeqCst);
+        let wake = self.wake.clone();
+        let local_change = self.local_change.clone();
+        let long_poll_enabled = settings.long_poll;
+        let interval_secs = self.interval_secs.clone();
+        interval_secs.store(settings.clamped_sync_interval_secs(), Ordering::SeqCst);
+        let max_backoff = Duration::from_secs(settings.max_backoff_secs);
+        let cycle_lock = self.cycle_lock.clone();
+        let sync_on_metered = settings.sync_on_metered;
+        let paused = self.paused.clone();
+        paused.store(false, Ordering::SeqCst);
+        let errors = self.errors.clone();
         
         tokio::spawn(async move {
-            let base_url = normalize_url(&settings.sync_url);
-            let db_url = format!("{}/{}", base_url, settings.sync_db_name);
-            let auth = if !settings.sync_username.is_empty() && !settings.sync_password.is_empty() {
-                Some((settings.sync_username.clone(), settings.sync_password.clone()))
-            } else {
-                None
+            let remote = match Remote::from_settings(&settings) {
+                Ok(remote) => remote,
+                Err(e) => {
+                    log::error!(db_url:% = redact_url(&settings.sync_url), status = "error"; "Sync not started: {}", e);
+                    record_error(&errors, &e).await;
+                    let new_state = SyncState {
+                        status: SyncStatus::Error,
+                        last_synced: None,
+                        error: Some(e),
+                        sync_mode: Some(sync_mode.clone()),
+                        next_retry_at: None,
+                        progress: None,
+                        note: None,
+                    };
+                    *state.write().await = new_state.clone();
+                    let _ = app_handle.emit("sync-state-changed", new_state);
+                    *running.write().await = false;
+                    return;
+                }
             };
             
             // Update state to connecting
@@ -191,92 +536,501 @@ impl SyncManager {
                     last_synced: None,
                     error: None,
                     sync_mode: Some(sync_mode.clone()),
+                    next_retry_at: None,
+                    progress: None,
+                    note: None,
                 };
                 *state.write().await = new_state.clone();
                 let _ = app_handle.emit("sync-state-changed", new_state);
             }
             
-            // Ensure remote database exists
-            if let Err(e) = ensure_db_exists(&client, &db_url, auth.as_ref()).await {
-                let new_state = SyncState {
-                    status: SyncStatus::Error,
-                    last_synced: None,
-                    error: Some(e),
-                    sync_mode: Some(sync_mode.clone()),
-                };
-                *state.write().await = new_state.clone();
-                let _ = app_handle.emit("sync-state-changed", new_state);
-                *running.write().await = false;
-                return;
-            }
+            log::info!(db_url:% = remote.log_url, status = "connecting"; "Starting sync");
+            
+            // Consecutive failed cycles, drives the retry backoff
+            let mut failures: u32 = 0;
+            let mut connectivity = Connectivity::default();
+            // Whether the server was checked and the database set up
+            let mut connected = false;
             
             // Main sync loop
             loop {
-                if !*running.read().await {
+                if !*running.read().await || generation.load(Ordering::SeqCst) != loop_generation {
                     break;
                 }
                 
-                // Update state to syncing
-                {
+                if paused.load(Ordering::SeqCst) {
+                    // A cycle that was already underway when pausing may have overwritten the state
+                    let last_synced = state.read().await.last_synced;
+                    let new_state = SyncState {
+                        status: SyncStatus::Paused,
+                        last_synced,
+                        error: None,
+                        sync_mode: Some(sync_mode.clone()),
+                        next_retry_at: None,
+                        progress: None,
+                        note: None,
+                    };
+                    *state.write().await = new_state.clone();
+                    let _ = app_handle.emit("sync-state-changed", new_state);
+                    wake.notified().await;
+                    continue;
+                }
+                
+                // Checked every iteration so moving to another network takes effect without a restart
+                if !sync_on_metered && metered::is_metered().await {
+                    let last_synced = state.read().await.last_synced;
+                    let new_state = SyncState {
+                        status: SyncStatus::Paused,
+                        last_synced,
+                        error: None,
+                        sync_mode: Some(sync_mode.clone()),
+                        next_retry_at: None,
+                        progress: None,
+                        note: Some("Waiting for an unmetered connection".to_string()),
+                    };
+                    *state.write().await = new_state.clone();
+                    let _ = app_handle.emit("sync-state-changed", new_state);
+                    tokio::select! {
+                        _ = sleep(METERED_RECHECK) => {}
+                        _ = wake.notified() => {}
+                    }
+                    continue;
+                }
+                
+                // Manual mode never parks on the changes feed, it waits for a trigger instead
+                let manual = interval_secs.load(Ordering::SeqCst) == 0;
+                let long_poll = long_poll_enabled && !manual;
+                
+                // Stays offline until a cycle gets through, rather than flickering every retry
+                if !connectivity.is_offline() {
                     let last_synced = state.read().await.last_synced;
                     let new_state = SyncState {
                         status: SyncStatus::Syncing,
                         last_synced,
                         error: None,
                         sync_mode: Some(sync_mode.clone()),
+                        next_retry_at: None,
+                        progress: None,
+                        note: None,
                     };
                     *state.write().await = new_state.clone();
                     let _ = app_handle.emit("sync-state-changed", new_state);
                 }
                 
-                // Perform sync cycle
-                match sync_cycle(&client, &db_url, auth.as_ref(), &db).await {
-                    Ok(_) => {
-                        let now = chrono::Utc::now().timestamp_millis();
+                // Perform sync cycle. In long-poll mode the pull blocks until the server
+                // has changes, so stop requests and local edits have to be able to cut it short.
+                let interrupt = async {
+                    tokio::select! {
+                        _ = wake.notified() => {}
+                        _ = local_change.notified(), if long_poll => {}
+                    }
+                };
+                let result = {
+                    let _cycle = cycle_lock.lock().await;
+                    // Make sure this is a CouchDB server and the remote database exists.
+                    // Done here so starting offline backs off and retries like any failed cycle.
+                    let connect = if connected { Ok(()) } else { remote.connect().await };
+                    match connect {
+                        Ok(()) => {
+                            connected = true;
+                            sync_cycle(&remote, &db, long_poll, &state, &app_handle, interrupt).await
+                        }
+                        Err(e) => Err(e),
+                    }
+                };
+                
+                let was_offline = connectivity.is_offline();
+                if connectivity.record(result.as_ref().map(|_| ()).map_err(String::as_str)) {
+                    log::info!(db_url:% = remote.log_url, status = "syncing"; "Sync server reachable again");
+                    let _ = app_handle.emit("sync-reconnected", ());
+                }
+                
+                let delay = match result {
+                    Ok(summary) => {
+                        failures = 0;
+                        log::debug!(db_url:% = remote.log_url, status = "synced", pulled = summary.applied; "Sync cycle finished");
+                        finish_cycle(&db, summary, &sync_mode, &state, &app_handle).await;
+                        // A long-poll already waited for changes, so go straight back
+                        if long_poll { Some(Duration::ZERO) } else { cycle_interval(&interval_secs) }
+                    }
+                    Err(e) if e == TOKEN_EXPIRED => {
+                        log::error!(db_url:% = remote.log_url, status = "stopped"; "Sync stopped: {}", e);
+                        record_error(&errors, &e).await;
                         let new_state = SyncState {
-                            status: SyncStatus::Paused,
-                            last_synced: Some(now),
-                            error: None,
+                            status: SyncStatus::Error,
+                            last_synced: state.read().await.last_synced,
+                            error: Some(e),
                             sync_mode: Some(sync_mode.clone()),
+                            next_retry_at: None,
+                            progress: None,
+                            note: None,
                         };
                         *state.write().await = new_state.clone();
                         let _ = app_handle.emit("sync-state-changed", new_state);
-                        let _ = app_handle.emit("tasks-changed", ());
+                        *running.write().await = false;
+                        break;
                     }
                     Err(e) => {
-                        eprintln!("[sync] error: {}", e);
+                        // Only the failure that goes offline is reported; the rest are expected
+                        let quiet = was_offline && connectivity.is_offline();
+                        if quiet {
+                            log::debug!(db_url:% = remote.log_url, status = "offline", failures; "Sync cycle failed: {}", e);
+                        } else {
+                            log::warn!(db_url:% = remote.log_url, status = "error", failures; "Sync cycle failed: {}", e);
+                            record_error(&errors, &e).await;
+                        }
+                        // Manual mode doesn't retry on its own either
+                        let delay = cycle_interval(&interval_secs)
+                            .map(|_| backoff_delay(failures, max_backoff));
+                        failures = failures.saturating_add(1);
+                        let retry_at = delay
+                            .map(|delay| chrono::Utc::now().timestamp_millis() + delay.as_millis() as i64);
                         let new_state = SyncState {
-                            status: SyncStatus::Error,
+                            status: if connectivity.is_offline() { SyncStatus::Offline } else { SyncStatus::Error },
                             last_synced: state.read().await.last_synced,
                             error: Some(e),
                             sync_mode: Some(sync_mode.clone()),
+                            next_retry_at: retry_at,
+                            progress: None,
+                            note: None,
                         };
                         *state.write().await = new_state.clone();
-                        let _ = app_handle.emit("sync-state-changed", new_state);
+                        if !quiet {
+                            let _ = app_handle.emit("sync-state-changed", new_state);
+                        }
+                        delay
                     }
+                };
+                
+                // Paused mid-cycle, park straight away rather than after the delay
+                if paused.load(Ordering::SeqCst) {
+                    continue;
                 }
                 
-                // Wait before next sync
-                sleep(Duration::from_secs(5)).await;
+                // Wait before next sync, waking early if sync is stopped or triggered
+                match delay {
+                    Some(delay) => tokio::select! {
+                        _ = sleep(delay) => {}
+                        _ = wake.notified() => {}
+                    },
+                    None => wake.notified().await,
+                }
             }
         });
     }
     
+    /// Skip cycles until `resume`, keeping the loop and its connection setup alive
+    pub async fn pause(&self, app_handle: &AppHandle) -> Result<(), String> {
+        if !*self.running.read().await {
+            return Err("Sync is not running".to_string());
+        }
+        
+        self.paused.store(true, Ordering::SeqCst);
+        // Cut a long-poll or the wait between cycles short so the loop parks now
+        self.wake.notify_waiters();
+        
+        let current_state = self.state.read().await;
+        let new_state = SyncState {
+            status: SyncStatus::Paused,
+            last_synced: current_state.last_synced,
+            error: None,
+            sync_mode: current_state.sync_mode.clone(),
+            next_retry_at: None,
+            progress: None,
+            note: None,
+        };
+        drop(current_state);
+        self.set_state(new_state, app_handle).await;
+        Ok(())
+    }
+    
+    /// Undo `pause` and run a cycle straight away
+    pub async fn resume(&self) -> Result<(), String> {
+        if !*self.running.read().await {
+            return Err("Sync is not running".to_string());
+        }
+        
+        if self.paused.swap(false, Ordering::SeqCst) {
+            self.wake.notify_one();
+        }
+        Ok(())
+    }
+    
     pub async fn stop_sync(&self, app_handle: &AppHandle) {
         *self.running.write().await = false;
+        self.paused.store(false, Ordering::SeqCst);
+        
+        // End the current loop (and its backoff) right away
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        self.wake.notify_waiters();
         
         let current_state = self.state.read().await;
         let new_state = SyncState {
-            status: SyncStatus::Paused,
+            status: SyncStatus::Stopped,
             last_synced: current_state.last_synced,
             error: None,
             sync_mode: current_state.sync_mode.clone(),
+            next_retry_at: None,
+            progress: None,
+            note: None,
         };
         drop(current_state);
         self.set_state(new_state, app_handle).await;
     }
 }
 
+/// Where and how to reach the sync server
+struct Remote {
+    client: Client,
+    base_url: String,
+    db_url: String,
+    auth: Option<Auth>,
+    proxy: Option<String>,
+    /// `design/name` filter for the changes feed
+    filter: Option<String>,
+    conflict_strategy: ConflictStrategy,
+    /// Changes per `_changes` page
+    pull_batch_size: usize,
+    /// `db_url` without credentials, for logs
+    log_url: String,
+}
+
+impl Remote {
+    fn from_settings(settings: &SyncSettings) -> Result<Self, String> {
+        let base_url = normalize_url(&settings.sync_url);
+        Ok(Self {
+            auth: Auth::from_settings(settings)?,
+            client: build_client(settings)?,
+            db_url: format!("{}/{}", base_url, settings.sync_db_name),
+            log_url: redact_url(&format!("{}/{}", base_url, settings.sync_db_name)),
+            base_url,
+            proxy: settings.proxy_url.clone(),
+            filter: settings.sync_filter().map(str::to_string),
+            conflict_strategy: settings.conflict_strategy,
+            pull_batch_size: settings.pull_batch_size.max(1),
+        })
+    }
+    
+    /// Check that a CouchDB server answers at the base URL, returning its version
+    async fn probe(&self) -> Result<String, String> {
+        let req = self.client.get(&self.base_url).timeout(PROBE_TIMEOUT);
+        let resp = send(req, self.auth.as_ref(), self.proxy.as_deref(), NOT_COUCHDB).await?;
+        
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            return Err(AUTH_FAILED.to_string());
+        }
+        
+        match resp.json::<WelcomeResponse>().await {
+            Ok(welcome) if welcome.couchdb == "Welcome" => Ok(welcome.version.unwrap_or_default()),
+            _ => Err(format!("{}: unexpected response from {}", NOT_COUCHDB, self.base_url)),
+        }
+    }
+    
+    /// `GET /`, then `HEAD` on the database with the configured auth. Stops at the
+    /// first step that fails, leaving the later flags false.
+    async fn check_credentials(&self) -> CredentialCheck {
+        let mut check = CredentialCheck::default();
+        
+        let req = self.client.get(&self.base_url).timeout(PROBE_TIMEOUT);
+        let resp = match send(req, self.auth.as_ref(), self.proxy.as_deref(), NOT_COUCHDB).await {
+            Ok(resp) => resp,
+            // A rejected cloud token still means the server answered
+            Err(e) if e == TOKEN_EXPIRED => {
+                check.reachable = true;
+                check.error = Some(e);
+                return check;
+            }
+            Err(e) => {
+                check.error = Some(e);
+                return check;
+            }
+        };
+        
+        // Servers with `require_valid_user` turn away the welcome request too
+        if resp.status() == StatusCode::UNAUTHORIZED {
+            check.reachable = true;
+            check.error = Some(AUTH_FAILED.to_string());
+            return check;
+        }
+        match resp.json::<WelcomeResponse>().await {
+            Ok(welcome) if welcome.couchdb == "Welcome" => {
+                check.reachable = true;
+                check.version = welcome.version;
+            }
+            _ => {
+                check.error = Some(format!("{}: unexpected response from {}", NOT_COUCHDB, self.base_url));
+                return check;
+            }
+        }
+        
+        let req = self.client.head(&self.db_url);
+        let resp = match send(req, self.auth.as_ref(), self.proxy.as_deref(), "Database lookup failed").await {
+            Ok(resp) => resp,
+            Err(e) => {
+                check.error = Some(e);
+                return check;
+            }
+        };
+        
+        match resp.status() {
+            status if status.is_success() => {
+                check.authenticated = true;
+                check.db_exists = true;
+            }
+            StatusCode::UNAUTHORIZED => {
+                check.error = Some(AUTH_FAILED.to_string());
+            }
+            StatusCode::FORBIDDEN => {
+                check.authenticated = true;
+                check.error = Some(format!("These credentials aren't allowed to access {}", self.log_url));
+            }
+            StatusCode::NOT_FOUND => {
+                check.authenticated = true;
+                check.error = Some(format!("Database {} doesn't exist yet; starting sync creates it", self.log_url));
+            }
+            status => {
+                check.error = Some(format!("Unexpected response {} from {}", status, self.log_url));
+            }
+        }
+        check
+    }
+    
+    /// Probe the server, then make sure the sync database and filter exist
+    async fn connect(&self) -> Result<(), String> {
+        self.probe().await?;
+        ensure_db_exists(&self.client, &self.db_url, self.auth.as_ref(), self.proxy.as_deref()).await?;
+        match &self.filter {
+            Some(filter) => self.ensure_filter_exists(filter).await,
+            None => Ok(()),
+        }
+    }
+    
+    /// Create the filter's design document with `TASK_FILTER_SOURCE` when the
+    /// server doesn't have it. An existing design document is left as it is.
+    async fn ensure_filter_exists(&self, filter: &str) -> Result<(), String> {
+        let (design, name) = split_filter(filter)
+            .ok_or_else(|| format!("Invalid sync filter '{}'", filter))?;
+        let design_url = format!("{}/_design/{}", self.db_url, design);
+        
+        let req = self.client.get(&design_url);
+        let resp = send(req, self.auth.as_ref(), self.proxy.as_deref(), "Filter lookup failed").await?;
+        if resp.status().is_success() {
+            return Ok(());
+        }
+        if resp.status() != StatusCode::NOT_FOUND {
+            let text = resp.text().await.unwrap_or_default();
+            return Err(format!("Failed to look up sync filter: {}", text));
+        }
+        
+        let body = serde_json::json!({ "filters": { name: TASK_FILTER_SOURCE } });
+        let req = self.client.put(&design_url).json(&body);
+        let resp = send(req, self.auth.as_ref(), self.proxy.as_deref(), "Filter upload failed").await?;
+        
+        // 409 = another device created it first
+        if resp.status().is_success() || resp.status() == StatusCode::CONFLICT {
+            Ok(())
+        } else {
+            let text = resp.text().await.unwrap_or_default();
+            Err(format!("Failed to create sync filter: {}", text))
+        }
+    }
+}
+
+/// Push local changes, then pull remote ones. `interrupt` resolving ends the
+/// pull at the page being fetched; pages already applied are still reported, and
+/// the rest is picked up next cycle from the saved `last_seq`.
+async fn sync_cycle(
+    remote: &Remote,
+    db: &Database,
+    long_poll: bool,
+    state: &RwLock<SyncState>,
+    app_handle: &AppHandle,
+    interrupt: impl Future<Output = ()>,
+) -> Result<PullSummary, String> {
+    let (client, db_url) = (&remote.client, remote.db_url.as_str());
+    let (auth, proxy) = (remote.auth.as_ref(), remote.proxy.as_deref());
+    
+    let mut progress = Progress::new(state, app_handle);
+    push_changes(client, db_url, auth, proxy, db, &mut progress).await?;
+    
+    let mut progress = Progress::new(state, app_handle);
+    pull_changes(remote, db, long_poll, &mut progress, interrupt).await
+}
+
+/// Publish a successful cycle and tidy up after it
+async fn finish_cycle(
+    db: &Database,
+    summary: PullSummary,
+    sync_mode: &str,
+    state: &RwLock<SyncState>,
+    app_handle: &AppHandle,
+) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let new_state = SyncState {
+        status: SyncStatus::Synced,
+        last_synced: Some(now),
+        error: None,
+        sync_mode: Some(sync_mode.to_string()),
+        next_retry_at: None,
+        progress: None,
+        note: None,
+    };
+    *state.write().await = new_state.clone();
+    let _ = app_handle.emit("sync-state-changed", new_state);
+    // One event per kind for the whole pull, so the frontend can patch in place
+    if !summary.upserted.is_empty() {
+        let _ = app_handle.emit("tasks-upserted", &summary.upserted);
+    }
+    if !summary.deleted.is_empty() {
+        let _ = app_handle.emit("tasks-deleted", &summary.deleted);
+    }
+    // Coarse fallback, and only when something arrived since it also wakes the sync loop
+    if summary.applied > 0 {
+        let _ = app_handle.emit("tasks-changed", ());
+    }
+    if summary.conflicts > 0 {
+        if let Ok(conflicts) = db.get_conflicts() {
+            let _ = app_handle.emit("sync-conflict", conflicts.len());
+        }
+    }
+    match db.purge_deleted_older_than(TOMBSTONE_RETENTION.as_millis() as i64) {
+        Ok(0) => {}
+        Ok(purged) => log::info!("Purged {} old tombstone(s)", purged),
+        Err(e) => log::warn!("Tombstone purge failed: {}", e),
+    }
+}
+
+/// Tells being offline apart from errors the server sent back, by counting
+/// cycles in a row whose requests got no response at all
+#[derive(Default)]
+struct Connectivity {
+    unreachable: u32,
+}
+
+impl Connectivity {
+    fn is_offline(&self) -> bool {
+        self.unreachable >= OFFLINE_THRESHOLD
+    }
+    
+    /// Record a cycle's outcome; true when it ended an offline spell
+    fn record(&mut self, result: Result<(), &str>) -> bool {
+        let was_offline = self.is_offline();
+        match result {
+            Err(e) if is_unreachable(e) => self.unreachable = self.unreachable.saturating_add(1),
+            _ => self.unreachable = 0,
+        }
+        was_offline && !self.is_offline()
+    }
+}
+
+/// Delay until the next cycle, or `None` in manual mode
+fn cycle_interval(interval_secs: &AtomicU64) -> Option<Duration> {
+    match interval_secs.load(Ordering::SeqCst) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
 fn normalize_url(url: &str) -> String {
     if url.starts_with("http://") || url.starts_with("https://") {
         url.to_string()
@@ -285,17 +1039,212 @@ fn normalize_url(url: &str) -> String {
     }
 }
 
+/// A server URL safe to log, with any credentials in it masked
+fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(&normalize_url(url)) {
+        Ok(mut parsed) => {
+            if parsed.password().is_some() {
+                let _ = parsed.set_password(Some("***"));
+            }
+            if !parsed.username().is_empty() {
+                let _ = parsed.set_username("***");
+            }
+            parsed.to_string()
+        }
+        Err(_) => "(invalid URL)".to_string(),
+    }
+}
+
+/// Publishes `SyncState::progress` while a push or pull works through its documents
+struct Progress<'a> {
+    state: &'a RwLock<SyncState>,
+    /// `None` when nothing listens, e.g. in tests
+    app_handle: Option<&'a AppHandle>,
+    last_emit: Option<Instant>,
+}
+
+impl<'a> Progress<'a> {
+    fn new(state: &'a RwLock<SyncState>, app_handle: &'a AppHandle) -> Self {
+        Self { state, app_handle: Some(app_handle), last_emit: None }
+    }
+    
+    /// Record progress, emitting `sync-state-changed` at most every
+    /// `PROGRESS_INTERVAL` apart from the first and final updates
+    async fn report(&mut self, done: usize, total: usize) {
+        let due = self.last_emit.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL);
+        if total == 0 || (!due && done < total) {
+            return;
+        }
+        
+        let snapshot = {
+            let mut state = self.state.write().await;
+            state.progress = Some((done, total));
+            state.clone()
+        };
+        if let Some(app_handle) = self.app_handle {
+            let _ = app_handle.emit("sync-state-changed", snapshot);
+        }
+        self.last_emit = Some(Instant::now());
+    }
+}
+
+/// HTTP client for the sync server, trusting the configured CA and presenting
+/// the client certificate when mutual TLS is set up
+fn build_client(settings: &SyncSettings) -> Result<Client, String> {
+    // Sends `Accept-Encoding: gzip` and transparently decompresses responses
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .gzip(true);
+    
+    if let Some(path) = &settings.ca_cert_path {
+        let pem = read_pem(path, "CA certificate")?;
+        let cert = Certificate::from_pem(&pem)
+            .map_err(|e| format!("Invalid CA certificate {}: {}", path, e))?;
+        builder = builder.add_root_certificate(cert);
+    }
+    
+    match (&settings.client_cert_path, &settings.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            // reqwest wants the certificate and key in a single PEM bundle
+            let mut pem = read_pem(cert_path, "client certificate")?;
+            pem.push(b'\n');
+            pem.extend(read_pem(key_path, "client key")?);
+            let identity = Identity::from_pem(&pem)
+                .map_err(|e| format!("Invalid client certificate {} or key {}: {}", cert_path, key_path, e))?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => return Err("Client certificate and client key must be set together".to_string()),
+    }
+    
+    // An explicit proxy replaces the environment's; without one reqwest reads
+    // HTTP_PROXY/HTTPS_PROXY/NO_PROXY itself
+    if let Some(proxy_url) = &settings.proxy_url {
+        let scheme = proxy_url.split("://").next().unwrap_or_default();
+        if !matches!(scheme, "http" | "https" | "socks5" | "socks5h") {
+            return Err(format!("Unsupported proxy {}: use an http://, https:// or socks5:// URL", proxy_url));
+        }
+        let proxy = Proxy::all(proxy_url.as_str())
+            .map_err(|e| format!("Invalid proxy {}: {}", proxy_url, e))?;
+        builder = builder.proxy(proxy);
+    }
+    
+    if settings.accept_invalid_certs {
+        log::warn!("Server certificate verification is disabled");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    
+    builder.build().map_err(|e| format!("Failed to create HTTP client: {}", e))
+}
+
+fn read_pem(path: &str, what: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|e| format!("Failed to read {} {}: {}", what, path, e))
+}
+
+/// Credentials sent with every request to the sync server
+enum Auth {
+    Basic { username: String, password: String },
+    /// Taskly Cloud API token
+    Bearer(String),
+}
+
+impl Auth {
+    fn from_settings(settings: &SyncSettings) -> Result<Option<Self>, String> {
+        if settings.sync_mode == "cloud" {
+            return match settings.auth_token.as_deref() {
+                Some(token) if !token.is_empty() => Ok(Some(Auth::Bearer(token.to_string()))),
+                _ => Err("Taskly Cloud sync needs an auth token".to_string()),
+            };
+        }
+        
+        if settings.sync_username.is_empty() || settings.sync_password.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Auth::Basic {
+            username: settings.sync_username.clone(),
+            password: settings.sync_password.clone(),
+        }))
+    }
+}
+
+/// Returned when the server rejects a cloud token; retrying can't fix it
+const TOKEN_EXPIRED: &str = "Taskly Cloud token expired or was revoked. Sign in again to resume sync.";
+
+/// How long the welcome probe waits before calling the server unreachable
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NOT_COUCHDB: &str = "Not a CouchDB server or unreachable";
+
+const AUTH_FAILED: &str = "Authentication failed: check the username and password";
+
+/// Body of CouchDB's `GET /`
+#[derive(Deserialize, Debug)]
+struct WelcomeResponse {
+    couchdb: String,
+    version: Option<String>,
+}
+
+/// Probe the server in `settings` without syncing, for the "Test connection"
+/// button. Returns the CouchDB version.
+pub async fn test_connection(settings: &SyncSettings) -> Result<String, String> {
+    Remote::from_settings(settings)?.probe().await
+}
+
+/// Check the server in `settings` answers, accepts the credentials and has the
+/// sync database, without creating anything
+pub async fn test_credentials(settings: &SyncSettings) -> Result<CredentialCheck, String> {
+    Ok(Remote::from_settings(settings)?.check_credentials().await)
+}
+
+/// Attach credentials and send a request to the sync server
+async fn send(
+    req: RequestBuilder,
+    auth: Option<&Auth>,
+    proxy: Option<&str>,
+    context: &str,
+) -> Result<Response, String> {
+    let req = match auth {
+        Some(Auth::Basic { username, password }) => req.basic_auth(username, Some(password)),
+        Some(Auth::Bearer(token)) => req.bearer_auth(token),
+        None => req,
+    };
+    
+    let resp = req.send().await.map_err(|e| request_error(context, e, proxy))?;
+    
+    if resp.status() == StatusCode::UNAUTHORIZED && matches!(auth, Some(Auth::Bearer(_))) {
+        return Err(TOKEN_EXPIRED.to_string());
+    }
+    
+    Ok(resp)
+}
+
+/// Part of the message for a request that couldn't connect or timed out,
+/// as opposed to one the server answered with an error
+const UNREACHABLE: &str = "server unreachable";
+
+/// Whether `error` came from a request that got no response, see `UNREACHABLE`
+fn is_unreachable(error: &str) -> bool {
+    error.contains(UNREACHABLE)
+}
+
+/// Describe a request that got no response. With a proxy configured, a failed
+/// connection means the proxy itself couldn't be reached or refused the tunnel.
+fn request_error(context: &str, e: reqwest::Error, proxy: Option<&str>) -> String {
+    match proxy {
+        Some(proxy) if e.is_connect() => format!("{}: proxy error ({}), {}: {}", context, proxy, UNREACHABLE, e),
+        _ if e.is_connect() || e.is_timeout() => format!("{}: {}: {}", context, UNREACHABLE, e),
+        _ => format!("{}: {}", context, e),
+    }
+}
+
 async fn ensure_db_exists(
     client: &Client,
     db_url: &str,
-    auth: Option<&(String, String)>,
+    auth: Option<&Auth>,
+    proxy: Option<&str>,
 ) -> Result<(), String> {
-    let mut req = client.put(db_url);
-    if let Some((user, pass)) = auth {
-        req = req.basic_auth(user, Some(pass));
-    }
-    
-    let resp = req.send().await.map_err(|e| format!("Connection failed: {}", e))?;
+    let req = client.put(db_url);
+    let resp = send(req, auth, proxy, "Connection failed").await?;
     
     // 201 = created, 412 = already exists - both are fine
     if resp.status().is_success() || resp.status().as_u16() == 412 {
@@ -306,74 +1255,284 @@ async fn ensure_db_exists(
     }
 }
 
-async fn sync_cycle(
+/// Current revision of each of `ids` on the server; missing documents are left out
+async fn fetch_remote_revs(
     client: &Client,
     db_url: &str,
-    auth: Option<&(String, String)>,
-    db: &Database,
-) -> Result<(), String> {
-    // 1. Push local changes to remote
-    push_changes(client, db_url, auth, db).await?;
+    auth: Option<&Auth>,
+    proxy: Option<&str>,
+    ids: &[&str],
+) -> Result<HashMap<String, String>, String> {
+    let req = client
+        .post(format!("{}/_all_docs", db_url))
+        .json(&serde_json::json!({ "keys": ids }));
+    let resp = send(req, auth, proxy, "Request failed").await?;
     
-    // 2. Pull remote changes to local
-    pull_changes(client, db_url, auth, db).await?;
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Failed to fetch remote revisions: {}", text));
+    }
     
-    Ok(())
+    let all_docs: AllDocsResponse = resp.json().await.map_err(|e| format!("Parse error: {}", e))?;
+    Ok(all_docs.rows
+        .into_iter()
+        .filter_map(|row| row.value.map(|value| (row.key, value.rev)))
+        .collect())
+}
+
+/// A document as the server currently has it
+struct RemoteDoc {
+    rev: String,
+    deleted: bool,
+    /// `None` when deleted
+    doc: Option<serde_json::Value>,
+}
+
+/// Current revision and content of each of `ids` on the server; missing
+/// documents are left out
+async fn fetch_remote_docs(
+    client: &Client,
+    db_url: &str,
+    auth: Option<&Auth>,
+    proxy: Option<&str>,
+    ids: &[&str],
+) -> Result<HashMap<String, RemoteDoc>, String> {
+    let req = client
+        .post(format!("{}/_all_docs?include_docs=true", db_url))
+        .json(&serde_json::json!({ "keys": ids }));
+    let resp = send(req, auth, proxy, "Request failed").await?;
+    
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Failed to fetch remote documents: {}", text));
+    }
+    
+    let all_docs: AllDocsResponse = resp.json().await.map_err(|e| format!("Parse error: {}", e))?;
+    Ok(all_docs.rows
+        .into_iter()
+        .filter_map(|row| {
+            let value = row.value?;
+            Some((row.key, RemoteDoc { rev: value.rev, deleted: value.deleted, doc: row.doc }))
+        })
+        .collect())
+}
+
+/// Whether `remote` holds what `doc` would write, ignoring revisions and other
+/// underscore fields the server adds
+fn same_content(doc: &CouchDoc, remote: &RemoteDoc) -> bool {
+    if doc.deleted.unwrap_or(false) || remote.deleted {
+        return doc.deleted.unwrap_or(false) && remote.deleted;
+    }
+    let strip = |value: serde_json::Value| match value {
+        serde_json::Value::Object(fields) => fields.into_iter()
+            .filter(|(key, _)| !key.starts_with('_'))
+            .collect::<serde_json::Map<_, _>>(),
+        _ => serde_json::Map::new(),
+    };
+    match (serde_json::to_value(doc), &remote.doc) {
+        (Ok(ours), Some(theirs)) => strip(ours) == strip(theirs.clone()),
+        _ => false,
+    }
+}
+
+fn gzip(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)
+        .and_then(|_| encoder.finish())
+        .map_err(|e| format!("Failed to compress request: {}", e))
+}
+
+/// Upload documents in one `_bulk_docs` request, returning the per-document results
+async fn bulk_push(
+    client: &Client,
+    db_url: &str,
+    auth: Option<&Auth>,
+    proxy: Option<&str>,
+    docs: &[CouchDoc],
+) -> Result<Vec<CouchResponse>, String> {
+    let url = format!("{}/_bulk_docs", db_url);
+    let body = serde_json::to_vec(&BulkDocsRequest { docs })
+        .map_err(|e| format!("Failed to serialize documents: {}", e))?;
+    let compressed = gzip(&body)?;
+    if cfg!(debug_assertions) {
+        log::debug!(
+            "Push body {} -> {} bytes gzipped ({} saved)",
+            body.len(),
+            compressed.len(),
+            body.len().saturating_sub(compressed.len())
+        );
+    }
+    
+    let req = client
+        .post(&url)
+        .header(CONTENT_TYPE, "application/json")
+        .header(CONTENT_ENCODING, "gzip")
+        .body(compressed);
+    let mut resp = send(req, auth, proxy, "Bulk push failed").await?;
+    
+    // Not every server, or proxy in front of it, accepts compressed request bodies
+    if resp.status() == StatusCode::UNSUPPORTED_MEDIA_TYPE {
+        let req = client
+            .post(&url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body);
+        resp = send(req, auth, proxy, "Bulk push failed").await?;
+    }
+    
+    if !resp.status().is_success() {
+        let text = resp.text().await.unwrap_or_default();
+        return Err(format!("Bulk push failed: {}", text));
+    }
+    
+    resp.json().await.map_err(|e| format!("Parse error: {}", e))
 }
 
 async fn push_changes(
     client: &Client,
     db_url: &str,
-    auth: Option<&(String, String)>,
+    auth: Option<&Auth>,
+    proxy: Option<&str>,
     db: &Database,
+    progress: &mut Progress<'_>,
 ) -> Result<(), String> {
-    // Get local tasks that have been modified
-    let tasks = db.get_all_tasks().map_err(|e| format!("DB error: {}", e))?;
+    // Everything edited locally that the server hasn't acknowledged yet
+    let tasks = db.get_dirty_tasks().map_err(|e| format!("DB error: {}", e))?;
+    let projects = db.get_dirty_projects().map_err(|e| format!("DB error: {}", e))?;
     
-    for task in tasks {
-        // Check if document exists on remote
-        let doc_url = format!("{}/{}", db_url, task.id);
-        let mut req = client.get(&doc_url);
-        if let Some((user, pass)) = auth {
-            req = req.basic_auth(user, Some(pass));
-        }
-        
-        let resp = req.send().await.map_err(|e| format!("Request failed: {}", e))?;
-        
-        let remote_rev = if resp.status().is_success() {
-            let remote_doc: CouchDoc = resp.json().await.map_err(|e| format!("Parse error: {}", e))?;
-            Some(remote_doc.rev.unwrap_or_default())
-        } else {
-            None
-        };
-        
-        // Prepare document for upload
-        let couch_doc = CouchDoc {
-            id: task.id.clone(),
-            rev: remote_rev,
-            task: TaskData {
+    if tasks.is_empty() && projects.is_empty() {
+        return Ok(());
+    }
+    
+    // Look up the current remote revision of every document in one request
+    let ids: Vec<&str> = tasks.iter().map(|t| t.id.as_str())
+        .chain(projects.iter().map(|p| p.id.as_str()))
+        .collect();
+    let remote_revs = fetch_remote_revs(client, db_url, auth, proxy, &ids).await?;
+    
+    // Prepare documents for upload, remembering which ids are projects
+    let project_updated_at: HashMap<String, i64> = projects.iter().map(|p| (p.id.clone(), p.updated_at)).collect();
+    let task_updated_at: HashMap<String, i64> = tasks.iter().map(|t| (t.id.clone(), t.updated_at)).collect();
+    let mut docs: Vec<CouchDoc> = tasks
+        .into_iter()
+        .map(|task| CouchDoc {
+            rev: remote_revs.get(&task.id).cloned(),
+            id: task.id,
+            body: DocBody::Task(Box::new(TaskData {
                 title: task.title,
                 description: task.description,
                 completed: task.completed,
+                completed_at: task.completed_at,
                 due_date: task.due_date,
                 updated_at: task.updated_at,
-                order: task.order,
-            },
+                created_at: task.created_at,
+                rank: task.rank,
+                order: None,
+                archived: task.archived,
+                tags: task.tags,
+                priority: task.priority,
+                recurrence: task.recurrence,
+                parent_id: task.parent_id,
+                project_id: task.project_id,
+                notes: task.notes,
+                attachments: task.attachments,
+                color: task.color,
+                estimate_minutes: task.estimate_minutes,
+                field_updated_at: task.field_updated_at,
+            })),
             deleted: if task.deleted { Some(true) } else { None },
-        };
+        })
+        .collect();
+    docs.extend(projects.into_iter().map(|project| CouchDoc {
+        rev: remote_revs.get(&project.id).cloned(),
+        id: project.id,
+        body: DocBody::Project(ProjectData {
+            name: project.name,
+            color: project.color,
+            order: project.order,
+            updated_at: project.updated_at,
+        }),
+        deleted: if project.deleted { Some(true) } else { None },
+    }));
+    
+    // POST them in batches, marking each batch clean as soon as it lands
+    let total = docs.len();
+    let mut done = 0;
+    let mut newest_pushed = None;
+    progress.report(done, total).await;
+    for batch in docs.chunks(SYNC_BATCH_SIZE) {
+        let mut results = bulk_push(client, db_url, auth, proxy, batch).await?;
         
-        // PUT the document
-        let mut req = client.put(&doc_url).json(&couch_doc);
-        if let Some((user, pass)) = auth {
-            req = req.basic_auth(user, Some(pass));
+        // A conflict here means the revision looked up above is already stale. When
+        // the server already holds exactly what we sent, e.g. an earlier push landed
+        // but its response was lost, retry once against the server's revision.
+        // Anything else is a real edit from another device and stays dirty for
+        // `pull_changes` to merge.
+        let conflicted: HashSet<&str> = results.iter()
+            .filter(|result| result.error.as_deref() == Some("conflict"))
+            .filter_map(|result| result.id.as_deref())
+            .collect();
+        if !conflicted.is_empty() {
+            let ids: Vec<&str> = conflicted.iter().copied().collect();
+            let current = fetch_remote_docs(client, db_url, auth, proxy, &ids).await?;
+            let retry: Vec<CouchDoc> = batch.iter()
+                .filter(|doc| conflicted.contains(doc.id.as_str()))
+                .filter_map(|doc| {
+                    let remote = current.get(&doc.id)?;
+                    same_content(doc, remote).then(|| CouchDoc { rev: Some(remote.rev.clone()), ..doc.clone() })
+                })
+                .collect();
+            if !retry.is_empty() {
+                let retried = bulk_push(client, db_url, auth, proxy, &retry).await?;
+                let retried_ids: HashSet<&str> = retry.iter().map(|doc| doc.id.as_str()).collect();
+                results.retain(|result| !retried_ids.contains(result.id.as_deref().unwrap_or_default()));
+                results.extend(retried);
+            }
         }
         
-        let resp = req.send().await.map_err(|e| format!("PUT failed: {}", e))?;
+        let mut batch_tasks = Vec::with_capacity(results.len());
+        let mut batch_projects = Vec::new();
+        for result in results {
+            match result.error.as_deref() {
+                None => {
+                    if let Some(id) = result.id {
+                        if let Some(&updated_at) = project_updated_at.get(&id) {
+                            batch_projects.push((id, updated_at));
+                        } else if let Some(&updated_at) = task_updated_at.get(&id) {
+                            // Keep the server's revision so later edits build on it
+                            if let Some(rev) = &result.rev {
+                                db.set_task_rev(&id, rev).map_err(|e| format!("DB error: {}", e))?;
+                            }
+                            batch_tasks.push((id, updated_at));
+                        }
+                    }
+                }
+                // Conflict, handled by pull; the document stays dirty and is retried
+                Some("conflict") => {}
+                Some(error) => {
+                    log::warn!(
+                        "Push error for {}: {} {}",
+                        result.id.unwrap_or_default(),
+                        error,
+                        result.reason.unwrap_or_default()
+                    );
+                }
+            }
+        }
         
-        if !resp.status().is_success() && resp.status().as_u16() != 409 {
-            // 409 = conflict, handled by pull
-            let text = resp.text().await.unwrap_or_default();
-            eprintln!("[sync] push error for {}: {}", task.id, text);
+        newest_pushed = batch_tasks.iter().map(|&(_, updated_at)| updated_at).chain(newest_pushed).max();
+        db.mark_clean(&batch_tasks).map_err(|e| format!("DB error: {}", e))?;
+        db.mark_projects_clean(&batch_projects).map_err(|e| format!("DB error: {}", e))?;
+        
+        done += batch.len();
+        progress.report(done, total).await;
+    }
+    
+    // Recorded once the whole push went through. Only `dirty` picks what's sent,
+    // so a clock that stepped back can't hide an edit behind the checkpoint.
+    if let Some(newest) = newest_pushed {
+        let checkpoint = db.get_push_checkpoint().map_err(|e| format!("DB error: {}", e))?;
+        if newest > checkpoint {
+            db.set_push_checkpoint(newest).map_err(|e| format!("DB error: {}", e))?;
         }
     }
     
@@ -381,58 +1540,434 @@ async fn push_changes(
 }
 
 async fn pull_changes(
-    client: &Client,
-    db_url: &str,
-    auth: Option<&(String, String)>,
+    remote: &Remote,
     db: &Database,
-) -> Result<(), String> {
-    // Get last sync sequence
-    let since = db.get_last_sync_seq()
+    long_poll: bool,
+    progress: &mut Progress<'_>,
+    interrupt: impl Future<Output = ()>,
+) -> Result<PullSummary, String> {
+    let mut since = db.get_last_sync_seq()
         .unwrap_or(None)
         .unwrap_or_else(|| "0".to_string());
     
-    // Fetch changes from CouchDB
-    let changes_url = format!("{}/_changes?include_docs=true&since={}", db_url, since);
-    let mut req = client.get(&changes_url);
-    if let Some((user, pass)) = auth {
-        req = req.basic_auth(user, Some(pass));
+    // Page through the feed so a huge history doesn't arrive in one response.
+    // Only the first page waits for changes; the rest are catching up.
+    let mut summary = PullSummary::default();
+    let mut projects_applied = 0;
+    let mut done = 0;
+    let mut long_poll = long_poll;
+    let mut interrupt = std::pin::pin!(interrupt);
+    loop {
+        // Only the wait for a page is cut short; a page that arrived is applied whole
+        let changes = tokio::select! {
+            changes = fetch_changes(remote, &since, long_poll) => changes?,
+            _ = &mut interrupt => break,
+        };
+        long_poll = false;
+        let received = changes.results.len();
+        let total = done + received + changes.pending.unwrap_or(0);
+        
+        let (tasks, projects) = docs_from_changes(changes.results);
+        progress.report(done, total).await;
+        if !tasks.is_empty() {
+            let applied = db.upsert_batch_from_remote(&tasks, remote.conflict_strategy).map_err(|e| format!("Upsert failed: {}", e))?;
+            summary.conflicts += applied.conflicts;
+            summary.upserted.extend(applied.upserted);
+            summary.deleted.extend(applied.deleted);
+        }
+        db.upsert_projects_from_remote(&projects).map_err(|e| format!("Upsert failed: {}", e))?;
+        projects_applied += projects.len();
+        
+        // Saved after every page, so an interrupted first sync resumes from here
+        db.set_last_sync_seq(&changes.last_seq).map_err(|e| format!("Failed to save seq: {}", e))?;
+        since = changes.last_seq;
+        done += received;
+        progress.report(done, total).await;
+        
+        if received < remote.pull_batch_size {
+            break;
+        }
     }
     
-    let resp = req.send().await.map_err(|e| format!("Changes request failed: {}", e))?;
+    summary.applied = summary.upserted.len() + summary.deleted.len() + projects_applied;
+    Ok(summary)
+}
+
+/// One page of at most `pull_batch_size` changes after `since`
+async fn fetch_changes(remote: &Remote, since: &str, long_poll: bool) -> Result<ChangesResponse, String> {
+    let mut changes_url = format!(
+        "{}/_changes?include_docs=true&since={}&limit={}",
+        remote.db_url, since, remote.pull_batch_size
+    );
+    if long_poll {
+        changes_url.push_str(&format!("&feed=longpoll&timeout={}", LONGPOLL_TIMEOUT.as_millis()));
+    }
+    // The server does the selection; `docs_from_changes` still skips design documents without one
+    if let Some(filter) = &remote.filter {
+        changes_url.push_str(&format!("&filter={}", filter));
+    }
+    let req = remote.client.get(&changes_url);
+    let resp = send(req, remote.auth.as_ref(), remote.proxy.as_deref(), "Changes request failed").await?;
     
     if !resp.status().is_success() {
         let text = resp.text().await.unwrap_or_default();
         return Err(format!("Failed to fetch changes: {}", text));
     }
     
-    let changes: ChangesResponse = resp.json().await.map_err(|e| format!("Parse error: {}", e))?;
-    
-    // Process each change
-    for result in changes.results {
-        if let Some(doc) = result.doc {
-            // Skip design documents
-            if doc.id.starts_with("_design") {
+    resp.json().await.map_err(|e| format!("Parse error: {}", e))
+}
+
+/// Tasks and projects in a page of changes, skipping design documents and
+/// anything unreadable
+fn docs_from_changes(results: Vec<ChangesResult>) -> (Vec<Task>, Vec<Project>) {
+    let mut tasks = Vec::with_capacity(results.len());
+    let mut projects = Vec::new();
+    for result in results {
+        let Some(mut value) = result.doc else {
+            continue;
+        };
+        
+        // Skip design documents
+        if result.id.starts_with("_design") {
+            continue;
+        }
+        
+        // Documents from before projects existed carry no type
+        if let Some(obj) = value.as_object_mut() {
+            obj.entry("type").or_insert_with(|| serde_json::json!("task"));
+        }
+        
+        let doc: CouchDoc = match serde_json::from_value(value) {
+            Ok(doc) => doc,
+            Err(e) => {
+                log::warn!("Skipping unreadable document {}: {}", result.id, e);
                 continue;
             }
-            
-            let task = Task {
+        };
+        let deleted = result.deleted.unwrap_or(false) || doc.deleted.unwrap_or(false);
+        
+        match doc.body {
+            DocBody::Task(task) => tasks.push(Task {
                 id: doc.id,
                 rev: doc.rev,
-                title: doc.task.title,
-                description: doc.task.description,
-                completed: doc.task.completed,
-                due_date: doc.task.due_date,
-                updated_at: doc.task.updated_at,
-                order: doc.task.order,
-                deleted: result.deleted.unwrap_or(false) || doc.deleted.unwrap_or(false),
-            };
-            
-            db.upsert_from_remote(&task).map_err(|e| format!("Upsert failed: {}", e))?;
+                title: task.title,
+                description: task.description,
+                completed: task.completed,
+                completed_at: task.completed_at,
+                due_date: task.due_date,
+                updated_at: task.updated_at,
+                created_at: if task.created_at > 0 { task.created_at } else { task.updated_at },
+                rank: match task.rank.is_empty() {
+                    true => rank::from_order(task.order.unwrap_or_default()),
+                    false => task.rank,
+                },
+                deleted,
+                archived: task.archived,
+                tags: task.tags,
+                priority: task.priority,
+                recurrence: task.recurrence,
+                parent_id: task.parent_id,
+                project_id: task.project_id,
+                notes: task.notes,
+                attachments: task.attachments,
+                color: task.color,
+                estimate_minutes: task.estimate_minutes,
+                field_updated_at: task.field_updated_at,
+            }),
+            DocBody::Project(project) => projects.push(Project {
+                id: doc.id,
+                rev: doc.rev,
+                name: project.name,
+                color: project.color,
+                order: project.order,
+                updated_at: project.updated_at,
+                deleted,
+            }),
         }
     }
     
-    // Update last sync sequence
-    db.set_last_sync_seq(&changes.last_seq).map_err(|e| format!("Failed to save seq: {}", e))?;
+    (tasks, projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::NewTask;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
     
-    Ok(())
+    /// A request the mock server received, with its body decompressed
+    struct Received {
+        path: String,
+        body: serde_json::Value,
+    }
+    
+    type Requests = Arc<std::sync::Mutex<Vec<Received>>>;
+    
+    /// Serve HTTP on a local port, answering each request with `respond`'s status
+    /// and JSON body. Returns the server's URL and every request it received.
+    async fn mock_server<F>(respond: F) -> (String, Requests)
+    where
+        F: Fn(&Received) -> (u16, serde_json::Value) + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Requests = Default::default();
+        let respond = Arc::new(respond);
+        
+        let received = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (received, respond) = (received.clone(), respond.clone());
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut reader = BufReader::new(reader);
+                    loop {
+                        let mut request_line = String::new();
+                        if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let path = request_line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                        
+                        let (mut length, mut gzipped) = (0, false);
+                        loop {
+                            let mut header = String::new();
+                            reader.read_line(&mut header).await.unwrap();
+                            let header = header.trim().to_ascii_lowercase();
+                            if header.is_empty() {
+                                break;
+                            }
+                            if let Some(value) = header.strip_prefix("content-length:") {
+                                length = value.trim().parse().unwrap();
+                            }
+                            gzipped |= header == "content-encoding: gzip";
+                        }
+                        
+                        let mut body = vec![0; length];
+                        reader.read_exact(&mut body).await.unwrap();
+                        if gzipped {
+                            let mut plain = Vec::new();
+                            GzDecoder::new(body.as_slice()).read_to_end(&mut plain).unwrap();
+                            body = plain;
+                        }
+                        let request = Received {
+                            path,
+                            body: serde_json::from_slice(&body).unwrap_or_default(),
+                        };
+                        
+                        let (status, reply) = respond(&request);
+                        received.lock().unwrap().push(request);
+                        let reply = reply.to_string();
+                        let response = format!(
+                            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                            status,
+                            reply.len(),
+                            reply
+                        );
+                        if writer.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        
+        (url, requests)
+    }
+    
+    /// Ids of the documents in a `_bulk_docs` or `_all_docs` request
+    fn request_ids(request: &Received) -> Vec<String> {
+        let ids = match request.body.get("docs") {
+            Some(docs) => docs.as_array().unwrap().iter().map(|doc| doc["_id"].clone()).collect(),
+            None => request.body["keys"].as_array().cloned().unwrap_or_default(),
+        };
+        ids.into_iter().map(|id| id.as_str().unwrap().to_string()).collect()
+    }
+    
+    /// Accept every pushed document as its first revision; nothing exists remotely yet
+    fn empty_server(request: &Received) -> (u16, serde_json::Value) {
+        let ids = request_ids(request);
+        if request.path.contains("_bulk_docs") {
+            let results: Vec<_> = ids.iter().map(|id| serde_json::json!({ "ok": true, "id": id, "rev": "1-a" })).collect();
+            (201, serde_json::json!(results))
+        } else {
+            let rows: Vec<_> = ids.iter().map(|id| serde_json::json!({ "key": id, "error": "not_found" })).collect();
+            (200, serde_json::json!({ "rows": rows }))
+        }
+    }
+    
+    async fn push(db: &Database, url: &str) -> Result<(), String> {
+        let state = RwLock::new(SyncState::default());
+        let mut progress = Progress { state: &state, app_handle: None, last_emit: None };
+        push_changes(&Client::new(), &format!("{}/tasks", url), None, None, db, &mut progress).await
+    }
+    
+    fn add(db: &Database, title: &str) -> Task {
+        db.add_task(NewTask { title: title.to_string(), ..NewTask::default() }).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn push_sends_every_dirty_task_in_two_requests() {
+        let db = Database::new_in_memory().unwrap();
+        for i in 0..25 {
+            add(&db, &format!("task {}", i));
+        }
+        let (url, requests) = mock_server(empty_server).await;
+        
+        push(&db, &url).await.unwrap();
+        
+        let requests = requests.lock().unwrap();
+        let paths: Vec<&str> = requests.iter().map(|request| request.path.as_str()).collect();
+        assert_eq!(paths, ["/tasks/_all_docs", "/tasks/_bulk_docs"]);
+        assert_eq!(request_ids(&requests[1]).len(), 25);
+        assert!(db.get_dirty_tasks().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn push_checkpoint_records_progress_without_hiding_edits() {
+        let db = Database::new_in_memory().unwrap();
+        let newest = (0..3).map(|i| add(&db, &format!("task {}", i)).updated_at).max().unwrap();
+        let (failing, _) = mock_server(|_| (500, serde_json::json!({ "error": "internal" }))).await;
+        
+        assert!(push(&db, &failing).await.is_err());
+        assert_eq!(db.get_push_checkpoint().unwrap(), 0);
+        
+        let (url, requests) = mock_server(empty_server).await;
+        push(&db, &url).await.unwrap();
+        assert_eq!(db.get_push_checkpoint().unwrap(), newest);
+        
+        // As if the clock had stepped back after the last push
+        let ahead = newest + 60 * 60 * 1000;
+        db.set_push_checkpoint(ahead).unwrap();
+        let behind = add(&db, "after the clock change");
+        push(&db, &url).await.unwrap();
+        
+        assert!(request_ids(&requests.lock().unwrap()[3]).contains(&behind.id));
+        assert_eq!(db.get_push_checkpoint().unwrap(), ahead);
+    }
+    
+    /// A server whose copy of every document is at `2-b`, while `_all_docs`
+    /// without docs still reports the stale `1-a`. With `same`, its copy holds
+    /// exactly what the first push sends; otherwise another device edited it.
+    fn stale_rev_server(same: bool) -> impl Fn(&Received) -> (u16, serde_json::Value) + Send + Sync {
+        let first_push = std::sync::Mutex::new(None::<serde_json::Value>);
+        move |request| {
+            let ids = request_ids(request);
+            let mut first_push = first_push.lock().unwrap();
+            if request.path.contains("_bulk_docs") {
+                let conflict = first_push.is_none();
+                if conflict {
+                    *first_push = Some(request.body["docs"].clone());
+                }
+                let results: Vec<_> = ids.iter()
+                    .map(|id| if conflict {
+                        serde_json::json!({ "id": id, "error": "conflict", "reason": "Document update conflict." })
+                    } else {
+                        serde_json::json!({ "ok": true, "id": id, "rev": "3-c" })
+                    })
+                    .collect();
+                (201, serde_json::json!(results))
+            } else if request.path.contains("include_docs=true") {
+                let docs = first_push.clone().unwrap_or_default();
+                let rows: Vec<_> = docs.as_array().unwrap().iter()
+                    .map(|doc| {
+                        let mut doc = doc.clone();
+                        doc["_rev"] = "2-b".into();
+                        if !same {
+                            doc["title"] = "edited elsewhere".into();
+                        }
+                        serde_json::json!({ "key": doc["_id"], "value": { "rev": "2-b" }, "doc": doc })
+                    })
+                    .collect();
+                (200, serde_json::json!({ "rows": rows }))
+            } else {
+                let rows: Vec<_> = ids.iter().map(|id| serde_json::json!({ "key": id, "value": { "rev": "1-a" } })).collect();
+                (200, serde_json::json!({ "rows": rows }))
+            }
+        }
+    }
+    
+    fn bulk_pushes(requests: &Requests) -> Vec<serde_json::Value> {
+        requests.lock().unwrap().iter()
+            .filter(|request| request.path.contains("_bulk_docs"))
+            .map(|request| request.body["docs"].clone())
+            .collect()
+    }
+    
+    #[tokio::test]
+    async fn push_with_a_stale_rev_retries_when_the_server_has_our_content() {
+        let db = Database::new_in_memory().unwrap();
+        let task = add(&db, "landed but unacknowledged");
+        let (url, requests) = mock_server(stale_rev_server(true)).await;
+        
+        push(&db, &url).await.unwrap();
+        
+        let pushes = bulk_pushes(&requests);
+        assert_eq!(pushes.len(), 2);
+        assert_eq!(pushes[0][0]["_rev"], "1-a");
+        assert_eq!(pushes[1][0]["_rev"], "2-b");
+        // The same document both times, never a new one
+        assert!(pushes.iter().all(|docs| docs.as_array().unwrap().len() == 1 && docs[0]["_id"] == task.id.as_str()));
+        assert!(db.get_dirty_tasks().unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn push_with_a_stale_rev_leaves_other_edits_for_pull() {
+        let db = Database::new_in_memory().unwrap();
+        let task = add(&db, "edited here");
+        let (url, requests) = mock_server(stale_rev_server(false)).await;
+        
+        push(&db, &url).await.unwrap();
+        
+        assert_eq!(bulk_pushes(&requests).len(), 1);
+        let dirty = db.get_dirty_tasks().unwrap();
+        assert_eq!(dirty.len(), 1);
+        assert_eq!(dirty[0].id, task.id);
+    }
+    
+    #[tokio::test]
+    async fn offline_only_after_enough_unreachable_cycles() {
+        let db = Database::new_in_memory().unwrap();
+        add(&db, "Plan trip");
+        // Nothing listens on a port that was just released
+        let closed = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let unreachable = push(&db, &closed).await.unwrap_err();
+        assert!(is_unreachable(&unreachable), "{}", unreachable);
+        
+        let mut connectivity = Connectivity::default();
+        for _ in 1..OFFLINE_THRESHOLD {
+            assert!(!connectivity.record(Err(&unreachable)));
+            assert!(!connectivity.is_offline());
+        }
+        assert!(!connectivity.record(Err(&unreachable)));
+        assert!(connectivity.is_offline());
+        
+        // An answer, even an error, ends the offline spell
+        let (url, _) = mock_server(|_| (500, serde_json::json!({ "error": "internal" }))).await;
+        let answered = push(&db, &url).await.unwrap_err();
+        assert!(!is_unreachable(&answered), "{}", answered);
+        assert!(connectivity.record(Err(&answered)));
+        assert!(!connectivity.is_offline());
+    }
+    
+    #[test]
+    fn a_reachable_cycle_restarts_the_offline_count() {
+        let unreachable = format!("Push failed: {}: connection refused", UNREACHABLE);
+        let mut connectivity = Connectivity::default();
+        
+        for _ in 1..OFFLINE_THRESHOLD {
+            connectivity.record(Err(&unreachable));
+        }
+        connectivity.record(Ok(()));
+        for _ in 1..OFFLINE_THRESHOLD {
+            connectivity.record(Err(&unreachable));
+        }
+        
+        assert!(!connectivity.is_offline());
+    }
 }
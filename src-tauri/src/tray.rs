@@ -0,0 +1,69 @@
+use crate::database::Database;
+use crate::quick_add;
+use tauri::menu::{Menu, MenuItem};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager};
+
+const TRAY_ID: &str = "main";
+
+/// Build the tray icon with its show/hide, quick add and quit items
+pub fn setup(app: &AppHandle) -> tauri::Result<()> {
+    let toggle = MenuItem::with_id(app, "toggle", "Show/Hide Taskist", true, None::<&str>)?;
+    let quick_add = MenuItem::with_id(app, "quick-add", "Quick Add…", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(app, &[&toggle, &quick_add, &quit])?;
+    
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip("Taskist")
+        .on_menu_event(|app, event| match event.id.as_ref() {
+            "toggle" => toggle_main_window(app),
+            "quick-add" => {
+                if let Err(e) = quick_add::open_window(app) {
+                    log::error!("{}", e);
+                }
+            }
+            "quit" => app.exit(0),
+            _ => {}
+        });
+    
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    
+    builder.build(app)?;
+    Ok(())
+}
+
+/// Show the number of incomplete tasks in the tray tooltip
+pub fn refresh(app: &AppHandle, db: &Database) {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    
+    let tooltip = match db.get_all_tasks() {
+        Ok(tasks) => match tasks.iter().filter(|task| !task.completed).count() {
+            1 => "Taskist: 1 task left".to_string(),
+            count => format!("Taskist: {} tasks left", count),
+        },
+        Err(e) => {
+            log::error!("Failed to count tasks: {}", e);
+            return;
+        }
+    };
+    
+    let _ = tray.set_tooltip(Some(tooltip));
+}
+
+fn toggle_main_window(app: &AppHandle) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    
+    if window.is_visible().unwrap_or(false) {
+        let _ = window.hide();
+    } else {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// File next to the executable that switches on portable mode
+pub const PORTABLE_MARKER: &str = "portable.txt";
+
+/// The executable's directory if `portable.txt` sits beside it, in which case
+/// all app data is kept there instead of the OS app data directory
+pub fn portable_dir(exe: &Path) -> Option<PathBuf> {
+    let dir = exe.parent()?;
+    dir.join(PORTABLE_MARKER).is_file().then(|| dir.to_path_buf())
+}
+
+/// `path` with `suffix` appended to its file name, in the same directory
+pub fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Replace `path` with `bytes` without ever leaving a half-written file:
+/// the data goes to a sibling temp file that is renamed over `path`.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    write_atomic_checked(path, bytes, |_| Ok(()))
+}
+
+/// `write_atomic`, with `check` run against the temp file before the rename.
+/// An error from it leaves `path` untouched.
+pub fn write_atomic_checked(
+    path: &Path,
+    bytes: &[u8],
+    check: impl FnOnce(&Path) -> Result<(), String>,
+) -> Result<(), String> {
+    let tmp = sibling_path(path, ".tmp");
+    
+    let result = write_synced(&tmp, bytes)
+        .and_then(|()| check(&tmp))
+        .and_then(|()| {
+            fs::rename(&tmp, path)
+                .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+        });
+    
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// Write and flush to disk, so a rename afterwards can't expose an empty file
+pub fn write_synced(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    let mut file = File::create(path)
+        .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    file.write_all(bytes)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// A file `remove_files` or `shred_files` couldn't delete
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FileError {
+    pub path: String,
+    pub error: String,
+}
+
+/// Delete every file in `paths`. Files that are already gone count as deleted.
+pub fn remove_files(paths: &[PathBuf]) -> Vec<FileError> {
+    remove_each(paths, |path| fs::remove_file(path))
+}
+
+/// `remove_files`, overwriting each file with zeros before it's deleted
+pub fn shred_files(paths: &[PathBuf]) -> Vec<FileError> {
+    remove_each(paths, |path| {
+        let mut file = OpenOptions::new().write(true).open(path)?;
+        let len = file.metadata()?.len();
+        file.write_all(&vec![0; len as usize])?;
+        file.sync_all()?;
+        drop(file);
+        fs::remove_file(path)
+    })
+}
+
+fn remove_each(paths: &[PathBuf], remove: impl Fn(&Path) -> std::io::Result<()>) -> Vec<FileError> {
+    paths.iter()
+        .filter_map(|path| match remove(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Some(FileError {
+                path: path.to_string_lossy().into_owned(),
+                error: e.to_string(),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A fresh, empty directory for tests that need real files; remove it when done
+#[cfg(test)]
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("taskist-test-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn portable_mode_needs_the_marker_file_beside_the_executable() {
+        let dir = temp_dir();
+        let exe = dir.join("taskist");
+        
+        assert_eq!(portable_dir(&exe), None);
+        
+        // A directory of that name doesn't count
+        fs::create_dir(dir.join(PORTABLE_MARKER)).unwrap();
+        assert_eq!(portable_dir(&exe), None);
+        fs::remove_dir(dir.join(PORTABLE_MARKER)).unwrap();
+        
+        fs::write(dir.join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(portable_dir(&exe), Some(dir.clone()));
+        
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
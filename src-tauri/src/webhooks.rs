@@ -0,0 +1,137 @@
+use crate::database::Task;
+use reqwest::{Client, Url};
+use serde::Serialize;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Quiet period after the last change before queued events are sent
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    Added,
+    Completed,
+    Reopened,
+    Deleted,
+}
+
+/// Body of every webhook request
+#[derive(Serialize, Debug)]
+struct Payload {
+    event: WebhookEvent,
+    task: Task,
+}
+
+/// POSTs task changes to the user's webhook URL in the background. Events are
+/// held until changes stop for `DEBOUNCE`, and only the latest event per task
+/// is sent. Failures are logged, never reported to the UI.
+pub struct WebhookNotifier {
+    client: Client,
+    url: RwLock<Option<String>>,
+    pending: Mutex<Vec<Payload>>,
+    wake: Notify,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: Option<String>) -> Self {
+        Self {
+            client: Client::new(),
+            url: RwLock::new(url),
+            pending: Mutex::new(Vec::new()),
+            wake: Notify::new(),
+        }
+    }
+    
+    /// Point future events at a new URL, or stop sending them with `None`
+    pub fn set_url(&self, url: Option<String>) {
+        if let Ok(mut current) = self.url.write() {
+            *current = url;
+        }
+    }
+    
+    /// Queue an event; a no-op while no URL is configured
+    pub fn notify(&self, event: WebhookEvent, task: Task) {
+        if self.url().is_none() {
+            return;
+        }
+        
+        let Ok(mut pending) = self.pending.lock() else {
+            return;
+        };
+        match pending.iter_mut().find(|queued| queued.task.id == task.id) {
+            Some(queued) => *queued = Payload { event, task },
+            None => pending.push(Payload { event, task }),
+        }
+        drop(pending);
+        
+        self.wake.notify_one();
+    }
+    
+    pub fn spawn(self: Arc<Self>) {
+        tauri::async_runtime::spawn(async move {
+            loop {
+                self.wake.notified().await;
+                
+                // Keep waiting while changes are still coming in
+                loop {
+                    tokio::select! {
+                        _ = tokio::time::sleep(DEBOUNCE) => break,
+                        _ = self.wake.notified() => {}
+                    }
+                }
+                
+                let batch = match self.pending.lock() {
+                    Ok(mut pending) => std::mem::take(&mut *pending),
+                    Err(_) => continue,
+                };
+                for payload in batch {
+                    if let Err(e) = self.send(&payload).await {
+                        log::warn!("{:?} for task {} failed: {}", payload.event, payload.task.id, e);
+                    }
+                }
+            }
+        });
+    }
+    
+    fn url(&self) -> Option<String> {
+        self.url.read().ok().and_then(|url| url.clone())
+    }
+    
+    async fn send(&self, payload: &Payload) -> Result<(), String> {
+        // The URL may have been cleared while the event was queued
+        let Some(url) = self.url() else {
+            return Ok(());
+        };
+        
+        let resp = self.client.post(&url)
+            .timeout(REQUEST_TIMEOUT)
+            .json(payload)
+            .send()
+            .await
+            .map_err(|e| format!("Request error: {}", e))?;
+        
+        if !resp.status().is_success() {
+            return Err(format!("Server returned {}", resp.status()));
+        }
+        
+        Ok(())
+    }
+}
+
+/// Check a webhook URL from settings, treating a blank one as unset
+pub fn normalize_url(url: Option<&str>) -> Result<Option<String>, String> {
+    let Some(url) = url.map(str::trim).filter(|url| !url.is_empty()) else {
+        return Ok(None);
+    };
+    
+    let parsed = Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err("Webhook URL must start with http:// or https://".to_string());
+    }
+    
+    Ok(Some(url.to_string()))
+}
@@ -11,34 +11,325 @@ import { listen, UnlistenFn } from '@tauri-apps/api/event';
 
 // ============ Types ============
 
+export type Priority = 'none' | 'low' | 'medium' | 'high';
+
+/**
+ * What adding does when an open task already has the title, ignoring case,
+ * accents and spacing: hand back that task, or reject with an error
+ */
+export type DuplicatePolicy = 'returnExisting' | 'reject';
+
 export interface Task {
   id: string;
   rev?: string;
   title: string;
   description?: string;
   completed: boolean;
+  /** When the task was last completed (epoch millis); unset while open */
+  completedAt?: number;
   dueDate?: string;
   updatedAt: number;
+  createdAt: number;
+  /** Position in the list; compare as strings */
+  rank: string;
+  deleted?: boolean;
+  archived?: boolean;
+  tags: string[];
+  priority: Priority;
+  /** iCal RRULE, e.g. "FREQ=WEEKLY;BYDAY=SA" */
+  recurrence?: string;
+  /** Id of the task this is a subtask of */
+  parentId?: string;
+  /** Id of the project this task belongs to; unset means the inbox */
+  projectId?: string;
+  /** Long-form notes, separate from the one-line description */
+  notes?: string;
+  attachments: Attachment[];
+  /** Chip color as `#rrggbb` */
+  color?: string;
+  /** How long the task is expected to take */
+  estimateMinutes?: number;
+  /** When each field was last edited (epoch millis), used to merge concurrent edits */
+  fieldUpdatedAt?: Record<string, number>;
+}
+
+/**
+ * A file or link referenced by a task. Metadata only: the file itself is not
+ * stored or synced, so the path may not resolve on other devices.
+ */
+export interface Attachment {
+  name: string;
+  /** Local file path or URL */
+  path: string;
+  mime?: string;
+}
+
+export interface Project {
+  id: string;
+  rev?: string;
+  name: string;
+  color: string;
   order: number;
+  updatedAt: number;
   deleted?: boolean;
 }
 
+/** A tag from `getAllTags`. Tags are stored lowercased with whitespace collapsed. */
+export interface TagCount {
+  tag: string;
+  count: number;
+}
+
+/** One task a template creates, due `dueOffsetMinutes` after it's used */
+export interface TemplateTask {
+  title: string;
+  description?: string;
+  dueOffsetMinutes?: number;
+}
+
+/** A named set of tasks kept on this device only */
+export interface Template {
+  name: string;
+  tasks: TemplateTask[];
+  updatedAt: number;
+}
+
 export type SyncMode = 'local' | 'selfhosted' | 'cloud';
 
+/**
+ * `newestWins` merges field by field (the default), `localWins` and `remoteWins`
+ * always keep one side, and `manual` records every divergence as a conflict
+ */
+export type ConflictStrategy = 'newestWins' | 'localWins' | 'remoteWins' | 'manual';
+
+export interface TaskFilter {
+  completed?: boolean;
+  /** Due strictly before this time (epoch millis) */
+  dueBefore?: number;
+  /** Due at or after this time (epoch millis) */
+  dueAfter?: number;
+  tag?: string;
+  /** Text in the title or description */
+  search?: string;
+}
+
+/**
+ * Partial task edit for `TasksAPI.updateFields`. Leave a field out to keep it;
+ * `null` clears a nullable field.
+ */
+export interface TaskPatch {
+  title?: string;
+  description?: string | null;
+  dueDate?: string | null;
+  tags?: string[];
+  priority?: Priority;
+  recurrence?: string | null;
+  parentId?: string | null;
+  projectId?: string | null;
+  notes?: string | null;
+  color?: string | null;
+  estimateMinutes?: number | null;
+}
+
+/** One interval of work on a task, kept on this device only */
+export interface TimeEntry {
+  id: number;
+  taskId: string;
+  startedAt: number;
+  /** Missing while the timer is running */
+  endedAt?: number | null;
+}
+
+export type TaskEventKind = 'created' | 'updated' | 'completed' | 'reopened' | 'deleted' | 'restored';
+
+/** One entry in a task's edit history, kept on this device only */
+export interface TaskEvent {
+  id: number;
+  taskId: string;
+  kind: TaskEventKind;
+  /** Epoch millis */
+  at: number;
+  /** The new task for 'created'; otherwise each changed field mapped to `[before, after]` */
+  details: Task | Record<string, [unknown, unknown]>;
+}
+
+export interface TaskStats {
+  total: number;
+  completed: number;
+  incomplete: number;
+  /** Open tasks past their due date */
+  overdue: number;
+  /** Open tasks due within the next 24 hours */
+  dueSoon: number;
+  /** Created since Monday */
+  createdThisWeek: number;
+}
+
 export interface SyncSettings {
   syncMode: SyncMode;
   syncUrl: string;
   syncUsername: string;
   syncPassword: string;
   syncDbName: string;
+  maxBackoffSecs?: number;
+  /** Use CouchDB's long-poll changes feed (default) instead of polling */
+  longPoll?: boolean;
+  /** Seconds between sync cycles (default 5); 0 syncs only on `SyncAPI.trigger` */
+  syncIntervalSecs?: number;
+  /** Keep syncing on metered connections (default true); detected on Linux and Windows */
+  syncOnMetered?: boolean;
+  /**
+   * Changes fetched per request when pulling (default 500, at most 10000). Each
+   * page is saved on its own, so an interrupted first sync resumes.
+   */
+  pullBatchSize?: number;
+  /**
+   * CouchDB filter (`design/name`) for pulled changes. A missing design document is
+   * created with a filter passing only `type == "task"` documents.
+   */
+  syncFilter?: string;
+  /** What a pull does with remote edits that diverge from unpushed local ones */
+  conflictStrategy?: ConflictStrategy;
+  /** Keychain account holding the password, when built with keychain support */
+  keychainAccount?: string;
+  /** Why the stored password couldn't be loaded, if it couldn't */
+  credentialError?: string;
+  /** PEM file with an extra CA to trust */
+  caCertPath?: string;
+  /** PEM client certificate and key, for mutual TLS */
+  clientCertPath?: string;
+  clientKeyPath?: string;
+  /** Skip server certificate verification; explicit opt-in for self-signed setups */
+  acceptInvalidCerts?: boolean;
+  /** http(s):// or socks5:// proxy; unset falls back to HTTP_PROXY/NO_PROXY */
+  proxyUrl?: string;
+  /** Taskly Cloud API token, used instead of username/password in cloud mode */
+  authToken?: string;
+  /** Set by `getSyncSettings`, which leaves `syncPassword` blank */
+  hasPassword?: boolean;
+  /** Set by `getSyncSettings`, which leaves `authToken` unset */
+  hasAuthToken?: boolean;
+}
+
+export interface SyncProfile {
+  name: string;
+  settings: SyncSettings;
+}
+
+export interface SyncProfiles {
+  /** Name of the profile sync runs against */
+  active: string;
+  profiles: SyncProfile[];
+}
+
+export interface AppSettings {
+  reminderMinutes: number;
+  /** Global accelerator for the quick add window, null to disable */
+  quickAddShortcut?: string | null;
+  /** Receives a POST of `{ event, task }` for every added, completed or deleted task */
+  webhookUrl?: string;
+  /** How long queued edits wait for the next keystroke, in milliseconds */
+  updateDebounceMs: number;
+  /** Most verbose level written to the log, `info` by default */
+  logLevel?: 'error' | 'warn' | 'info' | 'debug' | 'trace';
+  /** Check the database when the app starts and repair it if damaged, on by default */
+  checkIntegrityOnStartup?: boolean;
+  /** Set when the quick add shortcut couldn't be registered */
+  shortcutError?: string;
+}
+
+/** What protects the data at rest, from `getSecurityStatus` */
+export interface SecurityStatus {
+  /** Settings files are encrypted with the key in `encryption.key` */
+  settingsEncrypted: boolean;
+  /** The tasks database is encrypted with SQLCipher */
+  databaseEncrypted: boolean;
+  /** The key is unlocked with a passphrase instead of sitting in a key file */
+  passphraseMode: boolean;
+  syncPasswordInKeychain: boolean;
+  /** This build can keep the sync password in the OS keychain */
+  keychainAvailable: boolean;
+}
+
+/** A file `wipeAllData` couldn't remove */
+export interface FileError {
+  path: string;
+  error: string;
+}
+
+/** Phrase `wipeAllData` must be given to go ahead */
+export const WIPE_CONFIRMATION = 'DELETE ALL DATA';
+
+/** What was salvaged from a damaged database */
+export interface RecoveryReport {
+  recoveredRows: number;
+  /** Tables where reading stopped at damaged data, so rows may be missing */
+  incompleteTables: string[];
+  /** Where the damaged file was kept */
+  damagedPath: string;
 }
 
-export type SyncStatus = 'idle' | 'connecting' | 'syncing' | 'paused' | 'error' | 'disabled';
+/** Payload of `integrity-warning`; `recovery` is missing when repair failed */
+export interface IntegrityWarning {
+  recovery?: RecoveryReport | null;
+  error?: string | null;
+}
+
+/** Why `getSyncSettings` failed */
+export interface SettingsError {
+  kind: 'io' | 'unreadable';
+  message: string;
+}
+
+export type SyncStatus = 'idle' | 'connecting' | 'syncing' | 'synced' | 'paused' | 'stopped' | 'flushing' | 'offline' | 'error' | 'disabled';
 
 export interface SyncState {
   status: SyncStatus;
   lastSynced?: number;
   error?: string;
+  /** When sync will retry after an error (epoch millis) */
+  nextRetryAt?: number;
+  /** [done, total] documents while a push or pull is in progress */
+  progress?: [number, number] | null;
+  /** Why sync is holding off when that isn't an error, e.g. a metered connection */
+  note?: string | null;
+}
+
+/** Database size on disk in bytes, WAL included */
+export interface CompactStats {
+  beforeBytes: number;
+  afterBytes: number;
+}
+
+export interface SyncErrorEntry {
+  /** Epoch millis */
+  at: number;
+  message: string;
+}
+
+/** Result of `SettingsAPI.testCredentials`; later flags stay false once a step fails */
+export interface CredentialCheck {
+  reachable: boolean;
+  authenticated: boolean;
+  dbExists: boolean;
+  version: string | null;
+  /** Why the first failing step failed */
+  error: string | null;
+}
+
+export type UpdateError =
+  | { kind: 'conflict'; current: Task }
+  | { kind: 'failed'; message: string };
+
+export function isUpdateConflict(e: unknown): e is { kind: 'conflict'; current: Task } {
+  return typeof e === 'object' && e !== null && (e as UpdateError).kind === 'conflict';
+}
+
+export interface TaskConflict {
+  taskId: string;
+  local: Task;
+  remote: Task;
+  detectedAt: number;
 }
 
 // ============ Task API ============
@@ -51,32 +342,248 @@ export const TasksAPI = {
     return await invoke<Task[]>('get_all_tasks');
   },
 
+  /**
+   * Get only top-level tasks (not subtasks)
+   */
+  async getRoots(): Promise<Task[]> {
+    return await invoke<Task[]>('get_root_tasks');
+  },
+
+  /**
+   * Get the direct subtasks of a task
+   */
+  async getSubtasks(parentId: string): Promise<Task[]> {
+    return await invoke<Task[]>('get_subtasks', { parentId });
+  },
+
+  /**
+   * Get all tasks ordered by priority (highest first)
+   */
+  async getAllByPriority(): Promise<Task[]> {
+    return await invoke<Task[]>('get_all_tasks_sorted_by_priority');
+  },
+
+  /**
+   * Get all tasks ordered by creation time
+   */
+  async getAllByCreated(ascending = true): Promise<Task[]> {
+    return await invoke<Task[]>('get_all_tasks_by_created', { ascending });
+  },
+
+  /**
+   * Search task titles and descriptions, best matches first
+   */
+  async search(query: string): Promise<Task[]> {
+    return await invoke<Task[]>('search_tasks', { query });
+  },
+
+  /**
+   * Get all tasks carrying a tag
+   */
+  async getByTag(tag: string): Promise<Task[]> {
+    return await invoke<Task[]>('get_tasks_by_tag', { tag });
+  },
+
+  /**
+   * Every tag in use, by name, with how many tasks carry it
+   */
+  async getAllTags(): Promise<TagCount[]> {
+    return await invoke<TagCount[]>('get_all_tags');
+  },
+
+  /**
+   * Rename a tag on every task carrying it; returns the changed tasks
+   */
+  async renameTag(oldTag: string, newTag: string): Promise<Task[]> {
+    return await invoke<Task[]>('rename_tag', { old: oldTag, new: newTag });
+  },
+
+  /**
+   * Get tasks due in [start, end), both epoch millis
+   */
+  async getDueBetween(start: number, end: number): Promise<Task[]> {
+    return await invoke<Task[]>('get_tasks_due_between', { start, end });
+  },
+
+  /**
+   * Get tasks completed in [start, end), both epoch millis, in completion order
+   */
+  async getCompletedBetween(start: number, end: number): Promise<Task[]> {
+    return await invoke<Task[]>('get_completed_between', { start, end });
+  },
+
+  /**
+   * Get open tasks that are past their due date, most overdue first
+   */
+  async getOverdue(): Promise<Task[]> {
+    return await invoke<Task[]>('get_overdue_tasks');
+  },
+
+  /**
+   * Open tasks for the daily view: overdue, due between `dayStart` and `dayEnd`
+   * (epoch millis of the local day) or high priority, by due date then priority
+   */
+  async getToday(dayStart: number, dayEnd: number): Promise<Task[]> {
+    return await invoke<Task[]>('get_today_tasks', { dayStart, dayEnd });
+  },
+
+  /**
+   * Create a copy of a task right below it, titled "<title> (copy)"
+   */
+  async duplicate(id: string): Promise<Task> {
+    return await invoke<Task>('duplicate_task', { id });
+  },
+
+  /**
+   * Push a task's due date back by `addMillis` (from now if it has none).
+   * Presets like "tomorrow" or "next week" are computed by the caller.
+   */
+  async snooze(id: string, addMillis: number): Promise<Task> {
+    return await invoke<Task>('snooze_task', { id, addMillis });
+  },
+
+  /**
+   * Get tasks matching every set field of `filter`; an empty filter returns all tasks
+   */
+  async query(filter: TaskFilter): Promise<Task[]> {
+    return await invoke<Task[]>('query_tasks', { filter });
+  },
+
+  /**
+   * Get one page of tasks in list order; `limit` is capped at 500
+   */
+  async getPage(offset: number, limit: number): Promise<Task[]> {
+    return await invoke<Task[]>('get_tasks_page', { offset, limit });
+  },
+
+  /**
+   * Count the tasks `getPage` pages through
+   */
+  async count(): Promise<number> {
+    return await invoke<number>('count_tasks');
+  },
+
+  /**
+   * `[YYYY-MM-DD, count]` of tasks completed per local day between `start` and `end`
+   * (epoch millis), in date order. Days without completions are omitted. Pass
+   * `-new Date().getTimezoneOffset()` as `tzOffsetMinutes`.
+   */
+  async getCompletionsByDay(start: number, end: number, tzOffsetMinutes: number): Promise<[string, number][]> {
+    return await invoke<[string, number][]>('get_completions_by_day', { start, end, tzOffsetMinutes });
+  },
+
+  /**
+   * Get task counts for the stats panel
+   */
+  async getStats(): Promise<TaskStats> {
+    return await invoke<TaskStats>('get_task_stats');
+  },
+
   /**
    * Add a new task
    */
   async add(
     title: string,
-    options?: { description?: string; dueDate?: string }
+    options?: {
+      description?: string;
+      dueDate?: string;
+      tags?: string[];
+      priority?: Priority;
+      recurrence?: string;
+      parentId?: string;
+      projectId?: string;
+      notes?: string;
+      attachments?: Attachment[];
+      color?: string;
+      estimateMinutes?: number;
+      /** Set to skip adding when an open task in the project has the same title */
+      duplicatePolicy?: DuplicatePolicy;
+    }
   ): Promise<Task> {
+    const { duplicatePolicy, ...fields } = options ?? {};
     return await invoke<Task>('add_task', {
-      title,
-      description: options?.description,
-      dueDate: options?.dueDate,
+      newTask: { title, ...fields },
+      duplicatePolicy,
     });
   },
 
   /**
-   * Update an existing task
+   * Add a task with just a title, as from the quick add window
+   */
+  async quickAdd(title: string): Promise<Task> {
+    return await invoke<Task>('quick_add_task', { title });
+  },
+
+  /**
+   * Update an existing task. Rejects with an `UpdateError` of kind `conflict` when
+   * `task.rev` is no longer the stored rev; reapply the edit to `current` and retry.
    */
   async update(task: Task): Promise<Task> {
     return await invoke<Task>('update_task', { task });
   },
 
   /**
-   * Delete a task
+   * Change only the given fields of a task
+   */
+  async updateFields(id: string, patch: TaskPatch): Promise<Task> {
+    return await invoke<Task>('update_task_fields', { id, patch });
+  },
+
+  /**
+   * Queue an edit for a task, e.g. on every keystroke. Only the last edit is
+   * written, once edits stop for the configured debounce.
+   */
+  async queueUpdate(task: Task): Promise<Task> {
+    return await invoke<Task>('queue_task_update', { task });
+  },
+
+  /**
+   * Replace a task's notes; pass undefined to clear them
+   */
+  async setNotes(id: string, notes?: string): Promise<Task> {
+    return await invoke<Task>('set_task_notes', { id, notes: notes ?? null });
+  },
+
+  /**
+   * Set a task's color as `#rrggbb`; pass undefined to clear it
+   */
+  async setColor(id: string, color?: string): Promise<Task> {
+    return await invoke<Task>('set_task_color', { id, color: color ?? null });
+  },
+
+  /**
+   * Start timing work on a task; rejects if its timer is already running
+   */
+  async startTimer(id: string): Promise<TimeEntry> {
+    return await invoke<TimeEntry>('start_task_timer', { id });
+  },
+
+  /**
+   * Stop a task's running timer
+   */
+  async stopTimer(id: string): Promise<TimeEntry> {
+    return await invoke<TimeEntry>('stop_task_timer', { id });
+  },
+
+  /**
+   * Total time logged on a task in milliseconds, including a running timer
+   */
+  async getTimeSpent(id: string): Promise<number> {
+    return await invoke<number>('get_task_time_spent', { id });
+  },
+
+  /**
+   * Edit history of a task on this device, oldest first
+   */
+  async getHistory(id: string): Promise<TaskEvent[]> {
+    return await invoke<TaskEvent[]>('get_task_history', { id });
+  },
+
+  /**
+   * Delete a task and its subtasks, returning them as deleted, the task first
    */
-  async delete(id: string): Promise<void> {
-    await invoke('delete_task', { id });
+  async delete(id: string): Promise<Task[]> {
+    return await invoke<Task[]>('delete_task', { id });
   },
 
   /**
@@ -87,17 +594,192 @@ export const TasksAPI = {
   },
 
   /**
-   * Reorder a task up or down
+   * Complete or reopen several tasks at once
+   */
+  async bulkSetCompleted(ids: string[], completed: boolean): Promise<Task[]> {
+    return await invoke<Task[]>('bulk_complete_tasks', { ids, completed });
+  },
+
+  /**
+   * Delete several tasks at once
+   */
+  async bulkDelete(ids: string[]): Promise<Task[]> {
+    return await invoke<Task[]>('bulk_delete_tasks', { ids });
+  },
+
+  /**
+   * Get archived tasks, most recently touched first
+   */
+  async getArchived(): Promise<Task[]> {
+    return await invoke<Task[]>('get_archived_tasks');
+  },
+
+  /**
+   * Hide a task from the main list without deleting it
+   */
+  async archive(id: string): Promise<Task> {
+    return await invoke<Task>('archive_task', { id });
+  },
+
+  /**
+   * Bring an archived task back to the main list
+   */
+  async unarchive(id: string): Promise<Task> {
+    return await invoke<Task>('unarchive_task', { id });
+  },
+
+  /**
+   * Archive completed tasks untouched for the given number of milliseconds
+   */
+  async archiveCompletedOlderThan(millis: number): Promise<Task[]> {
+    return await invoke<Task[]>('archive_completed_older_than', { millis });
+  },
+
+  /**
+   * Get deleted tasks, most recently deleted first
+   */
+  async getTrash(): Promise<Task[]> {
+    return await invoke<Task[]>('get_trash');
+  },
+
+  /**
+   * Restore a deleted task to the end of the list
+   */
+  async restoreFromTrash(id: string): Promise<Task> {
+    return await invoke<Task>('restore_task', { id });
+  },
+
+  /**
+   * Permanently remove synced deleted tasks older than the given number of
+   * milliseconds. Returns how many were removed.
+   */
+  async purgeDeletedOlderThan(millis: number): Promise<number> {
+    return await invoke<number>('purge_deleted_older_than', { millis });
+  },
+
+  /**
+   * Undo the last task change; resolves to the restored tasks (empty if nothing to undo)
+   */
+  async undo(): Promise<Task[]> {
+    return await invoke<Task[]>('undo');
+  },
+
+  /**
+   * Redo the last undone task change
+   */
+  async redo(): Promise<Task[]> {
+    return await invoke<Task[]>('redo');
+  },
+
+  /**
+   * Reorder a task up or down. Returns the moved task and the neighbour it
+   * swapped with, or nothing at the ends of the list.
+   */
+  async reorder(taskId: string, direction: 'up' | 'down'): Promise<Task[]> {
+    return await invoke<Task[]>('reorder_task', { taskId, direction });
+  },
+
+  /**
+   * Reorder a task up or down within a project's view; tasks in other projects
+   * keep their order
+   */
+  async reorderInProject(taskId: string, direction: 'up' | 'down', projectId: string): Promise<Task[]> {
+    return await invoke<Task[]>('reorder_task_in_project', { taskId, direction, projectId });
+  },
+
+  /**
+   * Move a task into another task's position, returning it with its new rank
+   */
+  async moveToPosition(taskId: string, targetTaskId: string): Promise<Task> {
+    return await invoke<Task>('move_task_to_position', { taskId, targetTaskId });
+  },
+
+  /**
+   * Move a task to an index in the full list of non-deleted tasks, e.g. after a drag and drop
+   */
+  async moveToIndex(taskId: string, newIndex: number): Promise<Task> {
+    return await invoke<Task>('move_task_to_index', { taskId, newIndex });
+  },
+
+  /**
+   * Move a task to an index in a project's task list, e.g. after a drag and drop
+   * in the project view
+   */
+  async moveToIndexInProject(taskId: string, newIndex: number, projectId: string): Promise<Task> {
+    return await invoke<Task>('move_task_to_index_in_project', { taskId, newIndex, projectId });
+  },
+
+  /**
+   * Move several tasks together, keeping their relative order, so the first lands
+   * at `newIndex`. Returns the moved tasks in their new order.
+   */
+  async moveManyToIndex(taskIds: string[], newIndex: number): Promise<Task[]> {
+    return await invoke<Task[]>('move_tasks_to_index', { taskIds, newIndex });
+  },
+
+  /**
+   * Move a task between two neighbours (either may be omitted at the list edges)
+   */
+  async moveBetween(taskId: string, beforeId?: string, afterId?: string): Promise<Task> {
+    return await invoke<Task>('move_task_between', { taskId, beforeId, afterId });
+  },
+
+  /**
+   * Export all tasks as CSV text
+   */
+  async exportCsv(): Promise<string> {
+    return await invoke<string>('export_tasks_csv');
+  },
+
+  /**
+   * Export all tasks as a Markdown checklist
+   */
+  async exportMarkdown(): Promise<string> {
+    return await invoke<string>('export_tasks_markdown');
+  },
+
+  /**
+   * Export open tasks with a due date as an iCalendar (.ics) feed
+   */
+  async exportIcs(): Promise<string> {
+    return await invoke<string>('export_tasks_ics');
+  },
+
+  /**
+   * Create a JSON backup of all tasks and projects
+   */
+  async backup(): Promise<string> {
+    return await invoke<string>('backup_tasks');
+  },
+
+  /**
+   * Reclaim space left by deleted tasks; sync waits until it's done
+   */
+  async compact(): Promise<CompactStats> {
+    return await invoke<CompactStats>('compact_database');
+  },
+
+  /**
+   * Run SQLite's integrity check; false means the database is damaged and will
+   * be repaired at the next start
    */
-  async reorder(taskId: string, direction: 'up' | 'down'): Promise<void> {
-    await invoke('reorder_task', { taskId, direction });
+  async checkIntegrity(): Promise<boolean> {
+    return await invoke<boolean>('check_database_integrity');
   },
 
   /**
-   * Move a task to swap positions with another task
+   * Restore a JSON backup; returns the number of tasks it contained
    */
-  async moveToPosition(taskId: string, targetTaskId: string): Promise<void> {
-    await invoke('move_task_to_position', { taskId, targetTaskId });
+  async restore(json: string): Promise<number> {
+    return await invoke<number>('restore_tasks', { json });
+  },
+
+  /**
+   * Import open tasks from a Todoist JSON export; returns how many were added.
+   * Items imported before are skipped.
+   */
+  async importTodoist(json: string): Promise<number> {
+    return await invoke<number>('import_todoist', { json });
   },
 
   /**
@@ -106,20 +788,154 @@ export const TasksAPI = {
   onTasksChanged(callback: () => void): Promise<UnlistenFn> {
     return listen('tasks-changed', callback);
   },
+
+  /**
+   * Listen for the task list loaded at startup, sent once after `frontendReady`.
+   * Not sent if tasks changed before then; `getAll` covers that case.
+   */
+  onTasksInitial(callback: (tasks: Task[]) => void): Promise<UnlistenFn> {
+    return listen<Task[]>('tasks-initial', (event) => {
+      callback(event.payload);
+    });
+  },
+
+  /**
+   * Listen for the startup integrity check finding the database damaged, sent
+   * once after `frontendReady`
+   */
+  onIntegrityWarning(callback: (warning: IntegrityWarning) => void): Promise<UnlistenFn> {
+    return listen<IntegrityWarning>('integrity-warning', (event) => {
+      callback(event.payload);
+    });
+  },
+
+  /**
+   * Tell the backend the listeners are registered, so it can send
+   * `integrity-warning` and `tasks-initial`
+   */
+  async frontendReady(): Promise<void> {
+    await invoke('frontend_ready');
+  },
+
+  /**
+   * Listen for tasks a sync pull inserted or updated, one event per pull
+   */
+  onTasksUpserted(callback: (ids: string[]) => void): Promise<UnlistenFn> {
+    return listen<string[]>('tasks-upserted', (event) => {
+      callback(event.payload);
+    });
+  },
+
+  /**
+   * Listen for tasks a sync pull deleted, one event per pull
+   */
+  onTasksDeleted(callback: (ids: string[]) => void): Promise<UnlistenFn> {
+    return listen<string[]>('tasks-deleted', (event) => {
+      callback(event.payload);
+    });
+  },
+
+  /**
+   * Listen for tasks entering the reminder window before their due date
+   */
+  onTaskDueSoon(callback: (task: Task) => void): Promise<UnlistenFn> {
+    return listen<Task>('task-due-soon', (event) => {
+      callback(event.payload);
+    });
+  },
+
+  /**
+   * Listen for changes in the number of overdue tasks
+   */
+  onOverdueCountChanged(callback: (count: number) => void): Promise<UnlistenFn> {
+    return listen<number>('overdue-count-changed', (event) => {
+      callback(event.payload);
+    });
+  },
+};
+
+// ============ Projects API ============
+
+export const ProjectsAPI = {
+  /**
+   * Get all projects in display order
+   */
+  async getAll(): Promise<Project[]> {
+    return await invoke<Project[]>('get_all_projects');
+  },
+
+  /**
+   * Create a new project
+   */
+  async add(name: string, color: string): Promise<Project> {
+    return await invoke<Project>('add_project', { name, color });
+  },
+
+  /**
+   * Rename a project
+   */
+  async rename(projectId: string, name: string): Promise<Project> {
+    return await invoke<Project>('rename_project', { projectId, name });
+  },
+
+  /**
+   * Delete a project; its tasks move back to the inbox
+   */
+  async delete(projectId: string): Promise<void> {
+    await invoke('delete_project', { projectId });
+  },
+
+  /**
+   * Get the tasks belonging to a project
+   */
+  async getTasks(projectId: string): Promise<Task[]> {
+    return await invoke<Task[]>('get_tasks_for_project', { projectId });
+  },
+};
+
+// ============ Templates API ============
+
+export const TemplatesAPI = {
+  /**
+   * Save tasks as a template, replacing one with the same name
+   */
+  async save(name: string, tasks: TemplateTask[]): Promise<Template> {
+    return await invoke<Template>('save_template', { name, tasks });
+  },
+
+  /**
+   * Get every saved template, by name
+   */
+  async getAll(): Promise<Template[]> {
+    return await invoke<Template[]>('list_templates');
+  },
+
+  async delete(name: string): Promise<void> {
+    await invoke('delete_template', { name });
+  },
+
+  /**
+   * Add a template's tasks with due dates counted from now
+   */
+  async instantiate(name: string): Promise<Task[]> {
+    return await invoke<Task[]>('instantiate_template', { name });
+  },
 };
 
 // ============ Settings API ============
 
 export const SettingsAPI = {
   /**
-   * Get sync settings (decrypted from secure storage)
+   * Get sync settings (decrypted from secure storage), with the password and
+   * token blanked out. Rejects with a `SettingsError`; `unreadable` can be fixed with `resetSyncSettings`.
    */
   async getSyncSettings(): Promise<SyncSettings> {
     return await invoke<SyncSettings>('get_sync_settings');
   },
 
   /**
-   * Save sync settings (encrypted to secure storage)
+   * Save sync settings (encrypted to secure storage). A blank password or
+   * token keeps the stored one. Rejects with a list of problems when the settings don't validate.
    */
   async saveSyncSettings(settings: SyncSettings): Promise<void> {
     await invoke('save_sync_settings', { settings });
@@ -131,6 +947,95 @@ export const SettingsAPI = {
   async clearSyncSettings(): Promise<void> {
     await invoke('clear_sync_settings');
   },
+
+  /**
+   * Move unreadable sync settings aside and go back to the defaults
+   */
+  async resetSyncSettings(): Promise<SyncSettings> {
+    return await invoke<SyncSettings>('reset_sync_settings');
+  },
+
+  /**
+   * Saved sync profiles, secrets blanked out like `getSyncSettings`.
+   * `getSyncSettings` and `saveSyncSettings` act on the active one.
+   */
+  async listSyncProfiles(): Promise<SyncProfiles> {
+    return await invoke<SyncProfiles>('list_sync_profiles');
+  },
+
+  /**
+   * Create or update a named sync profile. A blank password or token keeps the stored one.
+   * Rejects with a list of problems when the settings don't validate.
+   */
+  async saveSyncProfile(name: string, settings: SyncSettings): Promise<void> {
+    await invoke('save_sync_profile', { name, settings });
+  },
+
+  /**
+   * Delete a sync profile other than the active one
+   */
+  async deleteSyncProfile(name: string): Promise<void> {
+    await invoke('delete_sync_profile', { name });
+  },
+
+  /**
+   * Switch sync to another profile and restart it against that server
+   */
+  async setActiveSyncProfile(name: string): Promise<void> {
+    await invoke('set_active_sync_profile', { name });
+  },
+
+  /**
+   * Replace the settings encryption key and re-encrypt stored settings with it
+   */
+  async rotateEncryptionKey(): Promise<void> {
+    await invoke('rotate_encryption_key');
+  },
+
+  /**
+   * Whether settings and tasks are encrypted at rest, and how the key is kept
+   */
+  async getSecurityStatus(): Promise<SecurityStatus> {
+    return await invoke<SecurityStatus>('get_security_status');
+  },
+
+  /**
+   * Erase the database, keys, settings and keychain entries. `confirmation`
+   * must be `WIPE_CONFIRMATION`. The app has to restart afterwards. Returns
+   * the files that couldn't be removed; safe to call again.
+   */
+  async wipeAllData(confirmation: string): Promise<FileError[]> {
+    return await invoke<FileError[]>('wipe_all_data', { confirmation });
+  },
+
+  /**
+   * Get app preferences (reminders, etc.)
+   */
+  async getAppSettings(): Promise<AppSettings> {
+    return await invoke<AppSettings>('get_app_settings');
+  },
+
+  /**
+   * Save app preferences
+   */
+  async saveAppSettings(settings: AppSettings): Promise<void> {
+    await invoke('save_app_settings', { settings });
+  },
+
+  /**
+   * Path of the log file, for attaching to bug reports
+   */
+  async getLogPath(): Promise<string> {
+    return await invoke<string>('get_log_path');
+  },
+
+  /**
+   * Whether the app runs in demo mode (`--demo` or `TASKIST_DEMO=1`), where
+   * sample tasks live in memory and everything is discarded on quit
+   */
+  async isDemoMode(): Promise<boolean> {
+    return await invoke<boolean>('is_demo_mode');
+  },
 };
 
 // ============ Sync API ============
@@ -143,6 +1048,13 @@ export const SyncAPI = {
     return await invoke<SyncState>('get_sync_state');
   },
 
+  /**
+   * Recent sync errors (up to 50), oldest first
+   */
+  async getErrors(): Promise<SyncErrorEntry[]> {
+    return await invoke<SyncErrorEntry[]>('get_sync_errors');
+  },
+
   /**
    * Start syncing with the remote CouchDB server
    */
@@ -157,6 +1069,20 @@ export const SyncAPI = {
     await invoke('stop_sync');
   },
 
+  /**
+   * Skip sync cycles until resumed, without stopping the sync loop
+   */
+  async pause(): Promise<void> {
+    await invoke('pause_sync');
+  },
+
+  /**
+   * Resume a paused sync and run a cycle right away
+   */
+  async resume(): Promise<void> {
+    await invoke('resume_sync');
+  },
+
   /**
    * Restart sync (useful after settings change)
    */
@@ -164,6 +1090,51 @@ export const SyncAPI = {
     await invoke('restart_sync');
   },
 
+  /**
+   * Check that the given settings reach a CouchDB server, resolving to its version
+   */
+  async testConnection(settings: SyncSettings): Promise<string> {
+    return await invoke<string>('test_sync_connection', { settings });
+  },
+
+  /**
+   * Check that the given settings reach the server, authenticate and find the
+   * sync database, without saving them or creating the database
+   */
+  async testCredentials(settings: SyncSettings): Promise<CredentialCheck> {
+    return await invoke<CredentialCheck>('test_sync_credentials', { settings });
+  },
+
+  /**
+   * Run a sync cycle now (the only way to sync when the interval is 0)
+   */
+  async trigger(): Promise<void> {
+    await invoke('trigger_sync');
+  },
+
+  /**
+   * Get tasks whose remote and local edits diverged
+   */
+  async getConflicts(): Promise<TaskConflict[]> {
+    return await invoke<TaskConflict[]>('get_conflicts');
+  },
+
+  /**
+   * Resolve a conflict by keeping the local or the remote version
+   */
+  async resolveConflict(taskId: string, winner: 'local' | 'remote'): Promise<Task> {
+    return await invoke<Task>('resolve_conflict', { taskId, winner });
+  },
+
+  /**
+   * Listen for changes in the number of unresolved conflicts
+   */
+  onConflict(callback: (count: number) => void): Promise<UnlistenFn> {
+    return listen<number>('sync-conflict', (event) => {
+      callback(event.payload);
+    });
+  },
+
   /**
    * Listen for sync state changes
    */
@@ -172,6 +1143,15 @@ export const SyncAPI = {
       callback(event.payload);
     });
   },
+
+  /**
+   * Listen for the server answering again after sync went `offline`
+   */
+  onReconnected(callback: () => void): Promise<UnlistenFn> {
+    return listen('sync-reconnected', () => {
+      callback();
+    });
+  },
 };
 
 // ============ Convenience Exports ============
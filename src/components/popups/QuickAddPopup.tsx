@@ -0,0 +1,66 @@
+import { useState, useRef, useEffect } from "react";
+import { getCurrentWindow } from "@tauri-apps/api/window";
+import { IoAddCircleOutline } from "react-icons/io5";
+import { TasksAPI } from "../../backend";
+
+export default function QuickAddPopup() {
+  const [title, setTitle] = useState("");
+  const [error, setError] = useState<string | null>(null);
+  const inputRef = useRef<HTMLInputElement>(null);
+  
+  const close = () => {
+    getCurrentWindow().close();
+  };
+  
+  // Focus input on mount
+  useEffect(() => {
+    setTimeout(() => inputRef.current?.focus(), 50);
+  }, []);
+  
+  // Close on escape
+  useEffect(() => {
+    const handleKeyDown = (e: KeyboardEvent) => {
+      if (e.key === "Escape") {
+        close();
+      }
+    };
+    
+    document.addEventListener("keydown", handleKeyDown);
+    return () => document.removeEventListener("keydown", handleKeyDown);
+  }, []);
+  
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    const trimmed = title.trim();
+    if (!trimmed) return;
+    
+    try {
+      await TasksAPI.quickAdd(trimmed);
+      close();
+    } catch (err) {
+      setError(String(err));
+    }
+  };
+  
+  return (
+    <form
+      onSubmit={handleSubmit}
+      className="flex items-center gap-2 h-screen px-4 bg-white dark:bg-neutral-800 select-none"
+    >
+      <IoAddCircleOutline size={22} className="text-red-500 shrink-0" />
+      <input
+        ref={inputRef}
+        type="text"
+        value={title}
+        onChange={(e) => {
+          setTitle(e.target.value);
+          setError(null);
+        }}
+        placeholder={error ?? "Add a task and press Enter"}
+        className={`flex-1 bg-transparent outline-none text-sm text-neutral-800 dark:text-neutral-200 ${
+          error ? "placeholder-red-500" : "placeholder-neutral-400"
+        }`}
+      />
+    </form>
+  );
+}
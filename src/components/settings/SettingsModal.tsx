@@ -61,6 +61,8 @@ function SettingsModal({ isOpen, onClose }: SettingsProps) {
         return "";
       
       case "syncPassword":
+        // Left blank to keep the stored password
+        if (!value && syncSettings.hasPassword) return "";
         if (!value.trim()) return "Password is required";
         if (value.length < 3) return "Password must be at least 3 characters";
         return "";
@@ -143,7 +145,9 @@ function SettingsModal({ isOpen, onClose }: SettingsProps) {
       setTimeout(() => setTestStatus(""), 5000);
     } catch (error: any) {
       console.error("[settings] save/validation failed:", error);
-      setTestStatus(`✗ Error: ${error.message || String(error)}`);
+      // Validation failures arrive as a list of problems
+      const message = Array.isArray(error) ? error.join("; ") : error.message || String(error);
+      setTestStatus(`✗ Error: ${message}`);
       setTimeout(() => setTestStatus(""), 5000);
     } finally {
       setIsTesting(false);
@@ -192,7 +196,7 @@ function SettingsModal({ isOpen, onClose }: SettingsProps) {
           label: "CouchDB Password",
           type: "password",
           default: syncSettings.syncPassword,
-          placeholder: "CouchDB admin password"
+          placeholder: syncSettings.hasPassword ? "Unchanged" : "CouchDB admin password"
         },
         {
           key: "syncDbName",
@@ -1,7 +1,7 @@
 import { useState, useCallback, useRef, useEffect } from "react";
 import { IoMdCalendar } from "react-icons/io";
 import { IoClose, IoCalendarOutline, IoFlagOutline, IoChevronDown, IoReorderTwo } from "react-icons/io5";
-import { TasksAPI, type Task } from "../../backend";
+import { TasksAPI, isUpdateConflict, type Task } from "../../backend";
 import { isOverdue, isDueToday, formatDueDate, getTodayString } from "./dateUtils";
 import { PROJECTS, type TaskItemProps } from "./types";
 
@@ -142,16 +142,28 @@ function TaskItem({
       return;
     }
     
-    const updatedTask: Task = {
-      ...task,
-      title: editTitle.trim(),
-      description: editDescription.trim() || undefined,
-      dueDate: editDueDate || undefined,
+    const applyEdit = (base: Task): Task => {
+      const edited: Task = {
+        ...base,
+        title: editTitle.trim(),
+        description: editDescription.trim() || undefined,
+        dueDate: editDueDate || undefined,
+      };
+      (edited as any).project = editProject;
+      return edited;
     };
-    (updatedTask as any).project = editProject;
     
-    await TasksAPI.update(updatedTask);
-    onUpdate(updatedTask);
+    let updatedTask = applyEdit(task);
+    let saved: Task;
+    try {
+      saved = await TasksAPI.update(updatedTask);
+    } catch (e) {
+      if (!isUpdateConflict(e)) throw e;
+      // Changed elsewhere since this copy was loaded: redo the edit on the stored version
+      updatedTask = applyEdit(e.current);
+      saved = await TasksAPI.update(updatedTask);
+    }
+    onUpdate({ ...updatedTask, rev: saved.rev });
     setIsExpanded(false);
   };
 
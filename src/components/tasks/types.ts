@@ -29,7 +29,7 @@ export interface DragHandleProps {
 export interface TaskItemProps {
   task: Task;
   onUpdate: (updatedTask: Task) => void | Promise<Task>;
-  onDelete: (taskId: string) => void | Promise<void>;
+  onDelete: (taskId: string) => void | Promise<Task[]>;
   isSelected: boolean;
   onSelect: (multiSelect: boolean) => void;
   onExpand: () => void;
@@ -7,9 +7,12 @@ import {
   IoWarning,
   IoCheckmarkCircle,
   IoCloseCircle,
-  IoRefresh
+  IoRefresh,
+  IoPause,
+  IoPlay
 } from "react-icons/io5";
 import { useTasks } from "../../context/TasksContext";
+import { SyncAPI } from "../../backend";
 import type { SyncStatus } from "../../backend";
 
 interface StatusConfig {
@@ -42,13 +45,41 @@ const statusConfigs: Record<SyncStatus, StatusConfig> = {
     iconClass: "text-blue-500",
     dotClass: "bg-blue-500",
   },
-  paused: {
+  synced: {
     label: "Synced",
     description: "All changes saved locally and synced",
     icon: <IoCloudDone size={16} />,
     iconClass: "text-emerald-500",
     dotClass: "bg-emerald-500",
   },
+  paused: {
+    label: "Sync Paused",
+    description: "Changes are saved locally and sync when resumed",
+    icon: <IoPause size={16} />,
+    iconClass: "text-amber-500",
+    dotClass: "bg-amber-500",
+  },
+  flushing: {
+    label: "Saving",
+    description: "Pushing the last changes before closing...",
+    icon: <IoSync size={16} className="animate-spin" />,
+    iconClass: "text-blue-500",
+    dotClass: "bg-blue-500",
+  },
+  stopped: {
+    label: "Sync Stopped",
+    description: "Changes are saved locally only",
+    icon: <IoCloudOffline size={16} />,
+    iconClass: "text-neutral-400",
+    dotClass: "bg-neutral-400",
+  },
+  offline: {
+    label: "Offline",
+    description: "Server unreachable – changes are saved locally and sync when it's back",
+    icon: <IoCloudOffline size={16} />,
+    iconClass: "text-amber-500",
+    dotClass: "bg-amber-500",
+  },
   idle: {
     label: "Local Only",
     description: "Data stored locally in SQLite",
@@ -165,21 +196,26 @@ function SyncPopup({
         <div className="flex items-center justify-between text-sm">
           <span className="text-neutral-500 dark:text-neutral-400">CouchDB</span>
           <div className="flex items-center gap-1.5">
-            {syncState.status === "paused" && (
+            {syncState.status === "synced" && (
               <IoCheckmarkCircle size={14} className="text-emerald-500" />
             )}
             {syncState.status === "error" && (
               <IoCloseCircle size={14} className="text-red-500" />
             )}
             <span className={`font-medium ${
-              syncState.status === "paused" ? "text-emerald-600 dark:text-emerald-400" :
+              syncState.status === "synced" ? "text-emerald-600 dark:text-emerald-400" :
+              syncState.status === "paused" || syncState.status === "offline" ? "text-amber-600 dark:text-amber-400" :
               syncState.status === "error" ? "text-red-600 dark:text-red-400" :
-              syncState.status === "syncing" || syncState.status === "connecting" ? "text-blue-600 dark:text-blue-400" :
+              syncState.status === "syncing" || syncState.status === "connecting" || syncState.status === "flushing" ? "text-blue-600 dark:text-blue-400" :
               "text-neutral-600 dark:text-neutral-400"
             }`}>
-              {syncState.status === "paused" ? "Connected" : 
+              {syncState.status === "synced" ? "Connected" : 
+               syncState.status === "paused" ? "Paused" :
+               syncState.status === "stopped" ? "Stopped" :
+               syncState.status === "offline" ? "Offline" :
                syncState.status === "error" ? "Disconnected" :
                syncState.status === "syncing" ? "Syncing" :
+               syncState.status === "flushing" ? "Saving" :
                syncState.status === "connecting" ? "Connecting" :
                "Not configured"}
             </span>
@@ -197,7 +233,7 @@ function SyncPopup({
       </div>
       
       {/* Actions */}
-      {(syncState.status === "error" || syncState.status === "paused") && (
+      {(syncState.status === "error" || syncState.status === "offline" || syncState.status === "synced") && (
         <div className="p-2 border-t border-neutral-100 dark:border-neutral-700">
           <button
             onClick={() => {
@@ -207,7 +243,21 @@ function SyncPopup({
             className="w-full flex items-center justify-center gap-2 px-3 py-2 rounded-lg text-sm font-medium text-blue-600 dark:text-blue-400 hover:bg-blue-50 dark:hover:bg-blue-900/20 transition-colors"
           >
             <IoRefresh size={16} />
-            <span>{syncState.status === "error" ? "Retry Sync" : "Sync Now"}</span>
+            <span>{syncState.status === "synced" ? "Sync Now" : "Retry Sync"}</span>
+          </button>
+        </div>
+      )}
+      {syncState.status === "paused" && (
+        <div className="p-2 border-t border-neutral-100 dark:border-neutral-700">
+          <button
+            onClick={() => {
+              SyncAPI.resume().catch(console.error);
+              onClose();
+            }}
+            className="w-full flex items-center justify-center gap-2 px-3 py-2 rounded-lg text-sm font-medium text-blue-600 dark:text-blue-400 hover:bg-blue-50 dark:hover:bg-blue-900/20 transition-colors"
+          >
+            <IoPlay size={16} />
+            <span>Resume Sync</span>
           </button>
         </div>
       )}
@@ -262,7 +312,7 @@ export function SyncIndicatorCompact({ isExpanded = true }: { isExpanded?: boole
             <span className="text-sm text-neutral-600 dark:text-neutral-400 truncate">
               {config.label}
             </span>
-            {syncState.lastSynced && syncState.status === "paused" && (
+            {syncState.lastSynced && syncState.status === "synced" && (
               <span className="text-xs text-neutral-400 dark:text-neutral-500">
                 {formatTimeAgo(syncState.lastSynced)}
               </span>
@@ -320,7 +370,7 @@ export default function SyncIndicator() {
           {syncState.status === "error" && (
             <span className="absolute -top-1 -right-1 w-2.5 h-2.5 rounded-full bg-red-500 animate-pulse" />
           )}
-          {syncState.status === "paused" && (
+          {syncState.status === "synced" && (
             <span className="absolute -top-1 -right-1 w-2.5 h-2.5 rounded-full bg-emerald-500" />
           )}
         </div>
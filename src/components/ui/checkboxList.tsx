@@ -373,7 +373,7 @@ export default function CheckboxList({
       break;
 
     case key === "Backspace" || key === "Delete":
-      updateMany((t) => TasksAPI.delete(t.id));
+      updateMany(async (t) => { await TasksAPI.delete(t.id); });
       setSelectedIds([]);
       break;
 
@@ -1,4 +1,4 @@
-import React, { createContext, useContext, useEffect, useMemo, useState } from "react";
+import React, { createContext, useContext, useEffect, useMemo, useRef, useState } from "react";
 import { TasksAPI, SyncAPI, SettingsAPI } from "../backend";
 import type { Task, SyncState } from "../backend";
 
@@ -14,10 +14,13 @@ const TasksContext = createContext<TasksContextType | null>(null);
 export function TasksProvider({ children }: { children: React.ReactNode }) {
   const [tasks, setTasks] = useState<Task[]>([]);
   const [syncState, setSyncState] = useState<SyncState>({ status: "idle" });
+  // Set once a fetch lands, after which the startup snapshot would be stale
+  const loaded = useRef(false);
 
   const refreshTasks = async () => {
     try {
       const all = await TasksAPI.getAll();
+      loaded.current = true;
       setTasks(all);
     } catch (error) {
       console.error("[tasks] Failed to refresh:", error);
@@ -42,6 +45,15 @@ export function TasksProvider({ children }: { children: React.ReactNode }) {
     // Listen for task changes from Rust backend
     let unlistenTasks: (() => void) | undefined;
     let unlistenSync: (() => void) | undefined;
+    let unlistenInitial: (() => void) | undefined;
+    
+    // Render the startup snapshot right away if it beats the first fetch
+    TasksAPI.onTasksInitial((initial) => {
+      if (!loaded.current) setTasks(initial);
+    }).then((unlisten) => {
+      unlistenInitial = unlisten;
+      TasksAPI.frontendReady().catch(console.error);
+    });
     
     TasksAPI.onTasksChanged(() => {
       refreshTasks();
@@ -58,6 +70,7 @@ export function TasksProvider({ children }: { children: React.ReactNode }) {
     return () => {
       unlistenTasks?.();
       unlistenSync?.();
+      unlistenInitial?.();
     };
   }, []);
 
@@ -2,6 +2,7 @@ import React from "react";
 import ReactDOM from "react-dom/client";
 import App from "./App";
 import DatePickerPopup from "./components/popups/DatePickerPopup";
+import QuickAddPopup from "./components/popups/QuickAddPopup";
 
 import "./App.css";
 
@@ -9,11 +10,14 @@ import {TasksProvider} from "./context/TasksContext";
 
 // Check if we're in a popup window
 const isDatePickerPopup = window.location.pathname.startsWith("/popup/datepicker");
+const isQuickAddPopup = window.location.pathname.startsWith("/popup/quickadd");
 
 ReactDOM.createRoot(document.getElementById("root") as HTMLElement).render(
   <React.StrictMode>
     {isDatePickerPopup ? (
       <DatePickerPopup />
+    ) : isQuickAddPopup ? (
+      <QuickAddPopup />
     ) : (
       <TasksProvider>
         <App />